@@ -0,0 +1,86 @@
+use std::fmt;
+
+use crate::domain::services::CouchDbRepository;
+
+/// Result of a single connection check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Report produced by `run_connection_check`
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    /// Whether every check passed
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    fn record(&mut self, name: &'static str, result: Result<String, String>) {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        self.checks.push(CheckResult {
+            name,
+            passed,
+            detail,
+        });
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let mark = if check.passed { "PASS" } else { "FAIL" };
+            writeln!(f, "[{}] {}: {}", mark, check.name, check.detail)?;
+        }
+        let summary = if self.passed() { "passed" } else { "failed" };
+        write!(f, "Connection check {}", summary)
+    }
+}
+
+/// Check that CouchDB is reachable and the vault database exists, without starting the server
+pub async fn run_connection_check(
+    repo: &(dyn CouchDbRepository + Send + Sync),
+    dbname: &str,
+) -> CheckReport {
+    let mut report = CheckReport::default();
+
+    // The welcome message doubles as a ping of the server root
+    let server = repo
+        .server_info()
+        .await
+        .map(|info| {
+            format!(
+                "{} reachable, CouchDB {}",
+                repo.get_base_url(),
+                info.version
+            )
+        })
+        .map_err(|e| format!("{} unreachable: {}", repo.get_base_url(), e));
+    let reachable = server.is_ok();
+    report.record("server", server);
+
+    if !reachable {
+        report.record("database", Err("skipped, server unreachable".to_string()));
+        return report;
+    }
+
+    let database = match repo.list_databases().await {
+        Ok(databases) if databases.iter().any(|name| name == dbname) => {
+            Ok(format!("database '{}' exists", dbname))
+        }
+        Ok(_) => Err(format!("database '{}' does not exist", dbname)),
+        Err(e) => Err(format!("failed to list databases: {}", e)),
+    };
+    report.record("database", database);
+
+    report
+}
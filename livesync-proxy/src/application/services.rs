@@ -1,31 +1,81 @@
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
 use axum::body::Body;
 use axum::http::{HeaderMap, Response};
 use bytes::Bytes;
+use metrics::gauge;
 use serde_json::Value;
+use tokio::sync::Semaphore;
 
 use crate::domain::{
-    models::{CouchDbDocument, DomainError},
+    models::{
+        CouchDbDocument, DatabaseInfo, DocumentPolicy, DocumentTransform, DomainError, Membership,
+        ServerInfo,
+    },
     services::CouchDbRepository,
 };
 
 /// Service for handling LiveSync operations
 pub struct LiveSyncService {
     couchdb_repo: Arc<dyn CouchDbRepository + Send + Sync>,
+    document_policy: DocumentPolicy,
+    document_transform: DocumentTransform,
+    /// Limits concurrent replications; `None` means unlimited
+    replication_slots: Option<(Arc<Semaphore>, usize)>,
+    /// Cluster mode detected at startup
+    clustered: OnceLock<bool>,
 }
 
 impl LiveSyncService {
     pub fn new(couchdb_repo: Arc<dyn CouchDbRepository + Send + Sync>) -> Self {
-        Self { couchdb_repo }
+        Self {
+            couchdb_repo,
+            document_policy: DocumentPolicy::default(),
+            document_transform: DocumentTransform::default(),
+            replication_slots: None,
+            clustered: OnceLock::new(),
+        }
+    }
+
+    /// Reject replications beyond `limit` running at the same time (0 disables the limit)
+    pub fn with_max_concurrent_replications(mut self, limit: usize) -> Self {
+        self.replication_slots = (limit > 0).then(|| (Arc::new(Semaphore::new(limit)), limit));
+        self
+    }
+
+    /// Number of replications currently holding a slot (always 0 without a limit)
+    pub fn active_replications(&self) -> usize {
+        self.replication_slots
+            .as_ref()
+            .map(|(slots, limit)| limit - slots.available_permits())
+            .unwrap_or(0)
+    }
+
+    /// Set the policy documents are validated against before saving
+    pub fn with_document_policy(mut self, policy: DocumentPolicy) -> Self {
+        self.document_policy = policy;
+        self
+    }
+
+    /// Set the metadata merged into documents before saving
+    pub fn with_document_transform(mut self, transform: DocumentTransform) -> Self {
+        self.document_transform = transform;
+        self
     }
 
     /// Handle a document sync operation
     pub async fn handle_document_sync(
         &self,
         db_name: &str,
-        document: CouchDbDocument,
+        mut document: CouchDbDocument,
     ) -> Result<CouchDbDocument, DomainError> {
+        // Reject documents that violate the configured policy
+        self.document_policy.validate(&document)?;
+
+        // Add the configured audit metadata
+        self.document_transform.apply(&mut document);
+
         // Ensure the database exists
         self.couchdb_repo.ensure_database(db_name).await?;
 
@@ -42,8 +92,147 @@ impl LiveSyncService {
         target: &str,
         options: Value,
     ) -> Result<Value, DomainError> {
+        // Reject instead of queueing so callers can retry later
+        let permit = match &self.replication_slots {
+            Some((slots, limit)) => match Arc::clone(slots).try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => return Err(DomainError::TooManyReplications(*limit)),
+            },
+            None => None,
+        };
+        gauge!("replications_active").set(self.active_replications() as f64);
+
         // Perform the replication
-        self.couchdb_repo.replicate(source, target, options).await
+        let result = self.couchdb_repo.replicate(source, target, options).await;
+
+        drop(permit);
+        gauge!("replications_active").set(self.active_replications() as f64);
+        result
+    }
+
+    /// Check that CouchDB is reachable through the configured client
+    pub async fn ping(&self) -> Result<(), DomainError> {
+        self.couchdb_repo.ping().await
+    }
+
+    /// Get the version and features of the CouchDB server
+    pub async fn server_info(&self) -> Result<ServerInfo, DomainError> {
+        self.couchdb_repo.server_info().await
+    }
+
+    /// Get the nodes of the CouchDB cluster
+    pub async fn membership(&self) -> Result<Membership, DomainError> {
+        self.couchdb_repo.membership().await
+    }
+
+    /// Check whether CouchDB runs as a cluster and remember the answer for `is_clustered`
+    pub async fn detect_cluster(&self) -> Result<bool, DomainError> {
+        let clustered = self.membership().await?.is_clustered();
+        Ok(*self.clustered.get_or_init(|| clustered))
+    }
+
+    /// Whether CouchDB runs as a cluster (`None` until `detect_cluster` succeeds)
+    ///
+    /// Sequence handling differs between the two: clustered sequences are opaque strings.
+    pub fn is_clustered(&self) -> Option<bool> {
+        self.clustered.get().copied()
+    }
+
+    /// List the databases on the CouchDB server
+    pub async fn list_databases(&self) -> Result<Vec<String>, DomainError> {
+        self.couchdb_repo.list_databases().await
+    }
+
+    /// Get document counts, sizes and the update sequence of a database
+    pub async fn database_info(&self, db_name: &str) -> Result<DatabaseInfo, DomainError> {
+        self.couchdb_repo.database_info(db_name).await
+    }
+
+    /// Build the index of a view ahead of traffic and return how long it took
+    pub async fn warm_view(
+        &self,
+        db_name: &str,
+        design_doc: &str,
+        view_name: &str,
+    ) -> Result<Duration, DomainError> {
+        let start = Instant::now();
+        self.couchdb_repo
+            .warm_view(db_name, design_doc, view_name)
+            .await?;
+        Ok(start.elapsed())
+    }
+
+    /// Create each database that does not exist yet, continuing past failures
+    ///
+    /// Returns the databases that could not be ensured together with the error.
+    pub async fn ensure_databases(&self, db_names: &[String]) -> Vec<(String, DomainError)> {
+        let mut failures = Vec::new();
+        for db_name in db_names {
+            if let Err(e) = self.couchdb_repo.ensure_database(db_name).await {
+                failures.push((db_name.clone(), e));
+            }
+        }
+        failures
+    }
+
+    /// Get the tasks currently running on the CouchDB server
+    pub async fn active_tasks(&self) -> Result<Value, DomainError> {
+        self.couchdb_repo.active_tasks().await
+    }
+
+    /// Get the state of the persistent replications defined in `_replicator`
+    pub async fn scheduler_docs(&self) -> Result<Value, DomainError> {
+        self.couchdb_repo.scheduler_docs().await
+    }
+
+    /// Get the number of revisions kept per document in a database
+    pub async fn get_revs_limit(&self, db_name: &str) -> Result<u64, DomainError> {
+        self.couchdb_repo.get_revs_limit(db_name).await
+    }
+
+    /// Set the number of revisions kept per document in a database
+    ///
+    /// The limit must be a positive integer; CouchDB rejects anything else.
+    pub async fn set_revs_limit(&self, db_name: &str, limit: i64) -> Result<u64, DomainError> {
+        let limit = u64::try_from(limit)
+            .ok()
+            .filter(|limit| *limit > 0)
+            .ok_or_else(|| {
+                DomainError::InvalidMessage(format!(
+                    "Revs limit must be a positive integer, got {}",
+                    limit
+                ))
+            })?;
+        self.couchdb_repo.set_revs_limit(db_name, limit).await?;
+        Ok(limit)
+    }
+
+    /// Create a CouchDB user, creating the `_users` database first if needed
+    pub async fn create_user(
+        &self,
+        name: &str,
+        password: &str,
+        roles: Vec<String>,
+    ) -> Result<(), DomainError> {
+        if name.trim().is_empty() || password.is_empty() {
+            return Err(DomainError::InvalidMessage(
+                "User name and password must not be empty".to_string(),
+            ));
+        }
+        self.couchdb_repo.ensure_database("_users").await?;
+        self.couchdb_repo.create_user(name, password, roles).await
+    }
+
+    /// Restrict a database to the given member and admin user names
+    pub async fn set_db_security(
+        &self,
+        db_name: &str,
+        members: Vec<String>,
+        admins: Vec<String>,
+    ) -> Result<(), DomainError> {
+        self.couchdb_repo
+            .set_db_security(db_name, members, admins)
+            .await
     }
 
     /// Get the CouchDB URL for proxying requests
@@ -77,4 +266,18 @@ impl LiveSyncService {
         repo.forward_request(method, path, query, headers, body)
             .await
     }
+
+    /// ボディをバッファせずにHTTPリクエストをCouchDBに転送する
+    pub async fn forward_request_stream(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        headers: HeaderMap,
+        body: Body,
+    ) -> Result<Response<Body>, DomainError> {
+        self.couchdb_repo
+            .forward_request_stream(method, path, query, headers, body)
+            .await
+    }
 }
@@ -11,6 +11,250 @@ pub struct CouchDbDocument {
     pub data: serde_json::Value,
 }
 
+/// CouchDB welcome message returned by `GET /`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub couchdb: String,
+    pub version: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub vendor: Option<ServerVendor>,
+}
+
+/// Vendor section of the CouchDB welcome message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerVendor {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// Cluster membership returned by `GET /_membership`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Membership {
+    #[serde(default)]
+    pub all_nodes: Vec<String>,
+    #[serde(default)]
+    pub cluster_nodes: Vec<String>,
+}
+
+impl Membership {
+    /// Whether more than one node takes part in the cluster
+    pub fn is_clustered(&self) -> bool {
+        self.cluster_nodes.len() > 1
+    }
+}
+
+/// Database information returned by `GET /{db}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseInfo {
+    pub db_name: String,
+    #[serde(default)]
+    pub doc_count: u64,
+    #[serde(default)]
+    pub doc_del_count: u64,
+    /// Opaque sequence; a string since CouchDB 2.x, a number before that
+    #[serde(default)]
+    pub update_seq: serde_json::Value,
+    #[serde(default)]
+    pub sizes: DatabaseSizes,
+}
+
+/// Query parameters for fetching a single document
+///
+/// Pull replication of documents with attachments relies on `atts_since` and
+/// `att_encoding_info` so only attachments newer than the local revisions are sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GetDocumentOptions {
+    /// Fetch this revision instead of the winning one
+    pub rev: Option<String>,
+    /// Include attachment bodies only for revisions newer than these
+    pub atts_since: Vec<String>,
+    /// Include the bodies of all attachments
+    pub attachments: bool,
+    /// Report the encoding of compressed attachments
+    pub att_encoding_info: bool,
+}
+
+impl GetDocumentOptions {
+    /// The options as query parameters, omitting the ones left at their defaults
+    pub fn query_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(rev) = &self.rev {
+            params.push(("rev", rev.clone()));
+        }
+        if !self.atts_since.is_empty() {
+            params.push((
+                "atts_since",
+                serde_json::to_string(&self.atts_since).unwrap_or_default(),
+            ));
+        }
+        if self.attachments {
+            params.push(("attachments", "true".to_string()));
+        }
+        if self.att_encoding_info {
+            params.push(("att_encoding_info", "true".to_string()));
+        }
+        params
+    }
+}
+
+/// Checkpoint of a `_changes` response
+///
+/// `last_seq` is kept as raw JSON so clustered sequences such as `"23-g1AAAA..."`
+/// (or integers from CouchDB 1.x) are re-emitted exactly as CouchDB sent them.
+#[derive(Debug, Deserialize)]
+pub struct ChangesCheckpoint<'a> {
+    #[serde(borrow)]
+    pub last_seq: &'a serde_json::value::RawValue,
+}
+
+/// Sizes section of the database information, in bytes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseSizes {
+    /// Size of the database file on disk
+    #[serde(default)]
+    pub file: u64,
+    /// Uncompressed size of the database contents
+    #[serde(default)]
+    pub external: u64,
+    /// Size of live data inside the database file
+    #[serde(default)]
+    pub active: u64,
+}
+
+/// Constraints a document must satisfy before it is saved
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentPolicy {
+    /// Maximum serialized size of a document in bytes
+    #[serde(default)]
+    pub max_size_bytes: Option<usize>,
+    /// Top-level fields every document must contain
+    #[serde(default)]
+    pub required_fields: Vec<String>,
+    /// Document id prefixes that may not be written
+    #[serde(default)]
+    pub forbidden_id_prefixes: Vec<String>,
+}
+
+impl DocumentPolicy {
+    /// Check a document against the policy
+    pub fn validate(&self, doc: &CouchDbDocument) -> Result<(), DomainError> {
+        if let Some(prefix) = self
+            .forbidden_id_prefixes
+            .iter()
+            .find(|prefix| doc.id.starts_with(prefix.as_str()))
+        {
+            return Err(DomainError::InvalidMessage(format!(
+                "Document id {} uses forbidden prefix {}",
+                doc.id, prefix
+            )));
+        }
+
+        if let Some(field) = self
+            .required_fields
+            .iter()
+            .find(|field| doc.data.get(field.as_str()).is_none())
+        {
+            return Err(DomainError::InvalidMessage(format!(
+                "Document {} is missing required field {}",
+                doc.id, field
+            )));
+        }
+
+        if let Some(max_size) = self.max_size_bytes {
+            let size = serde_json::to_vec(doc)
+                .map_err(|e| {
+                    DomainError::InvalidMessage(format!("Failed to serialize document: {}", e))
+                })?
+                .len();
+            if size > max_size {
+                return Err(DomainError::InvalidMessage(format!(
+                    "Document {} is {} bytes, exceeding the limit of {} bytes",
+                    doc.id, size, max_size
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Metadata merged into documents on save, for auditing
+///
+/// Note that CouchDB rejects top-level fields starting with `_` other than its own.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentTransform {
+    /// Static fields merged into every saved document
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    /// Field that receives the time the proxy saved the document (RFC 3339)
+    #[serde(default)]
+    pub timestamp_field: Option<String>,
+}
+
+impl DocumentTransform {
+    /// Whether the transform changes anything
+    pub fn is_enabled(&self) -> bool {
+        !self.metadata.is_empty() || self.timestamp_field.is_some()
+    }
+
+    /// Merge the configured metadata into the document, leaving `_id`/`_rev` untouched
+    pub fn apply(&self, doc: &mut CouchDbDocument) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let Some(data) = doc.data.as_object_mut() else {
+            return;
+        };
+
+        let timestamp = self
+            .timestamp_field
+            .as_ref()
+            .map(|field| (field.clone(), chrono::Utc::now().to_rfc3339().into()));
+
+        for (key, value) in self.metadata.clone().into_iter().chain(timestamp) {
+            if key == "_id" || key == "_rev" {
+                continue;
+            }
+            data.insert(key, value);
+        }
+    }
+}
+
+/// Normalize a document revision given either as a `?rev=` value or an
+/// `If-Match` header value (`"1-abc"`, `W/"1-abc"`), checking the `N-hash` format
+pub fn normalize_rev(rev: &str) -> Result<String, DomainError> {
+    let rev = rev.trim();
+    let rev = rev.strip_prefix("W/").unwrap_or(rev);
+    let rev = rev
+        .strip_prefix('"')
+        .and_then(|r| r.strip_suffix('"'))
+        .unwrap_or(rev);
+
+    let valid = match rev.split_once('-') {
+        Some((generation, hash)) => {
+            !generation.is_empty()
+                && generation.bytes().all(|b| b.is_ascii_digit())
+                && generation != "0"
+                && !hash.is_empty()
+                && hash.bytes().all(|b| b.is_ascii_alphanumeric())
+        }
+        None => false,
+    };
+
+    if valid {
+        Ok(rev.to_string())
+    } else {
+        Err(DomainError::InvalidMessage(format!(
+            "Malformed document revision: {}",
+            rev
+        )))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DomainError {
     #[error("Invalid message format: {0}")]
@@ -22,6 +266,15 @@ pub enum DomainError {
     #[error("CouchDB error: {0}")]
     CouchDbError(String),
 
+    #[error("CouchDB returned status {status} with a non-JSON body: {body}")]
+    CouchDbStatus { status: u16, body: String },
+
     #[error("HTTP proxy error: {0}")]
     HttpProxyError(String),
+
+    #[error("Too many concurrent replications (limit {0})")]
+    TooManyReplications(usize),
+
+    #[error("CouchDB request timed out: {0}")]
+    Timeout(String),
 }
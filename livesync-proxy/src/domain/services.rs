@@ -4,7 +4,9 @@ use axum::http::{HeaderMap, Response};
 use bytes::Bytes;
 use serde_json::Value;
 
-use crate::domain::models::{CouchDbDocument, DomainError};
+use crate::domain::models::{
+    CouchDbDocument, DatabaseInfo, DomainError, GetDocumentOptions, Membership, ServerInfo,
+};
 
 /// Repository interface for CouchDB operations
 #[async_trait]
@@ -16,6 +18,14 @@ pub trait CouchDbRepository {
         doc_id: &str,
     ) -> Result<CouchDbDocument, DomainError>;
 
+    /// Get a document with revision and attachment options
+    async fn get_document_with_options(
+        &self,
+        db_name: &str,
+        doc_id: &str,
+        options: &GetDocumentOptions,
+    ) -> Result<CouchDbDocument, DomainError>;
+
     /// Save a document to the database
     async fn save_document(
         &self,
@@ -31,7 +41,48 @@ pub trait CouchDbRepository {
         rev: &str,
     ) -> Result<(), DomainError>;
 
+    /// Copy a document to a new id using the CouchDB `COPY` method
+    async fn copy_document(
+        &self,
+        db_name: &str,
+        src_id: &str,
+        dest_id: &str,
+    ) -> Result<CouchDbDocument, DomainError>;
+
+    /// Save a document only if no document with its id exists yet
+    ///
+    /// Returns the stored document unchanged when it already exists, so provisioning
+    /// can be re-run without overwriting anything.
+    async fn create_document_if_absent(
+        &self,
+        db_name: &str,
+        doc: CouchDbDocument,
+    ) -> Result<CouchDbDocument, DomainError>;
+
+    /// Fetch several documents at once with `_bulk_get`, skipping ones that failed
+    async fn bulk_get(
+        &self,
+        db_name: &str,
+        requests: Vec<(String, Option<String>)>,
+    ) -> Result<Vec<CouchDbDocument>, DomainError>;
+
+    /// Write several documents at once with `_bulk_docs`
+    ///
+    /// With `new_edits` set to false (as pull replication does) the supplied `_rev` and
+    /// `_revisions` are stored as-is instead of new revisions being generated. Returns
+    /// CouchDB's per-document results, which are empty in that mode.
+    async fn bulk_docs(
+        &self,
+        db_name: &str,
+        docs: Vec<CouchDbDocument>,
+        new_edits: bool,
+    ) -> Result<Vec<Value>, DomainError>;
+
     /// Query the database with a view
+    ///
+    /// Documents are returned in the order the view emits its rows; implementations
+    /// must not reorder them. A `partition` option restricts the query to that
+    /// partition of a partitioned database.
     async fn query_view(
         &self,
         db_name: &str,
@@ -40,6 +91,14 @@ pub trait CouchDbRepository {
         options: Value,
     ) -> Result<Vec<CouchDbDocument>, DomainError>;
 
+    /// Query a view with `limit=0` so CouchDB builds its index without returning rows
+    async fn warm_view(
+        &self,
+        db_name: &str,
+        design_doc: &str,
+        view_name: &str,
+    ) -> Result<(), DomainError>;
+
     /// Ensure a database exists
     async fn ensure_database(&self, db_name: &str) -> Result<(), DomainError>;
 
@@ -51,6 +110,49 @@ pub trait CouchDbRepository {
         options: Value,
     ) -> Result<Value, DomainError>;
 
+    /// Check that the server responds, using the configured probe method
+    async fn ping(&self) -> Result<(), DomainError>;
+
+    /// Get the server version and features from the welcome message
+    async fn server_info(&self) -> Result<ServerInfo, DomainError>;
+
+    /// Get the nodes of the cluster (`_membership`); servers without the endpoint count as a single node
+    async fn membership(&self) -> Result<Membership, DomainError>;
+
+    /// Get document counts, sizes and the update sequence of a database
+    async fn database_info(&self, db_name: &str) -> Result<DatabaseInfo, DomainError>;
+
+    /// List the names of all databases on the server
+    async fn list_databases(&self) -> Result<Vec<String>, DomainError>;
+
+    /// Get the tasks currently running on the server (`_active_tasks`)
+    async fn active_tasks(&self) -> Result<Value, DomainError>;
+
+    /// Get the state of the persistent replications (`_scheduler/docs`)
+    async fn scheduler_docs(&self) -> Result<Value, DomainError>;
+
+    /// Get how many revisions of each document the database keeps (`_revs_limit`)
+    async fn get_revs_limit(&self, db_name: &str) -> Result<u64, DomainError>;
+
+    /// Set how many revisions of each document the database keeps (`_revs_limit`)
+    async fn set_revs_limit(&self, db_name: &str, limit: u64) -> Result<(), DomainError>;
+
+    /// Create a CouchDB user in the `_users` database
+    async fn create_user(
+        &self,
+        name: &str,
+        password: &str,
+        roles: Vec<String>,
+    ) -> Result<(), DomainError>;
+
+    /// Replace the security object (`_security`) of a database with the given user names
+    async fn set_db_security(
+        &self,
+        db_name: &str,
+        members: Vec<String>,
+        admins: Vec<String>,
+    ) -> Result<(), DomainError>;
+
     /// Get the base URL of the CouchDB server
     fn get_base_url(&self) -> String;
 
@@ -66,4 +168,14 @@ pub trait CouchDbRepository {
         headers: HeaderMap,
         body: Bytes,
     ) -> Result<Response<Body>, DomainError>;
+
+    /// Forward an HTTP request to CouchDB, streaming the body instead of buffering it
+    async fn forward_request_stream(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        headers: HeaderMap,
+        body: Body,
+    ) -> Result<Response<Body>, DomainError>;
 }
@@ -1,2 +1,4 @@
 pub mod config;
 pub mod couchdb;
+#[cfg(unix)]
+pub mod transport;
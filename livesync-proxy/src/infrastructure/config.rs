@@ -1,57 +1,1197 @@
+use std::collections::HashMap;
 use std::env;
+use std::path::Path;
+use std::time::Duration;
 
 use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone)]
+use crate::domain::models::{DocumentPolicy, DocumentTransform};
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub couchdb: CouchDbConfig,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    #[serde(default)]
+    pub document_policy: DocumentPolicy,
+    #[serde(default)]
+    pub document_transform: DocumentTransform,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Port the server binds to when neither `PORT` nor a config file sets one
+pub const DEFAULT_PORT: u16 = 3000;
+
+/// Where the binding port was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortSource {
+    /// The `PORT` environment variable
+    Env,
+    /// `server.port` from the config files (or `APP_SERVER_PORT`)
+    ConfigFile,
+    Default,
+}
+
+impl std::fmt::Display for PortSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Env => write!(f, "PORT"),
+            Self::ConfigFile => write!(f, "config file"),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Pick the binding port: an explicit `PORT` wins over the config file, which wins over the default
+///
+/// Values that are not a valid nonzero port are ignored so the next source is used.
+pub fn resolve_port(env_port: Option<&str>, file_port: Option<u16>) -> (u16, PortSource) {
+    let valid = |port: &u16| *port != 0;
+    if let Some(port) = env_port.and_then(|p| p.trim().parse().ok()).filter(valid) {
+        return (port, PortSource::Env);
+    }
+    if let Some(port) = file_port.filter(valid) {
+        return (port, PortSource::ConfigFile);
+    }
+    (DEFAULT_PORT, PortSource::Default)
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Close connections that do not finish sending their request line and headers
+    /// within this many seconds (0 disables)
+    #[serde(default = "default_header_read_timeout_secs")]
+    pub header_read_timeout_secs: u64,
+}
+
+impl ServerConfig {
+    /// Header read timeout as a duration (None when disabled)
+    pub fn header_read_timeout(&self) -> Option<Duration> {
+        (self.header_read_timeout_secs > 0)
+            .then(|| Duration::from_secs(self.header_read_timeout_secs))
+    }
+}
+
+fn default_header_read_timeout_secs() -> u64 {
+    30
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct CouchDbConfig {
     pub url: String,
     pub username: String,
     pub password: String,
     pub dbname: String,
+    /// Additional nodes tried in order when the current node is unreachable
+    #[serde(default)]
+    pub failover_urls: Vec<String>,
+    /// Nodes with an explicit role; replicas serve read-only requests
+    #[serde(default)]
+    pub nodes: Vec<CouchDbNode>,
+    /// HTTP method used by health probes and database existence checks
+    #[serde(default)]
+    pub probe_method: ProbeMethod,
+    /// TCP and connection pool tuning shared by every upstream client
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    /// Separate clusters serving the `/db` paths under a prefix
+    #[serde(default)]
+    pub routes: Vec<CouchDbRoute>,
+    /// Databases created at startup in addition to `dbname`
+    #[serde(default)]
+    pub ensure_dbs: Vec<String>,
+    /// Maximum replications started through the proxy at the same time (0 disables the limit)
+    #[serde(default)]
+    pub max_concurrent_replications: usize,
+}
+
+impl CouchDbConfig {
+    /// Databases to create at startup: `dbname` followed by `ensure_dbs`, without duplicates
+    pub fn databases_to_ensure(&self) -> Vec<String> {
+        let mut databases = vec![self.dbname.clone()];
+        for db in &self.ensure_dbs {
+            if !databases.contains(db) {
+                databases.push(db.clone());
+            }
+        }
+        databases
+    }
+}
+
+/// A CouchDB cluster that serves the `/db` paths under a prefix
+///
+/// Requests matching no route go to the default `url`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CouchDbRoute {
+    /// Path prefix below `/db`, matched on whole segments (`team-a` matches `/db/team-a/...`)
+    pub prefix: String,
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// TCP and connection pool settings applied to every CouchDB HTTP client
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ConnectionConfig {
+    /// Interval of TCP keep-alive probes in seconds (0 disables keep-alive)
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+    /// Disable Nagle's algorithm to reduce latency
+    #[serde(default = "default_true")]
+    pub tcp_nodelay: bool,
+    /// How long idle pooled connections are kept in seconds
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    /// Lowest TLS version negotiated with an `https://` CouchDB
+    #[serde(default)]
+    pub min_tls_version: TlsVersion,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
+            tcp_nodelay: true,
+            pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+            min_tls_version: TlsVersion::default(),
+        }
+    }
+}
+
+/// Minimum TLS protocol version for connections to CouchDB
+///
+/// TLS 1.3 cannot be required because the native TLS backend does not support it as a minimum.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    Tls1_0,
+    #[serde(rename = "1.1")]
+    Tls1_1,
+    #[default]
+    #[serde(rename = "1.2")]
+    Tls1_2,
+}
+
+impl TlsVersion {
+    /// The version as understood by the HTTP client
+    pub fn to_reqwest(self) -> reqwest::tls::Version {
+        match self {
+            Self::Tls1_0 => reqwest::tls::Version::TLS_1_0,
+            Self::Tls1_1 => reqwest::tls::Version::TLS_1_1,
+            Self::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+        }
+    }
+}
+
+impl std::str::FromStr for TlsVersion {
+    type Err = String;
+
+    /// Accepts `1.2` as well as `TLSv1.2`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let lower = value.trim().to_ascii_lowercase();
+        match lower.trim_start_matches("tlsv").trim_start_matches("tls") {
+            "1.0" | "1" => Ok(Self::Tls1_0),
+            "1.1" => Ok(Self::Tls1_1),
+            "1.2" => Ok(Self::Tls1_2),
+            _ => Err(format!("unsupported minimum TLS version: {}", value)),
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// TCP keep-alive interval, or `None` when disabled
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        (self.tcp_keepalive_secs > 0).then(|| Duration::from_secs(self.tcp_keepalive_secs))
+    }
+
+    pub fn pool_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.pool_idle_timeout_secs)
+    }
+}
+
+/// HTTP method used to probe CouchDB
+///
+/// `Head` falls back to `GET` when a reverse proxy answers `405 Method Not Allowed`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeMethod {
+    #[default]
+    Head,
+    Get,
+}
+
+/// Body format of the errors generated by the proxy itself
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorResponseFormat {
+    /// CouchDB-style `{"error": ..., "reason": ...}`
+    #[default]
+    Json,
+    /// `error: ...` and `reason: ...` lines as `text/plain`
+    Text,
+}
+
+/// Role of an additional CouchDB node
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    /// Receives all requests when it is the current primary, and writes always
+    #[default]
+    Primary,
+    /// Receives read-only requests (`_all_docs`, views, `_changes`) by weight
+    Replica,
+}
+
+/// An additional CouchDB node and its role
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct CouchDbNode {
+    pub url: String,
+    #[serde(default)]
+    pub role: NodeRole,
+    /// Relative share of read-only requests for replicas
+    #[serde(default = "default_node_weight")]
+    pub weight: u32,
+}
+
+/// Behaviour of the `/db` proxy endpoints
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ProxyConfig {
+    /// Add the `X-LiveSync-Proxy: <version>` header to proxied responses
+    #[serde(default = "default_true")]
+    pub version_header: bool,
+    /// Upstream proxies whose `X-Forwarded-For` header is trusted
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+    /// Upper bound for the `X-Upstream-Timeout-Seconds` override
+    #[serde(default = "default_max_upstream_timeout_secs")]
+    pub max_upstream_timeout_secs: u64,
+    /// Maximum concurrent `/db` requests per client (0 disables the limit)
+    #[serde(default)]
+    pub max_concurrent_per_client: usize,
+    /// Maximum concurrent longpoll/continuous `_changes` feeds across all clients (0 disables the limit)
+    #[serde(default)]
+    pub max_longpoll_connections: usize,
+    /// Requests slower than this are logged at warn level (0 disables the log)
+    #[serde(default = "default_slow_request_threshold_ms")]
+    pub slow_request_threshold_ms: u64,
+    /// Rewrite requests for `/db` itself to the default database
+    #[serde(default)]
+    pub map_root_to_default_db: bool,
+    /// Maximum number of inbound request headers (0 disables the limit)
+    #[serde(default = "default_max_header_count")]
+    pub max_header_count: usize,
+    /// Maximum total size of inbound header names and values in bytes (0 disables the limit)
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    /// Which upstream response headers are forwarded to clients
+    #[serde(default)]
+    pub response_header_policy: ResponseHeaderPolicy,
+    /// Log small JSON response bodies pretty-printed at debug level
+    #[serde(default)]
+    pub pretty_debug_json: bool,
+    /// Send the client-facing path and query to CouchDB in `X-Original-Path` / `X-Original-Query`
+    #[serde(default)]
+    pub forward_original_path: bool,
+    /// Answer requests from origins outside the CORS allowlist with a 403 JSON error
+    #[serde(default)]
+    pub cors_debug: bool,
+    /// Body format of errors generated by the proxy (upstream errors are passed through)
+    #[serde(default)]
+    pub error_response_format: ErrorResponseFormat,
+    /// Retry a GET/HEAD answered with 429 once when `Retry-After` is at most this many seconds (0 disables)
+    #[serde(default)]
+    pub retry_after_max_secs: u64,
+    /// Answer `/db` with 503 without contacting CouchDB while the health check marks it unavailable
+    #[serde(default)]
+    pub fail_fast_when_unavailable: bool,
+    /// Keep the last N proxied requests (auth headers removed) for `/api/replay` (0 disables)
+    #[serde(default)]
+    pub capture_requests: usize,
+    /// Queue up to N document writes while CouchDB is unavailable and replay them on recovery (0 disables)
+    #[serde(default)]
+    pub write_queue_capacity: usize,
+    /// Stream request bodies with a `Content-Length` above this many bytes instead of buffering them (0 disables)
+    #[serde(default)]
+    pub stream_request_body_threshold: u64,
+    /// HTML file served for `/` and static files during maintenance (embedded page when unset)
+    #[serde(default)]
+    pub maintenance_page_path: Option<String>,
+    /// Upstream credentials used for `X-Auth-User` sent by a trusted proxy (empty disables the mode)
+    #[serde(default)]
+    pub forwarded_auth_users: Vec<ForwardedAuthUser>,
+    /// Fault and latency injection for resilience testing (never enabled by default)
+    #[serde(default)]
+    pub chaos: ChaosConfig,
+}
+
+impl ProxyConfig {
+    /// Serialize the proxy settings with the forwarded users' passwords replaced by their length
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        for (index, user) in self.forwarded_auth_users.iter().enumerate() {
+            value["forwarded_auth_users"][index]["password"] = redact_secret(Some(&user.password));
+        }
+        value
+    }
+}
+
+/// Replace a secret with its length so that it can be shown to operators
+fn redact_secret(secret: Option<&String>) -> serde_json::Value {
+    match secret {
+        Some(secret) => serde_json::json!({ "redacted": true, "length": secret.len() }),
+        None => serde_json::Value::Null,
+    }
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            version_header: true,
+            trusted_proxies: Vec::new(),
+            max_upstream_timeout_secs: default_max_upstream_timeout_secs(),
+            max_concurrent_per_client: 0,
+            max_longpoll_connections: 0,
+            slow_request_threshold_ms: default_slow_request_threshold_ms(),
+            map_root_to_default_db: false,
+            max_header_count: default_max_header_count(),
+            max_header_bytes: default_max_header_bytes(),
+            response_header_policy: ResponseHeaderPolicy::default(),
+            pretty_debug_json: false,
+            forward_original_path: false,
+            cors_debug: false,
+            error_response_format: ErrorResponseFormat::Json,
+            retry_after_max_secs: 0,
+            fail_fast_when_unavailable: false,
+            capture_requests: 0,
+            write_queue_capacity: 0,
+            stream_request_body_threshold: 0,
+            maintenance_page_path: None,
+            forwarded_auth_users: Vec::new(),
+            chaos: ChaosConfig::default(),
+        }
+    }
+}
+
+/// CouchDB credentials injected for a user authenticated by a trusted proxy
+///
+/// The proxy in front sends the user name in `X-Auth-User`; requests for
+/// users not listed here fall back to the default credentials.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ForwardedAuthUser {
+    /// Value of the `X-Auth-User` header
+    pub user: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Faults injected into `/db` requests before they are forwarded
+///
+/// Nothing is injected unless `enabled` is set explicitly, whatever the probabilities are.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ChaosConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Probability (0.0-1.0) of answering with an injected 502
+    #[serde(default)]
+    pub fault_probability: f64,
+    /// Probability (0.0-1.0) of delaying the request by `delay_ms`
+    #[serde(default)]
+    pub delay_probability: f64,
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+impl ChaosConfig {
+    /// Whether any fault can actually be injected
+    pub fn is_active(&self) -> bool {
+        self.enabled
+            && (self.fault_probability > 0.0 || (self.delay_probability > 0.0 && self.delay_ms > 0))
+    }
+}
+
+/// Internal CouchDB headers stripped from responses unless configured otherwise
+pub const DEFAULT_DENIED_RESPONSE_HEADERS: [&str; 2] = ["x-couch-node", "x-couchdb-body-time"];
+
+/// Filter applied to upstream response headers on both proxy paths
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "mode", content = "headers", rename_all = "lowercase")]
+pub enum ResponseHeaderPolicy {
+    /// Forward only the listed headers (plus `Content-Type`, `Content-Length` and `Retry-After`)
+    Allow(Vec<String>),
+    /// Forward everything except the listed headers
+    Deny(Vec<String>),
+}
+
+impl Default for ResponseHeaderPolicy {
+    fn default() -> Self {
+        Self::Deny(
+            DEFAULT_DENIED_RESPONSE_HEADERS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        )
+    }
+}
+
+impl ResponseHeaderPolicy {
+    /// Whether a response header with this name may be forwarded
+    pub fn permits(&self, name: &str) -> bool {
+        let listed = |names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        match self {
+            Self::Allow(names) => {
+                name.eq_ignore_ascii_case("content-type")
+                    || name.eq_ignore_ascii_case("content-length")
+                    || name.eq_ignore_ascii_case("retry-after")
+                    || listed(names)
+            }
+            Self::Deny(names) => !listed(names),
+        }
+    }
+}
+
+/// Prometheus metrics exposed at `/metrics`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MetricsConfig {
+    /// Install the Prometheus recorder; `/metrics` returns 503 when disabled
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Log a summary of the request counts every this many seconds (0 disables)
+    #[serde(default)]
+    pub summary_log_interval_secs: u64,
+    /// Log the final Prometheus output when the server shuts down
+    #[serde(default)]
+    pub flush_on_shutdown: bool,
+    /// Pushgateway base URL the final output is also pushed to on shutdown
+    #[serde(default)]
+    pub pushgateway_url: Option<String>,
+}
+
+impl MetricsConfig {
+    /// Interval of the request summary log (None when disabled)
+    pub fn summary_log_interval(&self) -> Option<Duration> {
+        (self.summary_log_interval_secs > 0)
+            .then(|| Duration::from_secs(self.summary_log_interval_secs))
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            summary_log_interval_secs: 0,
+            flush_on_shutdown: false,
+            pushgateway_url: None,
+        }
+    }
+}
+
+/// Health reporting at `/health`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HealthConfig {
+    /// Report `degraded` when the last background check is older than this (0 disables)
+    #[serde(default = "default_max_status_age_secs")]
+    pub max_status_age_secs: u64,
+    /// Random spread (0.0-1.0) applied to the check interval and backoff
+    #[serde(default = "default_health_jitter")]
+    pub jitter_fraction: f64,
+    /// `/health/ready` fails until the default database has at least this many documents (0 disables)
+    #[serde(default)]
+    pub min_doc_count: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_status_age_secs: default_max_status_age_secs(),
+            jitter_fraction: default_health_jitter(),
+            min_doc_count: 0,
+        }
+    }
+}
+
+/// Access control for the management (`/api/*`) endpoints
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct AdminConfig {
+    /// Bearer token required by admin endpoints; admin endpoints are disabled when unset
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_slow_request_threshold_ms() -> u64 {
+    2000
+}
+
+fn default_health_jitter() -> f64 {
+    0.2
+}
+
+fn default_max_status_age_secs() -> u64 {
+    600
+}
+
+fn default_tcp_keepalive_secs() -> u64 {
+    30
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_header_count() -> usize {
+    100
+}
+
+fn default_max_header_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_node_weight() -> u32 {
+    1
+}
+
+fn default_max_upstream_timeout_secs() -> u64 {
+    600
+}
+
+/// Environment variables the configuration is built from
+///
+/// Values in `overrides` (read from a `.env` file on reload) take precedence over the
+/// process environment, which is only read and never modified.
+#[derive(Debug, Default)]
+struct EnvVars {
+    overrides: HashMap<String, String>,
+    /// Whether variables missing from `overrides` are read from the process environment
+    inherit: bool,
+}
+
+impl EnvVars {
+    /// The process environment as it is
+    fn process() -> Self {
+        Self::with_overrides(HashMap::new())
+    }
+
+    /// The process environment with `overrides` on top
+    fn with_overrides(overrides: HashMap<String, String>) -> Self {
+        Self {
+            overrides,
+            inherit: true,
+        }
+    }
+
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        match self.overrides.get(name) {
+            Some(value) => Ok(value.clone()),
+            None if self.inherit => env::var(name),
+            None => Err(env::VarError::NotPresent),
+        }
+    }
+
+    /// Read and parse a value, ignoring unparsable values
+    fn parse<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.var(name).ok().and_then(|v| v.trim().parse().ok())
+    }
+
+    /// Read a boolean flag (`1`/`true`/`yes`/`on` are truthy)
+    fn flag(&self, name: &str, default: bool) -> bool {
+        match self.var(name) {
+            Ok(value) => matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            ),
+            Err(_) => default,
+        }
+    }
+
+    /// Read a comma-separated list, skipping empty entries
+    fn list(&self, name: &str) -> Vec<String> {
+        self.var(name)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Read the variables of a `.env` file without applying them to the process environment
+fn read_dotenv(iter: dotenvy::Result<dotenvy::Iter<std::fs::File>>) -> HashMap<String, String> {
+    let iter = match iter {
+        Ok(iter) => iter,
+        Err(e) => {
+            tracing::debug!("No .env file loaded on reload: {}", e);
+            return HashMap::new();
+        }
+    };
+    iter.filter_map(|item| {
+        item.map_err(|e| tracing::warn!("Skipping invalid line in .env: {}", e))
+            .ok()
+    })
+    .collect()
+}
+
+/// Overlay the values set through the environment onto the values from the config files
+///
+/// A value counts as set through the environment when it differs from the value an
+/// empty environment produces (`defaults`), so unset variables keep the file values.
+fn overlay_env(
+    files: &mut serde_json::Value,
+    env: serde_json::Value,
+    defaults: &serde_json::Value,
+) {
+    match (files, env) {
+        (serde_json::Value::Object(files), serde_json::Value::Object(env)) => {
+            for (key, value) in env {
+                match files.get_mut(&key) {
+                    Some(file_value) => overlay_env(file_value, value, &defaults[&key]),
+                    None => {
+                        files.insert(key, value);
+                    }
+                }
+            }
+        }
+        (files, env) => {
+            if env != *defaults {
+                *files = env;
+            }
+        }
+    }
+}
+
+/// Remove the `user:pass@` part of a URL, if any
+fn strip_credentials(value: &str) -> String {
+    match url::Url::parse(value) {
+        Ok(mut url) if value.contains('@') => {
+            url.set_username("").unwrap_or_default();
+            url.set_password(None).unwrap_or_default();
+            url.to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Remove any credentials from a CouchDB URL and make sure it ends with a slash
+fn clean_node_url(value: &str) -> String {
+    let clean_url = strip_credentials(value);
+
+    if clean_url.ends_with('/') {
+        clean_url
+    } else {
+        format!("{}/", clean_url)
+    }
+}
+
+/// Parse `COUCHDB_NODES` entries of the form `url;role=replica;weight=2`
+pub fn parse_couchdb_nodes(value: &str) -> Vec<CouchDbNode> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let mut node = CouchDbNode {
+                url: clean_node_url(parts.next()?),
+                role: NodeRole::default(),
+                weight: default_node_weight(),
+            };
+            for option in parts {
+                let valid = match option.split_once('=') {
+                    Some(("role", "primary")) => {
+                        node.role = NodeRole::Primary;
+                        true
+                    }
+                    Some(("role", "replica")) => {
+                        node.role = NodeRole::Replica;
+                        true
+                    }
+                    Some(("weight", weight)) => weight.parse().map(|w| node.weight = w).is_ok(),
+                    _ => false,
+                };
+                if !valid {
+                    tracing::warn!("Ignoring invalid COUCHDB_NODES entry: {}", entry);
+                    return None;
+                }
+            }
+            Some(node)
+        })
+        .collect()
+}
+
+/// Parse `COUCHDB_ROUTES` entries of the form `prefix=url`
+///
+/// Credentials in the URL are used for that backend; otherwise the default ones apply.
+pub fn parse_couchdb_routes(
+    value: &str,
+    default_username: &str,
+    default_password: &str,
+) -> Vec<CouchDbRoute> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let Some((prefix, url)) = entry.split_once('=') else {
+                tracing::warn!("Ignoring invalid COUCHDB_ROUTES entry: {}", entry);
+                return None;
+            };
+            let mut username = default_username.to_string();
+            let mut password = default_password.to_string();
+            if let Ok(parsed) = url::Url::parse(url.trim()) {
+                if !parsed.username().is_empty() {
+                    username = parsed.username().to_string();
+                    password = parsed.password().unwrap_or_default().to_string();
+                }
+            }
+            Some(CouchDbRoute {
+                prefix: prefix
+                    .trim()
+                    .trim_end_matches("/*")
+                    .trim_matches('/')
+                    .to_string(),
+                url: clean_node_url(url.trim()),
+                username,
+                password,
+            })
+        })
+        .collect()
+}
+
+/// Parse a comma-separated list of `key=value` pairs into JSON string fields
+pub fn parse_metadata_fields(value: &str) -> serde_json::Map<String, serde_json::Value> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Some((
+                key.trim().to_string(),
+                serde_json::Value::String(value.trim().to_string()),
+            )),
+            _ => {
+                tracing::warn!("Ignoring invalid DOC_METADATA_FIELDS entry: {}", entry);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse `allow:<headers>` or `deny:<headers>`; a bare list of headers is a denylist
+pub fn parse_response_header_policy(value: &str) -> ResponseHeaderPolicy {
+    let (mode, list) = match value.trim().split_once(':') {
+        Some((mode, list)) => (mode.trim().to_ascii_lowercase(), list),
+        None => ("deny".to_string(), value),
+    };
+    let headers = list
+        .split(',')
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    match mode.as_str() {
+        "allow" => ResponseHeaderPolicy::Allow(headers),
+        "deny" => ResponseHeaderPolicy::Deny(headers),
+        other => {
+            tracing::warn!(
+                "Ignoring unknown RESPONSE_HEADER_POLICY mode '{}', using the default denylist",
+                other
+            );
+            ResponseHeaderPolicy::default()
+        }
+    }
+}
+
+/// Parse a comma-separated list of CIDRs or plain IP addresses
+pub fn parse_trusted_proxies(value: &str) -> Vec<IpNet> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<std::net::IpAddr>().map(IpNet::from))
+                .map_err(|_| tracing::warn!("Ignoring invalid TRUSTED_PROXIES entry: {}", entry))
+                .ok()
+        })
+        .collect()
+}
+
+/// Parse `FORWARDED_AUTH_USERS` entries of the form `user=couchdb_user:password`
+pub fn parse_forwarded_auth_users(value: &str) -> Vec<ForwardedAuthUser> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry.split_once('=').and_then(|(user, credentials)| {
+                let (username, password) = credentials.split_once(':')?;
+                Some(ForwardedAuthUser {
+                    user: user.trim().to_string(),
+                    username: username.trim().to_string(),
+                    password: password.to_string(),
+                })
+            });
+            if parsed.is_none() {
+                // The entry may contain a password, so it is not logged
+                tracing::warn!("Ignoring invalid FORWARDED_AUTH_USERS entry");
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// Differences found when the configuration is reloaded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigChanges {
+    /// Changed `proxy` settings, which take effect on the next request
+    pub applied: Vec<&'static str>,
+    /// Changed sections that only take effect after a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+impl ConfigChanges {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
 }
 
 impl AppConfig {
     pub fn load() -> Result<Self, ConfigError> {
+        // Deserialize into our config struct
+        Self::layered_config()?.try_deserialize()
+    }
+
+    /// Build the layered configuration from the config files and `APP_`/`COUCHDB_` variables
+    fn layered_config() -> Result<Config, ConfigError> {
+        Self::file_config_builder(&EnvVars::process())
+            // Add environment variables (with prefix APP_)
+            .add_source(Environment::with_prefix("APP").separator("_"))
+            // Override with specific environment variables for CouchDB
+            .add_source(Environment::with_prefix("COUCHDB").separator("_"))
+            .build()
+    }
+
+    /// Start a configuration from `config/default` and `config/{RUN_ENV}`
+    fn file_config_builder(vars: &EnvVars) -> config::ConfigBuilder<config::builder::DefaultState> {
         // Get the environment (default is development)
-        let env = env::var("RUN_ENV").unwrap_or_else(|_| "development".into());
+        let env = vars.var("RUN_ENV").unwrap_or_else(|_| "development".into());
 
         // Start with default configuration
-        let config = Config::builder()
+        Config::builder()
             // Load default configuration from files
             .add_source(File::with_name("config/default").required(false))
             // Load environment-specific configuration
             .add_source(File::with_name(&format!("config/{}", env)).required(false))
-            // Add environment variables (with prefix APP_)
-            .add_source(Environment::with_prefix("APP").separator("_"))
-            // Override with specific environment variables for CouchDB
-            .add_source(Environment::with_prefix("COUCHDB").separator("_"))
-            .build()?;
+    }
 
-        // Deserialize into our config struct
-        config.try_deserialize()
+    /// Resolve the port the server binds to and log which source it came from
+    pub fn resolve_server_port() -> (u16, PortSource) {
+        let file_port = Self::layered_config()
+            .and_then(|config| config.get::<u16>("server.port"))
+            .ok();
+        let (port, source) = resolve_port(env::var("PORT").ok().as_deref(), file_port);
+        tracing::info!("Resolved server port {} from {}", port, source);
+        (port, source)
+    }
+
+    /// Check that the resolved configuration is usable
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let node_urls = std::iter::once(&self.couchdb.url)
+            .chain(&self.couchdb.failover_urls)
+            .chain(self.couchdb.nodes.iter().map(|node| &node.url))
+            .chain(self.couchdb.routes.iter().map(|route| &route.url));
+        for node_url in node_urls {
+            // Only the primary node may be reached over a Unix socket
+            if node_url == &self.couchdb.url && node_url.starts_with("unix://") {
+                if node_url
+                    .trim_start_matches("unix://")
+                    .trim_matches('/')
+                    .is_empty()
+                {
+                    return Err(ConfigError::Message(format!(
+                        "CouchDB URL '{}' must name a socket path",
+                        node_url
+                    )));
+                }
+                continue;
+            }
+
+            let url = url::Url::parse(node_url).map_err(|e| {
+                ConfigError::Message(format!("Invalid CouchDB URL '{}': {}", node_url, e))
+            })?;
+
+            if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+                return Err(ConfigError::Message(format!(
+                    "CouchDB URL '{}' must be an http(s) URL with a host",
+                    node_url
+                )));
+            }
+        }
+
+        if let Some(route) = self
+            .couchdb
+            .routes
+            .iter()
+            .find(|route| route.prefix.is_empty())
+        {
+            return Err(ConfigError::Message(format!(
+                "CouchDB route to '{}' must have a non-empty prefix",
+                route.url
+            )));
+        }
+
+        if self.server.port == 0 {
+            return Err(ConfigError::Message(
+                "Server port must be nonzero".to_string(),
+            ));
+        }
+
+        // A username without a password (or vice versa) can never authenticate
+        if self.couchdb.username.is_empty() != self.couchdb.password.is_empty() {
+            return Err(ConfigError::Message(
+                "CouchDB username and password must both be set or both be empty".to_string(),
+            ));
+        }
+
+        if self.couchdb.dbname.is_empty() {
+            return Err(ConfigError::Message(
+                "CouchDB database name must not be empty".to_string(),
+            ));
+        }
+
+        let chaos = &self.proxy.chaos;
+        for probability in [chaos.fault_probability, chaos.delay_probability] {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(ConfigError::Message(format!(
+                    "Chaos probabilities must be between 0.0 and 1.0, got {}",
+                    probability
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the configuration with secrets replaced by their length
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        value["couchdb"]["password"] = redact_secret(Some(&self.couchdb.password));
+        for (index, route) in self.couchdb.routes.iter().enumerate() {
+            value["couchdb"]["routes"][index]["password"] = redact_secret(Some(&route.password));
+        }
+        value["proxy"] = self.proxy.redacted();
+        value["admin"]["token"] = redact_secret(self.admin.token.as_ref());
+        // Pushgateway URLs often carry basic auth credentials
+        value["metrics"]["pushgateway_url"] = self
+            .metrics
+            .pushgateway_url
+            .as_deref()
+            .map(strip_credentials)
+            .into();
+        value
+    }
+
+    /// Compare with a newly loaded configuration and classify what changed
+    pub fn changes(&self, new: &AppConfig) -> ConfigChanges {
+        let mut changes = ConfigChanges::default();
+
+        let (old_proxy, new_proxy) = (&self.proxy, &new.proxy);
+        let proxy_fields = [
+            (
+                "version_header",
+                old_proxy.version_header != new_proxy.version_header,
+            ),
+            (
+                "trusted_proxies",
+                old_proxy.trusted_proxies != new_proxy.trusted_proxies,
+            ),
+            (
+                "max_upstream_timeout_secs",
+                old_proxy.max_upstream_timeout_secs != new_proxy.max_upstream_timeout_secs,
+            ),
+            (
+                "max_concurrent_per_client",
+                old_proxy.max_concurrent_per_client != new_proxy.max_concurrent_per_client,
+            ),
+            (
+                "max_longpoll_connections",
+                old_proxy.max_longpoll_connections != new_proxy.max_longpoll_connections,
+            ),
+            (
+                "slow_request_threshold_ms",
+                old_proxy.slow_request_threshold_ms != new_proxy.slow_request_threshold_ms,
+            ),
+            (
+                "map_root_to_default_db",
+                old_proxy.map_root_to_default_db != new_proxy.map_root_to_default_db,
+            ),
+            (
+                "max_header_count",
+                old_proxy.max_header_count != new_proxy.max_header_count,
+            ),
+            (
+                "max_header_bytes",
+                old_proxy.max_header_bytes != new_proxy.max_header_bytes,
+            ),
+            (
+                "response_header_policy",
+                old_proxy.response_header_policy != new_proxy.response_header_policy,
+            ),
+            (
+                "pretty_debug_json",
+                old_proxy.pretty_debug_json != new_proxy.pretty_debug_json,
+            ),
+            (
+                "forward_original_path",
+                old_proxy.forward_original_path != new_proxy.forward_original_path,
+            ),
+            ("cors_debug", old_proxy.cors_debug != new_proxy.cors_debug),
+            (
+                "error_response_format",
+                old_proxy.error_response_format != new_proxy.error_response_format,
+            ),
+            (
+                "retry_after_max_secs",
+                old_proxy.retry_after_max_secs != new_proxy.retry_after_max_secs,
+            ),
+            (
+                "fail_fast_when_unavailable",
+                old_proxy.fail_fast_when_unavailable != new_proxy.fail_fast_when_unavailable,
+            ),
+            (
+                "capture_requests",
+                old_proxy.capture_requests != new_proxy.capture_requests,
+            ),
+            (
+                "write_queue_capacity",
+                old_proxy.write_queue_capacity != new_proxy.write_queue_capacity,
+            ),
+            (
+                "stream_request_body_threshold",
+                old_proxy.stream_request_body_threshold != new_proxy.stream_request_body_threshold,
+            ),
+            (
+                "maintenance_page_path",
+                old_proxy.maintenance_page_path != new_proxy.maintenance_page_path,
+            ),
+            (
+                "forwarded_auth_users",
+                old_proxy.forwarded_auth_users != new_proxy.forwarded_auth_users,
+            ),
+            ("chaos", old_proxy.chaos != new_proxy.chaos),
+        ];
+        changes.applied = proxy_fields
+            .into_iter()
+            .filter_map(|(name, changed)| changed.then_some(name))
+            .collect();
+
+        let sections = [
+            ("server", self.server != new.server),
+            ("couchdb", self.couchdb != new.couchdb),
+            (
+                "document_policy",
+                self.document_policy != new.document_policy,
+            ),
+            (
+                "document_transform",
+                self.document_transform != new.document_transform,
+            ),
+            ("admin", self.admin != new.admin),
+            ("metrics", self.metrics != new.metrics),
+            ("health", self.health != new.health),
+        ];
+        changes.restart_required = sections
+            .into_iter()
+            .filter_map(|(name, changed)| changed.then_some(name))
+            .collect();
+
+        changes
+    }
+
+    /// Re-read `.env` and the config files and build the configuration again
+    ///
+    /// The environment of a running process cannot be changed from outside, so a
+    /// reload only sees new values written to the `.env` file (which take precedence
+    /// over the process environment) and to the config files. The process environment
+    /// is left untouched.
+    pub fn reload_from_env() -> Self {
+        Self::reload_with(EnvVars::with_overrides(read_dotenv(dotenvy::dotenv_iter())))
+    }
+
+    /// Like `reload_from_env`, reading the `.env` file at `path`
+    pub fn reload_from_dotenv_file(path: &Path) -> Self {
+        Self::reload_with(EnvVars::with_overrides(read_dotenv(
+            dotenvy::from_path_iter(path),
+        )))
+    }
+
+    /// Build the configuration from the config files with the environment on top
+    fn reload_with(vars: EnvVars) -> Self {
+        let from_env = Self::from_vars(&vars);
+        let files = match Self::file_config_builder(&vars)
+            .build()
+            .and_then(|config| config.try_deserialize::<serde_json::Value>())
+        {
+            Ok(files) => files,
+            Err(e) => {
+                tracing::warn!("Failed to read config files on reload: {}", e);
+                return from_env;
+            }
+        };
+        let (Ok(env), Ok(defaults)) = (
+            serde_json::to_value(&from_env),
+            serde_json::to_value(Self::from_vars(&EnvVars::default())),
+        ) else {
+            return from_env;
+        };
+
+        let mut merged = files;
+        overlay_env(&mut merged, env, &defaults);
+        serde_json::from_value(merged).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring config files on reload: {}", e);
+            from_env
+        })
     }
 
     /// Create a config object from environment variables directly (for containerized deployment)
     pub fn from_env() -> Self {
-        let couchdb_url =
-            env::var("COUCHDB_URL").unwrap_or_else(|_| "http://couchdb:5984".to_string());
+        Self::from_vars(&EnvVars::process())
+    }
+
+    fn from_vars(vars: &EnvVars) -> Self {
+        // COUCHDB_URLS takes precedence; its first entry is the primary node
+        let mut couchdb_urls = vars.list("COUCHDB_URLS");
+        let couchdb_url = if couchdb_urls.is_empty() {
+            vars.var("COUCHDB_URL")
+                .unwrap_or_else(|_| "http://couchdb:5984".to_string())
+        } else {
+            couchdb_urls.remove(0)
+        };
 
         // Parse the CouchDB URL to extract auth if present
-        let mut username = env::var("COUCHDB_USER").unwrap_or_else(|_| "admin".to_string());
-        let mut password = env::var("COUCHDB_PASSWORD").unwrap_or_else(|_| "secret".to_string());
+        let mut username = vars
+            .var("COUCHDB_USER")
+            .unwrap_or_else(|_| "admin".to_string());
+        let mut password = vars
+            .var("COUCHDB_PASSWORD")
+            .unwrap_or_else(|_| "secret".to_string());
 
         if couchdb_url.contains('@') {
             if let Ok(url) = url::Url::parse(&couchdb_url) {
@@ -64,41 +1204,149 @@ impl AppConfig {
             }
         }
 
-        // Clean the URL if it contains auth
-        let clean_url = if couchdb_url.contains('@') {
-            if let Ok(mut url) = url::Url::parse(&couchdb_url) {
-                url.set_username("").unwrap_or_default();
-                url.set_password(None).unwrap_or_default();
-                url.to_string()
-            } else {
-                couchdb_url
-            }
-        } else {
-            couchdb_url
-        };
-
-        // Ensure the URL ends with a slash
-        let url_with_slash = if clean_url.ends_with('/') {
-            clean_url
-        } else {
-            format!("{}/", clean_url)
-        };
+        // Clean the URL if it contains auth and ensure it ends with a slash
+        let url_with_slash = clean_node_url(&couchdb_url);
+        let failover_urls = couchdb_urls.iter().map(|url| clean_node_url(url)).collect();
+        let routes = vars
+            .var("COUCHDB_ROUTES")
+            .map(|value| parse_couchdb_routes(&value, &username, &password))
+            .unwrap_or_default();
 
-        let dbname = env::var("COUCHDB_DBNAME").unwrap_or_else(|_| "obsidian".to_string());
+        // DEFAULT_DB is accepted as an alias of COUCHDB_DBNAME and takes precedence
+        let dbname = vars
+            .var("DEFAULT_DB")
+            .or_else(|_| vars.var("COUCHDB_DBNAME"))
+            .unwrap_or_else(|_| "obsidian".to_string());
 
         AppConfig {
             server: ServerConfig {
-                host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-                port: env::var("PORT")
-                    .ok()
-                    .and_then(|p| p.parse().ok())
-                    .unwrap_or(3000),
+                host: vars.var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
+                port: Self::resolve_server_port().0,
+                header_read_timeout_secs: vars
+                    .parse("HEADER_READ_TIMEOUT_SECS")
+                    .unwrap_or_else(default_header_read_timeout_secs),
             },
             couchdb: CouchDbConfig {
                 url: url_with_slash,
                 username,
                 password,
                 dbname,
+                failover_urls,
+                nodes: vars
+                    .var("COUCHDB_NODES")
+                    .map(|value| parse_couchdb_nodes(&value))
+                    .unwrap_or_default(),
+                probe_method: match vars.var("COUCHDB_PROBE_METHOD") {
+                    Ok(value) if value.trim().eq_ignore_ascii_case("get") => ProbeMethod::Get,
+                    _ => ProbeMethod::Head,
+                },
+                connection: ConnectionConfig {
+                    tcp_keepalive_secs: vars
+                        .parse("COUCHDB_TCP_KEEPALIVE_SECS")
+                        .unwrap_or_else(default_tcp_keepalive_secs),
+                    tcp_nodelay: vars.flag("COUCHDB_TCP_NODELAY", true),
+                    pool_idle_timeout_secs: vars
+                        .parse("COUCHDB_POOL_IDLE_TIMEOUT_SECS")
+                        .unwrap_or_else(default_pool_idle_timeout_secs),
+                    min_tls_version: vars.parse("MIN_TLS_VERSION").unwrap_or_default(),
+                },
+                routes,
+                ensure_dbs: vars.list("ENSURE_DBS"),
+                max_concurrent_replications: vars.parse("MAX_CONCURRENT_REPLICATIONS").unwrap_or(0),
+            },
+            proxy: ProxyConfig {
+                version_header: vars.flag("PROXY_VERSION_HEADER", true),
+                trusted_proxies: vars
+                    .var("TRUSTED_PROXIES")
+                    .map(|value| parse_trusted_proxies(&value))
+                    .unwrap_or_default(),
+                max_upstream_timeout_secs: vars
+                    .parse("MAX_UPSTREAM_TIMEOUT_SECS")
+                    .unwrap_or_else(default_max_upstream_timeout_secs),
+                max_concurrent_per_client: vars.parse("MAX_CONCURRENT_PER_CLIENT").unwrap_or(0),
+                max_longpoll_connections: vars.parse("MAX_LONGPOLL_CONNECTIONS").unwrap_or(0),
+                slow_request_threshold_ms: vars
+                    .parse("SLOW_REQUEST_THRESHOLD_MS")
+                    .unwrap_or_else(default_slow_request_threshold_ms),
+                map_root_to_default_db: vars.flag("MAP_ROOT_TO_DEFAULT_DB", false),
+                max_header_count: vars
+                    .parse("MAX_HEADER_COUNT")
+                    .unwrap_or_else(default_max_header_count),
+                max_header_bytes: vars
+                    .parse("MAX_HEADER_BYTES")
+                    .unwrap_or_else(default_max_header_bytes),
+                response_header_policy: vars
+                    .var("RESPONSE_HEADER_POLICY")
+                    .map(|value| parse_response_header_policy(&value))
+                    .unwrap_or_default(),
+                pretty_debug_json: vars.flag("PRETTY_DEBUG_JSON", false),
+                forward_original_path: vars.flag("FORWARD_ORIGINAL_PATH", false),
+                cors_debug: vars.flag("CORS_DEBUG", false),
+                error_response_format: match vars.var("ERROR_RESPONSE_FORMAT") {
+                    Ok(value) if value.trim().eq_ignore_ascii_case("text") => {
+                        ErrorResponseFormat::Text
+                    }
+                    _ => ErrorResponseFormat::Json,
+                },
+                retry_after_max_secs: vars.parse("UPSTREAM_RETRY_AFTER_MAX_SECS").unwrap_or(0),
+                fail_fast_when_unavailable: vars.flag("FAIL_FAST_WHEN_UNAVAILABLE", false),
+                capture_requests: vars.parse("CAPTURE_REQUESTS").unwrap_or(0),
+                write_queue_capacity: vars.parse("WRITE_QUEUE_CAPACITY").unwrap_or(0),
+                stream_request_body_threshold: vars
+                    .parse("STREAM_REQUEST_BODY_THRESHOLD")
+                    .unwrap_or(0),
+                maintenance_page_path: vars
+                    .var("MAINTENANCE_PAGE_PATH")
+                    .ok()
+                    .filter(|path| !path.trim().is_empty()),
+                forwarded_auth_users: vars
+                    .var("FORWARDED_AUTH_USERS")
+                    .map(|value| parse_forwarded_auth_users(&value))
+                    .unwrap_or_default(),
+                chaos: ChaosConfig {
+                    enabled: vars.flag("CHAOS_ENABLED", false),
+                    fault_probability: vars.parse("CHAOS_FAULT_PROBABILITY").unwrap_or(0.0),
+                    delay_probability: vars.parse("CHAOS_DELAY_PROBABILITY").unwrap_or(0.0),
+                    delay_ms: vars.parse("CHAOS_DELAY_MS").unwrap_or(0),
+                },
+            },
+            document_policy: DocumentPolicy {
+                max_size_bytes: vars.parse("DOC_MAX_SIZE_BYTES"),
+                required_fields: vars.list("DOC_REQUIRED_FIELDS"),
+                forbidden_id_prefixes: vars.list("DOC_FORBIDDEN_ID_PREFIXES"),
+            },
+            document_transform: DocumentTransform {
+                metadata: vars
+                    .var("DOC_METADATA_FIELDS")
+                    .map(|value| parse_metadata_fields(&value))
+                    .unwrap_or_default(),
+                timestamp_field: vars
+                    .var("DOC_TIMESTAMP_FIELD")
+                    .ok()
+                    .filter(|field| !field.is_empty()),
+            },
+            admin: AdminConfig {
+                token: vars.var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            },
+            metrics: MetricsConfig {
+                enabled: vars.flag("METRICS_ENABLED", true),
+                summary_log_interval_secs: vars
+                    .parse("METRICS_SUMMARY_LOG_INTERVAL_SECS")
+                    .unwrap_or(0),
+                flush_on_shutdown: vars.flag("METRICS_FLUSH_ON_SHUTDOWN", false),
+                pushgateway_url: vars
+                    .var("METRICS_PUSHGATEWAY_URL")
+                    .ok()
+                    .filter(|url| !url.trim().is_empty()),
+            },
+            health: HealthConfig {
+                max_status_age_secs: vars
+                    .parse("HEALTH_MAX_STATUS_AGE_SECS")
+                    .unwrap_or_else(default_max_status_age_secs),
+                jitter_fraction: vars
+                    .parse("HEALTH_CHECK_JITTER")
+                    .unwrap_or_else(default_health_jitter),
+                min_doc_count: vars.parse("MIN_DOC_COUNT").unwrap_or(0),
             },
         }
     }
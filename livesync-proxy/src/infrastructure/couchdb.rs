@@ -4,54 +4,799 @@ use axum::body::Body as AxumBody;
 use axum::http::{HeaderMap, Response as AxumResponse};
 use axum::response::Response;
 use bytes::Bytes;
+use futures::StreamExt;
+use http_body_util::StreamBody;
+use hyper::body::Frame;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use reqwest::header::{HeaderName, HeaderValue};
-use reqwest::{Client, Method, StatusCode};
+use reqwest::{Client, ClientBuilder, Method, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::Value;
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
-use crate::domain::models::{CouchDbDocument, DomainError};
+use crate::domain::models::{
+    normalize_rev, CouchDbDocument, DatabaseInfo, DomainError, GetDocumentOptions, Membership,
+    ServerInfo,
+};
 use crate::domain::services::CouchDbRepository;
+use crate::infrastructure::config::{ConnectionConfig, CouchDbNode, NodeRole, ProbeMethod};
+#[cfg(unix)]
+use crate::infrastructure::transport::{UnixHttpClient, UNIX_SOCKET_BASE_URL};
+
+/// リクエスト単位でアップストリームのタイムアウトを上書きするヘッダー名
+///
+/// 信頼できる送信元からのリクエストに限り、ハンドラー側で上限値に丸めた値が設定される。
+pub const UPSTREAM_TIMEOUT_HEADER: &str = "x-upstream-timeout-seconds";
+
+/// デフォルトの認証情報の代わりに使う `Authorization` の値を渡す内部ヘッダー名
+///
+/// 信頼できるプロキシが認証したユーザーに対して、ハンドラー側でのみ設定される。
+/// CouchDBへはこのヘッダー自体は転送しない。
+pub const UPSTREAM_AUTHORIZATION_HEADER: &str = "x-livesync-upstream-authorization";
+
+/// 通常リクエストのタイムアウト（秒）
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+/// longpollリクエストのタイムアウト（秒）
+pub const LONGPOLL_TIMEOUT_SECS: u64 = 120;
+/// 通常の_changesリクエストのタイムアウト（秒）
+pub const CHANGES_TIMEOUT_SECS: u64 = 90;
+
+/// 上流へのリクエストに付けるUser-Agentの既定値
+pub const DEFAULT_USER_AGENT: &str = "Obsidian-LiveSync-Proxy/1.0";
+
+/// longpollがクライアント側で中断されたことを示すレスポンス拡張
+///
+/// ハンドラーはこれを見て、通常のエラーとは別に中断数を集計する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LongpollAborted;
+
+/// 中断されたlongpollに返すレスポンスを構築
+pub fn longpoll_aborted_response() -> Result<AxumResponse<AxumBody>> {
+    AxumResponse::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(
+            HeaderName::from_static("content-type"),
+            HeaderValue::from_static("application/json"),
+        )
+        .extension(LongpollAborted)
+        .body(AxumBody::from(r#"{"ok":true,"reason":"request_aborted"}"#))
+        .map_err(|e| anyhow!("Failed to build abort response: {}", e))
+}
+
+/// `_changes` のフィードの種類
+///
+/// クエリの `feed=` から判定し、種類ごとにタイムアウトとストリーミングの扱いを変える。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangesFeedKind {
+    /// `feed` の指定なし、または `feed=normal`
+    Normal,
+    /// `feed=longpoll`
+    Longpoll,
+    /// `feed=continuous`
+    Continuous,
+    /// `feed=eventsource`
+    EventSource,
+}
+
+impl ChangesFeedKind {
+    /// パスとクエリからフィードの種類を判定する（`_changes` 以外はNone）
+    pub fn detect(path: &str, query: Option<&str>) -> Option<Self> {
+        if !path.contains("/_changes") {
+            return None;
+        }
+        let feed = query
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .find_map(|pair| pair.strip_prefix("feed="));
+        Some(match feed {
+            Some("longpoll") => Self::Longpoll,
+            Some("continuous") => Self::Continuous,
+            Some("eventsource") => Self::EventSource,
+            _ => Self::Normal,
+        })
+    }
+
+    /// 応答を待ち続けることが前提のフィードか
+    pub fn is_long_lived(self) -> bool {
+        self != Self::Normal
+    }
+
+    /// レスポンスをバッファせずに流し続けるフィードか
+    pub fn is_streaming(self) -> bool {
+        matches!(self, Self::Continuous | Self::EventSource)
+    }
+}
+
+/// リクエストの種類ごとのアップストリームのタイムアウト
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamTimeouts {
+    pub default: Duration,
+    pub longpoll: Duration,
+    pub changes: Duration,
+}
+
+impl UpstreamTimeouts {
+    /// フィードの種類に応じたリクエスト全体のタイムアウト
+    ///
+    /// ストリーミングのフィードは全体では制限せずNoneを返す
+    /// （代わりに `changes` をデータが届かない間の読み取りタイムアウトに使う）。
+    pub fn for_feed(&self, feed: Option<ChangesFeedKind>) -> Option<Duration> {
+        match feed {
+            None => Some(self.default),
+            Some(ChangesFeedKind::Normal) => Some(self.changes),
+            Some(ChangesFeedKind::Longpoll) => Some(self.longpoll),
+            Some(ChangesFeedKind::Continuous | ChangesFeedKind::EventSource) => None,
+        }
+    }
+}
+
+impl Default for UpstreamTimeouts {
+    fn default() -> Self {
+        Self {
+            default: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            longpoll: Duration::from_secs(LONGPOLL_TIMEOUT_SECS),
+            changes: Duration::from_secs(CHANGES_TIMEOUT_SECS),
+        }
+    }
+}
+
+/// HTTPクライアントの作成に使う設定
+///
+/// 通常・longpoll・_changes用のすべてのクライアントで同じ設定を使う。
+#[derive(Debug, Clone)]
+struct HttpSettings {
+    connection: ConnectionConfig,
+    user_agent: String,
+    verbose: bool,
+}
+
+impl HttpSettings {
+    /// 設定を反映したHTTPクライアントのビルダーを作成する
+    fn client_builder(&self, timeout: Duration) -> ClientBuilder {
+        self.streaming_client_builder().timeout(timeout)
+    }
+
+    /// リクエスト全体のタイムアウトを設定しないビルダーを作成する
+    ///
+    /// リダイレクトは追わず、そのままクライアントに返す（意図しないホストへ転送しないため）。
+    fn streaming_client_builder(&self) -> ClientBuilder {
+        Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .connection_verbose(self.verbose)
+            .user_agent(self.user_agent.as_str())
+            .tcp_keepalive(self.connection.tcp_keepalive())
+            .tcp_nodelay(self.connection.tcp_nodelay)
+            .pool_idle_timeout(self.connection.pool_idle_timeout())
+            .min_tls_version(self.connection.min_tls_version.to_reqwest())
+    }
+}
+
+/// CouchDBへリクエストを送信できなかった理由
+#[derive(Debug, thiserror::Error)]
+pub enum UpstreamError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[cfg(unix)]
+    #[error("Request over Unix socket failed: {0}")]
+    Unix(#[from] hyper_util::client::legacy::Error),
+
+    #[error("Request timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+impl UpstreamError {
+    /// タイムアウトで失敗したか
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Self::Http(e) => e.is_timeout(),
+            Self::Timeout(_) => true,
+            #[cfg(unix)]
+            Self::Unix(_) => false,
+        }
+    }
+
+    /// 接続できずに失敗したか（リクエストは届いていない）
+    pub fn is_connect(&self) -> bool {
+        match self {
+            Self::Http(e) => e.is_connect(),
+            Self::Timeout(_) => false,
+            #[cfg(unix)]
+            Self::Unix(e) => e.is_connect(),
+        }
+    }
+}
+
+/// CouchDBへのリクエストのエラーをドメインエラーに変換する
+///
+/// タイムアウトは呼び出し側が再試行や504を選べるように `Timeout` として区別する。
+fn request_error(action: &str, e: impl Into<UpstreamError>) -> DomainError {
+    let e = e.into();
+    if e.is_timeout() {
+        DomainError::Timeout(format!("Failed to {}: {}", action, e))
+    } else {
+        DomainError::CouchDbError(format!("Failed to {}: {}", action, e))
+    }
+}
+
+/// JSONでないエラーボディをエラーに含める際の最大文字数
+const ERROR_SNIPPET_CHARS: usize = 200;
+
+/// レスポンスをJSONとして解釈する
+///
+/// CouchDBの前段のプロキシがHTMLのエラーページを返すことがあるため、
+/// Content-TypeがJSONでなく本文もJSONとして読めない場合は本文の先頭を含むエラーにする。
+async fn parse_json_response<T: DeserializeOwned>(
+    response: reqwest::Response,
+    context: &str,
+) -> Result<T, DomainError> {
+    let status = response.status().as_u16();
+    let is_json = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.to_ascii_lowercase().contains("json"));
+
+    if is_json {
+        return response.json::<T>().await.map_err(|e| {
+            DomainError::InvalidMessage(format!("Failed to parse {}: {}", context, e))
+        });
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|e| request_error(&format!("read {}", context), e))?;
+
+    // CouchDBはAcceptヘッダーによってtext/plainでJSONを返すことがある
+    serde_json::from_str::<T>(&body).map_err(|_| {
+        warn!(
+            "CouchDB returned a non-JSON {} (status {})",
+            context, status
+        );
+        DomainError::CouchDbStatus {
+            status,
+            body: body.trim().chars().take(ERROR_SNIPPET_CHARS).collect(),
+        }
+    })
+}
+
+/// ベースURLが/で終わるように調整
+fn normalize_base_url(base_url: &str) -> String {
+    if base_url.ends_with('/') {
+        base_url.to_string()
+    } else {
+        format!("{}/", base_url)
+    }
+}
+
+/// パスセグメントをエンコードする際にそのまま残す文字（RFC 3986の非予約文字）
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// パスの1セグメントとして使えるようにエンコードする（`/`・`?`・`#`・`%` なども含む）
+fn encode_path_segment(segment: &str) -> String {
+    utf8_percent_encode(segment, PATH_SEGMENT_ENCODE_SET).to_string()
+}
+
+/// ドキュメントのURLを組み立てる（IDはパスセグメントとしてエンコードする）
+fn document_url(base_url: &str, db_name: &str, doc_id: &str) -> String {
+    format!("{}{}/{}", base_url, db_name, encode_path_segment(doc_id))
+}
+
+/// ドキュメントのPUTの結果
+enum PutDocumentOutcome {
+    /// 保存され、新しい_revが設定されたドキュメント
+    Saved(CouchDbDocument),
+    /// 既に同じIDのドキュメントが存在した（409 Conflict）
+    Conflict,
+}
+
+/// ノードのベースURLにパスとクエリを付与する
+fn node_url(base_url: &str, path: &str, query: Option<&str>) -> String {
+    let mut url = format!("{}{}", base_url, path);
+    if let Some(q) = query {
+        url.push('?');
+        url.push_str(q);
+    }
+    url
+}
+
+/// レプリカで処理できる読み取り専用リクエストか
+fn is_replica_read(method: &Method, path: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD)
+        && (path.contains("/_all_docs")
+            || path.contains("/_changes")
+            || (path.contains("/_design/") && path.contains("/_view/")))
+}
 
 /// CouchDB クライアント
 pub struct CouchDbClient {
     client: Client,
+    /// CouchDBノードのベースURL（先頭が設定上のプライマリ）
+    nodes: Vec<String>,
+    /// 現在優先して使用するノードのインデックス
+    primary: AtomicUsize,
+    /// 読み取り専用のレプリカのベースURL
+    replicas: Vec<String>,
+    /// 重みに応じてレプリカのインデックスを並べた振り分け順
+    replica_schedule: Vec<usize>,
+    replica_cursor: AtomicUsize,
+    username: String,
+    password: String,
+    /// データベース名が指定されない場合に使用するデータベース
+    default_db: String,
+    /// ヘルスチェックとデータベースの存在確認に使うメソッド
+    probe_method: ProbeMethod,
+    /// すべてのHTTPクライアントに適用するTCP・接続プール・User-Agentの設定
+    http: HttpSettings,
+    /// リクエストの種類ごとのタイムアウト
+    timeouts: UpstreamTimeouts,
+    /// Unixドメインソケットで接続する場合のクライアント（ベースURL宛てのリクエストに使う）
+    #[cfg(unix)]
+    unix_client: Option<UnixHttpClient>,
+}
+
+/// `CouchDbClient` を設定項目ごとに組み立てるビルダー
+///
+/// 指定しなかった項目は `CouchDbClient::new` と同じ既定値になる。
+#[derive(Debug, Clone)]
+pub struct CouchDbClientBuilder {
     base_url: String,
     username: String,
     password: String,
+    default_db: String,
+    failover_urls: Vec<String>,
+    nodes: Vec<CouchDbNode>,
+    probe_method: ProbeMethod,
+    http: HttpSettings,
+    timeouts: UpstreamTimeouts,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+}
+
+impl CouchDbClientBuilder {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            username: String::new(),
+            password: String::new(),
+            default_db: "obsidian".to_string(),
+            failover_urls: Vec::new(),
+            nodes: Vec::new(),
+            probe_method: ProbeMethod::default(),
+            http: HttpSettings {
+                connection: ConnectionConfig::default(),
+                user_agent: DEFAULT_USER_AGENT.to_string(),
+                verbose: true,
+            },
+            timeouts: UpstreamTimeouts::default(),
+            #[cfg(unix)]
+            unix_socket: None,
+        }
+    }
+
+    /// ベースURLの代わりにUnixドメインソケットでCouchDBに接続する
+    #[cfg(unix)]
+    pub fn unix_socket(mut self, socket_path: PathBuf) -> Self {
+        self.unix_socket = Some(socket_path);
+        self
+    }
+
+    /// Basic認証に使う認証情報
+    pub fn credentials(mut self, username: &str, password: &str) -> Self {
+        self.username = username.to_string();
+        self.password = password.to_string();
+        self
+    }
+
+    /// データベース名が指定されない場合に使用するデータベース
+    pub fn default_db(mut self, dbname: &str) -> Self {
+        self.default_db = dbname.to_string();
+        self
+    }
+
+    /// プライマリに接続できない場合に切り替えるノード
+    pub fn failover_urls(mut self, urls: &[String]) -> Self {
+        self.failover_urls.extend_from_slice(urls);
+        self
+    }
+
+    /// 役割付きのノード
+    pub fn nodes(mut self, nodes: &[CouchDbNode]) -> Self {
+        self.nodes.extend_from_slice(nodes);
+        self
+    }
+
+    /// ヘルスチェックとデータベースの存在確認に使うメソッド
+    pub fn probe_method(mut self, probe_method: ProbeMethod) -> Self {
+        self.probe_method = probe_method;
+        self
+    }
+
+    /// TCP・接続プールの設定
+    pub fn connection(mut self, connection: ConnectionConfig) -> Self {
+        self.http.connection = connection;
+        self
+    }
+
+    /// リクエストの種類ごとのタイムアウト
+    pub fn timeouts(mut self, timeouts: UpstreamTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// 上流へのリクエストに付けるUser-Agent
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.http.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// 接続の詳細なログを出力するか
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.http.verbose = verbose;
+        self
+    }
+
+    /// クライアントを作成する
+    pub fn build(self) -> CouchDbClient {
+        let client = self
+            .http
+            .client_builder(self.timeouts.default)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        #[cfg(unix)]
+        let base_url = match &self.unix_socket {
+            Some(_) => UNIX_SOCKET_BASE_URL.to_string(),
+            None => normalize_base_url(&self.base_url),
+        };
+        #[cfg(not(unix))]
+        let base_url = normalize_base_url(&self.base_url);
+        #[cfg(unix)]
+        let unix_client = self.unix_socket.map(|socket_path| {
+            info!(
+                "Connecting to CouchDB over Unix socket {}",
+                socket_path.display()
+            );
+            UnixHttpClient::new(socket_path, &self.http.user_agent)
+        });
+
+        debug!("Creating CouchDB client with URL: {}", base_url);
+
+        CouchDbClient {
+            client,
+            nodes: vec![base_url],
+            primary: AtomicUsize::new(0),
+            replicas: Vec::new(),
+            replica_schedule: Vec::new(),
+            replica_cursor: AtomicUsize::new(0),
+            username: self.username,
+            password: self.password,
+            default_db: self.default_db,
+            probe_method: self.probe_method,
+            http: self.http,
+            timeouts: self.timeouts,
+            #[cfg(unix)]
+            unix_client,
+        }
+        .with_failover_urls(&self.failover_urls)
+        .with_nodes(&self.nodes)
+    }
 }
 
 impl CouchDbClient {
     /// 新しいCouchDBクライアントを作成
     pub fn new(base_url: &str, username: &str, password: &str) -> Self {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))
-            .connection_verbose(true)
-            .user_agent("Obsidian-LiveSync-Proxy/1.0")
+        CouchDbClientBuilder::new(base_url)
+            .credentials(username, password)
+            .build()
+    }
+
+    /// 設定項目ごとにクライアントを組み立てるビルダーを作成
+    pub fn builder(base_url: &str) -> CouchDbClientBuilder {
+        CouchDbClientBuilder::new(base_url)
+    }
+
+    /// TCP・接続プールの設定を指定し、通常用のクライアントを作り直す
+    pub fn with_connection_config(mut self, connection: ConnectionConfig) -> Self {
+        self.http.connection = connection;
+        self.client = self
+            .http
+            .client_builder(self.timeouts.default)
             .build()
             .expect("Failed to create HTTP client");
+        self
+    }
 
-        // ベースURLが/で終わるように調整
-        let base_url = if base_url.ends_with('/') {
-            base_url.to_string()
-        } else {
-            format!("{}/", base_url)
+    /// HTTPクライアントに適用しているTCP・接続プールの設定
+    pub fn connection_config(&self) -> &ConnectionConfig {
+        &self.http.connection
+    }
+
+    /// リクエストの種類ごとのタイムアウト
+    pub fn timeouts(&self) -> UpstreamTimeouts {
+        self.timeouts
+    }
+
+    /// ヘルスチェックとデータベースの存在確認に使うメソッドを指定
+    pub fn with_probe_method(mut self, probe_method: ProbeMethod) -> Self {
+        self.probe_method = probe_method;
+        self
+    }
+
+    /// 設定されたメソッドでURLを確認し、ステータスを返す
+    ///
+    /// HEADが405で拒否された場合はGETで再試行する。
+    async fn probe(&self, url: &str) -> Result<StatusCode> {
+        // 認証情報がない場合は認証なしで接続する
+        let send = |method: Method| {
+            let mut request = self.client.request(method, url);
+            if !self.username.is_empty() && !self.password.is_empty() {
+                request = request.basic_auth(&self.username, Some(&self.password));
+            }
+            self.send(request)
         };
 
-        debug!("Creating CouchDB client with URL: {}", base_url);
+        if self.probe_method == ProbeMethod::Head {
+            let status = send(Method::HEAD).await?.status();
+            if status != StatusCode::METHOD_NOT_ALLOWED {
+                return Ok(status);
+            }
+            debug!("HEAD is not allowed for {}, falling back to GET", url);
+        }
 
-        Self {
-            client,
-            base_url,
-            username: username.to_string(),
-            password: password.to_string(),
+        Ok(send(Method::GET).await?.status())
+    }
+
+    /// デフォルトデータベース名を指定
+    pub fn with_default_db(mut self, dbname: &str) -> Self {
+        self.default_db = dbname.to_string();
+        self
+    }
+
+    /// プライマリに接続できない場合に切り替えるノードを追加
+    pub fn with_failover_urls(mut self, urls: &[String]) -> Self {
+        for url in urls {
+            let url = normalize_base_url(url);
+            if !self.nodes.contains(&url) {
+                debug!("Adding CouchDB failover node: {}", url);
+                self.nodes.push(url);
+            }
+        }
+        self
+    }
+
+    /// 役割付きのノードを追加する
+    ///
+    /// プライマリはフェイルオーバー先に、レプリカは読み取り専用リクエストの振り分け先になる。
+    /// 各ノードには同じ認証情報でBasic認証するため、ノードごとのセッション状態は持たない。
+    pub fn with_nodes(mut self, nodes: &[CouchDbNode]) -> Self {
+        let mut weights = Vec::new();
+        for node in nodes {
+            let url = normalize_base_url(&node.url);
+            match node.role {
+                NodeRole::Primary => {
+                    self = self.with_failover_urls(std::slice::from_ref(&url));
+                }
+                NodeRole::Replica if node.weight > 0 => {
+                    debug!(
+                        "Adding CouchDB read replica: {} (weight {})",
+                        url, node.weight
+                    );
+                    self.replicas.push(url);
+                    weights.push(node.weight);
+                }
+                NodeRole::Replica => debug!("Ignoring CouchDB replica with zero weight: {}", url),
+            }
+        }
+
+        // 重み付きラウンドロビン: 重みの大きいレプリカほど多く、かつ分散して並べる
+        let max_weight = weights.iter().copied().max().unwrap_or(0);
+        self.replica_schedule = (0..max_weight)
+            .flat_map(|round| {
+                weights
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, weight)| **weight > round)
+                    .map(|(index, _)| index)
+            })
+            .collect();
+        self
+    }
+
+    /// ドキュメントをPUTし、競合（409）はエラーと区別して返す
+    async fn put_document(
+        &self,
+        db_name: &str,
+        doc: CouchDbDocument,
+    ) -> Result<PutDocumentOutcome, DomainError> {
+        let url = document_url(self.base_url(), db_name, &doc.id);
+        debug!("Saving document: {}/{}", db_name, doc.id);
+
+        let response = self
+            .send(
+                self.client
+                    .put(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .json(&doc),
+            )
+            .await
+            .map_err(|e| request_error("save document", e))?;
+
+        match response.status() {
+            StatusCode::CONFLICT => return Ok(PutDocumentOutcome::Conflict),
+            status if !status.is_success() => {
+                return Err(DomainError::CouchDbError(format!(
+                    "Failed to save document with status: {}",
+                    status
+                )));
+            }
+            _ => {}
+        }
+
+        // 必要なフィールドだけを含む構造体を定義
+        #[derive(Deserialize)]
+        struct RevOnly {
+            rev: String,
+        }
+
+        let save_response: RevOnly = parse_json_response(response, "save response").await?;
+
+        // 更新された_revを持つドキュメントを返す
+        let mut updated_doc = doc;
+        updated_doc.rev = Some(save_response.rev);
+
+        Ok(PutDocumentOutcome::Saved(updated_doc))
+    }
+
+    /// 現在優先して使用するノードのベースURL
+    fn base_url(&self) -> &str {
+        &self.nodes[self.primary.load(Ordering::Relaxed)]
+    }
+
+    /// 現在のプライマリから順に、試行するノードのインデックスを返す
+    fn node_order(&self) -> Vec<usize> {
+        let primary = self.primary.load(Ordering::Relaxed);
+        (0..self.nodes.len())
+            .map(|offset| (primary + offset) % self.nodes.len())
+            .collect()
+    }
+
+    /// 読み取り専用リクエストを次のレプリカに送信する
+    ///
+    /// レプリカがない、または応答がない場合はNoneを返し、プライマリで処理させる。
+    async fn send_to_replica(
+        &self,
+        client: &Client,
+        request: &reqwest::Request,
+        timeout: Option<Duration>,
+        path: &str,
+        query: Option<&str>,
+    ) -> Option<(String, reqwest::Response)> {
+        if self.replica_schedule.is_empty() {
+            return None;
+        }
+
+        let slot =
+            self.replica_cursor.fetch_add(1, Ordering::Relaxed) % self.replica_schedule.len();
+        let replica = &self.replicas[self.replica_schedule[slot]];
+        let url = node_url(replica, path, query);
+
+        let mut replica_request = request.try_clone()?;
+        *replica_request.url_mut() = reqwest::Url::parse(&url).ok()?;
+
+        match self.execute(client, replica_request, timeout).await {
+            Ok(response) => {
+                debug!("Served read request from replica {}", replica);
+                Some((url, response))
+            }
+            Err(e) => {
+                warn!("CouchDB replica {} failed ({}), using primary", replica, e);
+                None
+            }
+        }
+    }
+
+    /// リクエストを送信する（Unixドメインソケットのノード宛てはソケット経由）
+    ///
+    /// `timeout` はソケット経由の場合に使うタイムアウト（TCPの場合は `client` の設定に従う）。
+    async fn execute(
+        &self,
+        client: &Client,
+        request: reqwest::Request,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::Response, UpstreamError> {
+        #[cfg(unix)]
+        if let Some(unix_client) = &self.unix_client {
+            if request.url().as_str().starts_with(UNIX_SOCKET_BASE_URL) {
+                return unix_client.execute(request, timeout).await;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = timeout;
+        Ok(client.execute(request).await?)
+    }
+
+    /// 通常用のクライアントで組み立てたリクエストを送信する
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, UpstreamError> {
+        let request = request.build()?;
+        self.execute(&self.client, request, Some(self.timeouts.default))
+            .await
+    }
+
+    /// リクエストを送信し、接続できないノードは次のノードに切り替える
+    ///
+    /// 接続エラーはリクエストが届いていないため常に切り替える。タイムアウトは
+    /// 書き込みが実行済みの可能性があるため、GET/HEADの場合のみ切り替える。
+    /// 最後に試行したURLと結果を返す。
+    async fn send_with_failover(
+        &self,
+        client: &Client,
+        request: reqwest::Request,
+        timeout: Option<Duration>,
+        path: &str,
+        query: Option<&str>,
+    ) -> (String, Result<reqwest::Response, UpstreamError>) {
+        let order = self.node_order();
+        let retry_on_timeout = matches!(*request.method(), Method::GET | Method::HEAD);
+        let mut last = None;
+
+        for (attempt, &index) in order.iter().enumerate() {
+            let url = node_url(&self.nodes[index], path, query);
+
+            let mut node_request = match request.try_clone() {
+                Some(node_request) => node_request,
+                None => return (url, self.execute(client, request, timeout).await),
+            };
+            match reqwest::Url::parse(&url) {
+                Ok(parsed) => *node_request.url_mut() = parsed,
+                Err(e) => {
+                    warn!("Skipping CouchDB node with invalid URL {}: {}", url, e);
+                    continue;
+                }
+            }
+
+            let result = self.execute(client, node_request, timeout).await;
+            let has_next = attempt + 1 < order.len();
+            match result {
+                Err(ref e)
+                    if has_next && (e.is_connect() || (e.is_timeout() && retry_on_timeout)) =>
+                {
+                    warn!(
+                        "CouchDB node {} failed ({}), trying next node",
+                        self.nodes[index], e
+                    );
+                    last = Some((url, result));
+                }
+                _ => {
+                    if result.is_ok() && index != self.primary.load(Ordering::Relaxed) {
+                        warn!("Failing over to CouchDB node {}", self.nodes[index]);
+                        self.primary.store(index, Ordering::Relaxed);
+                    }
+                    return (url, result);
+                }
+            }
         }
+
+        last.expect("CouchDbClient always has at least one node")
     }
 
     /// CouchDBサーバーにpingを送信して接続を確認
     pub async fn ping(&self) -> Result<()> {
-        let url = format!("{}/", self.base_url);
+        let url = format!("{}/", self.base_url());
         debug!("Pinging CouchDB at {}", url);
 
         // 認証情報をデバッグ出力
@@ -67,28 +812,11 @@ impl CouchDbClient {
             );
         }
 
-        // 認証が必要かどうかを確認
-        let mut req_builder = self.client.get(&url);
-
-        if !self.username.is_empty() && !self.password.is_empty() {
-            debug!("Adding basic authentication to ping request");
-            req_builder = req_builder.basic_auth(&self.username, Some(&self.password));
-        }
-
-        // リクエストのヘッダーを表示
-        debug!("Sending ping request to CouchDB");
-
-        let response = req_builder.send().await?;
-
-        // ステータスを事前に取得
-        let status = response.status();
+        // ヘルスチェック用のメソッドで確認する（HEADが拒否された場合はGET）
+        let status = self.probe(&url).await?;
         debug!("CouchDB ping response status: {}", status);
 
-        // レスポンスボディをログに出力（エラー情報を取得するため）
         if !status.is_success() {
-            if let Ok(body_text) = response.text().await {
-                debug!("CouchDB ping response body: {}", body_text);
-            }
             error!("CouchDB ping failed with status: {}", status);
             return Err(anyhow!("CouchDB ping failed with status: {}", status));
         }
@@ -99,29 +827,23 @@ impl CouchDbClient {
 
     /// データベースが存在するか確認
     pub async fn database_exists(&self, db_name: &str) -> Result<bool> {
-        let url = format!("{}/{}", self.base_url, db_name);
+        let url = format!("{}/{}", self.base_url(), db_name);
         debug!("Checking if database exists: {}", db_name);
 
-        let response = self
-            .client
-            .head(&url)
-            .basic_auth(&self.username, Some(&self.password))
-            .send()
-            .await?;
-
-        Ok(response.status() == StatusCode::OK)
+        Ok(self.probe(&url).await? == StatusCode::OK)
     }
 
     /// データベースを作成
     pub async fn create_database(&self, db_name: &str) -> Result<()> {
-        let url = format!("{}/{}", self.base_url, db_name);
+        let url = format!("{}/{}", self.base_url(), db_name);
         debug!("Creating database: {}", db_name);
 
         let response = self
-            .client
-            .put(&url)
-            .basic_auth(&self.username, Some(&self.password))
-            .send()
+            .send(
+                self.client
+                    .put(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
             .await?;
 
         match response.status() {
@@ -152,8 +874,37 @@ impl CouchDbClient {
         headers: HeaderMap,
         body: Bytes,
     ) -> Result<Response<AxumBody>> {
-        // URLを構築
-        let mut url = format!("{}{}", self.base_url, path);
+        self.send_forward_request(method, path, query, headers, reqwest::Body::from(body))
+            .await
+    }
+
+    /// ボディをバッファせずにHTTPリクエストをCouchDBに転送する
+    ///
+    /// ボディは届いた分から順に上流へ送る。複製できないため、接続できなくても
+    /// 他のノードへは切り替えない。
+    pub async fn http_forward_request_stream(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        headers: HeaderMap,
+        body: AxumBody,
+    ) -> Result<Response<AxumBody>> {
+        self.send_forward_request(method, path, query, headers, streaming_request_body(body))
+            .await
+    }
+
+    /// HTTPリクエストをCouchDBに転送する共通処理
+    async fn send_forward_request(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        headers: HeaderMap,
+        body: reqwest::Body,
+    ) -> Result<Response<AxumBody>> {
+        // URLを構築（実際の送信先はフェイルオーバーにより変わる）
+        let mut url = format!("{}{}", self.base_url(), path);
         if let Some(ref q) = query {
             url.push('?');
             url.push_str(q);
@@ -162,17 +913,19 @@ impl CouchDbClient {
         // より詳細なリクエスト情報をログに出力
         info!("Forwarding request to CouchDB: {} {}", method, url);
         debug!("Request headers: {:?}", headers);
-        debug!("Request body size: {} bytes", body.len());
+        match body.as_bytes() {
+            Some(bytes) => debug!("Request body size: {} bytes", bytes.len()),
+            None => debug!("Request body is streamed"),
+        }
 
         // HTTPメソッドを解析
         let method = Method::from_str(method).unwrap_or(Method::GET);
 
-        // longpollリクエストの検出 - _changesエンドポイントでfeed=longpollパラメータを含む場合
-        let is_longpoll = path.contains("/_changes")
-            && query.as_ref().is_some_and(|q| q.contains("feed=longpoll"));
-
-        // 通常の_changesリクエストの検出（longpollでない場合も含む）
-        let is_changes_request = path.contains("/_changes");
+        // _changesのフィードの種類を判定（longpoll・continuous・eventsourceを区別する）
+        let feed = ChangesFeedKind::detect(path, query.as_deref());
+        let is_longpoll = feed == Some(ChangesFeedKind::Longpoll);
+        let is_changes_request = feed.is_some();
+        let is_streaming = feed.is_some_and(ChangesFeedKind::is_streaming);
 
         // bulk_docsリクエストの検出（大きなデータ転送が予想される）
         let _is_bulk_docs = path.contains("/_bulk_docs");
@@ -184,24 +937,28 @@ impl CouchDbClient {
                 "Detected longpoll request, using extended timeout: {} {}",
                 method, url
             );
-            Client::builder()
-                .timeout(std::time::Duration::from_secs(120)) // 120秒のタイムアウト（CouchDBの設定より長く）
-                .connection_verbose(true)
-                .user_agent("Obsidian-LiveSync-Proxy/1.0")
-                .tcp_keepalive(Some(std::time::Duration::from_secs(30))) // TCP keepaliveを有効化
-                .tcp_nodelay(true) // TCPノーディレイを有効化（レイテンシ削減）
-                .pool_idle_timeout(std::time::Duration::from_secs(120)) // 接続プールのアイドルタイムアウトを延長
+            // CouchDBの設定より長いタイムアウト
+            self.http
+                .client_builder(self.timeouts.longpoll)
                 .pool_max_idle_per_host(10) // ホストごとの最大アイドル接続数を増加
                 .build()
                 .expect("Failed to create HTTP client for longpoll")
+        } else if is_streaming {
+            // continuous・eventsourceは終わりがないため、全体ではなく無通信の時間で打ち切る
+            info!(
+                "Detected streaming _changes feed ({:?}), streaming the response: {} {}",
+                feed, method, url
+            );
+            self.http
+                .streaming_client_builder()
+                .read_timeout(self.timeouts.changes)
+                .build()
+                .expect("Failed to create HTTP client for streaming changes feed")
         } else if is_changes_request {
             // 通常の_changesリクエスト用のクライアント（longpollではない）
             info!("Detected regular _changes request: {} {}", method, url);
-            Client::builder()
-                .timeout(std::time::Duration::from_secs(90)) // 90秒のタイムアウト
-                .connection_verbose(true)
-                .user_agent("Obsidian-LiveSync-Proxy/1.0")
-                .tcp_nodelay(true) // TCPノーディレイを有効化
+            self.http
+                .client_builder(self.timeouts.changes)
                 .build()
                 .expect("Failed to create HTTP client for changes request")
         } else {
@@ -212,6 +969,26 @@ impl CouchDbClient {
         // reqwestのリクエストビルダーを構築
         let mut req_builder = client.request(method.clone(), &url);
 
+        // タイムアウトの上書き指定があればリクエスト単位で適用
+        let timeout_override = headers
+            .get(UPSTREAM_TIMEOUT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+        // 上書きがない場合のフィードに応じたタイムアウト（ソケット経由の送信で使う）
+        let feed_timeout = self.timeouts.for_feed(feed);
+        let effective_timeout_secs = match timeout_override {
+            Some(secs) => {
+                info!("Using upstream timeout override of {} seconds", secs);
+                req_builder = req_builder.timeout(Duration::from_secs(secs));
+                secs
+            }
+            None => self
+                .timeouts
+                .for_feed(feed)
+                .unwrap_or(self.timeouts.changes)
+                .as_secs(),
+        };
+
         // Abortエラーを防ぐために必要なヘッダーを追加（_changesリクエスト用）
         if is_changes_request {
             // Connection: keep-aliveを明示的に設定
@@ -230,8 +1007,11 @@ impl CouchDbClient {
             }
         }
 
-        // 認証情報を追加（空でない場合のみ）
-        if !self.username.is_empty() && !self.password.is_empty() {
+        // 認証情報を追加（転送されたユーザーの認証情報を優先し、なければ空でない場合のみ）
+        if let Some(authorization) = headers.get(UPSTREAM_AUTHORIZATION_HEADER) {
+            debug!("Using upstream credentials of the forwarded user");
+            req_builder = req_builder.header(reqwest::header::AUTHORIZATION, authorization);
+        } else if !self.username.is_empty() && !self.password.is_empty() {
             debug!("Adding basic auth for user: {}", self.username);
             req_builder = req_builder.basic_auth(&self.username, Some(&self.password));
         } else {
@@ -240,22 +1020,53 @@ impl CouchDbClient {
             );
         }
 
+        // ボディが空の書き込み（データベースの作成など）か
+        let is_empty_body = body.as_bytes().is_some_and(<[u8]>::is_empty);
+        let is_empty_write =
+            is_empty_body && matches!(method, Method::PUT | Method::POST | Method::PATCH);
+
         // ヘッダーを追加（Hostヘッダーは除外し、認証関連ヘッダーも上書き）
+        // Content-Typeはボディが空でもそのまま転送する
         for (key, value) in headers.iter() {
             if key.as_str().to_lowercase() != "host"
                 && key.as_str().to_lowercase() != "authorization"
+                && key.as_str() != UPSTREAM_TIMEOUT_HEADER
+                && key.as_str() != UPSTREAM_AUTHORIZATION_HEADER
+                && !(is_empty_write && key == reqwest::header::CONTENT_LENGTH)
             {
                 req_builder = req_builder.header(key.as_str(), value);
             }
         }
 
         // リクエストボディを追加（空でなければ）
-        if !body.is_empty() {
+        // 空の書き込みはCouchDBが誤解しないよう `Content-Length: 0` を明示する
+        if !is_empty_body {
             req_builder = req_builder.body(body);
+        } else if is_empty_write {
+            req_builder = req_builder
+                .header(reqwest::header::CONTENT_LENGTH, "0")
+                .body(Bytes::new());
         }
 
-        // リクエストを送信
-        let response = match req_builder.send().await {
+        // リクエストを送信（接続できない場合は他のノードに切り替える）
+        // 読み取り専用リクエストはレプリカを優先し、書き込みは常にプライマリに送る
+        let request = req_builder.build()?;
+        let replica_response = if is_replica_read(&method, path) {
+            self.send_to_replica(&client, &request, feed_timeout, path, query.as_deref())
+                .await
+        } else {
+            None
+        };
+        let (sent_url, send_result) = match replica_response {
+            Some((replica_url, response)) => (replica_url, Ok(response)),
+            None => {
+                self.send_with_failover(&client, request, feed_timeout, path, query.as_deref())
+                    .await
+            }
+        };
+        url = sent_url;
+
+        let response = match send_result {
             Ok(resp) => resp,
             Err(e) => {
                 // 接続エラーの詳細をログに出力
@@ -272,22 +1083,13 @@ impl CouchDbClient {
                             "Longpoll request was aborted by client, this is often normal: {} {}",
                             method, url
                         );
-                        return AxumResponse::builder()
-                            .status(StatusCode::NO_CONTENT)
-                            .header(
-                                HeaderName::from_static("content-type"),
-                                HeaderValue::from_static("application/json"),
-                            )
-                            .body(AxumBody::from(r#"{"ok":true,"reason":"request_aborted"}"#))
-                            .map_err(|e| anyhow!("Failed to build abort response: {}", e));
+                        return longpoll_aborted_response();
                     }
                     // タイムアウトエラー - 特に長時間リクエストで発生
                     err if err.is_timeout() => {
                         warn!(
                             "Request timed out: {} {} after {} seconds",
-                            method,
-                            url,
-                            if is_longpoll { 120 } else { 30 }
+                            method, url, effective_timeout_secs
                         );
                         return AxumResponse::builder()
                             .status(StatusCode::GATEWAY_TIMEOUT)
@@ -297,7 +1099,7 @@ impl CouchDbClient {
                             )
                             .body(AxumBody::from(format!(
                                 r#"{{"error":"Request timed out after {} seconds","reason":"timeout"}}"#,
-                                if is_longpoll { 120 } else { 30 }
+                                effective_timeout_secs
                             )))
                             .map_err(|e| anyhow!("Failed to build timeout response: {}", e));
                     }
@@ -352,8 +1154,26 @@ impl CouchDbClient {
             }
         }
 
-        // ストリーミングレスポンスでなく、完全なボディを取得してからレスポンスを返す
-        // 特にchunkedエンコーディングの場合に問題が発生することがあるため
+        // continuous・eventsourceはバッファせずに届いた分から順に流す
+        if is_streaming {
+            let chunks = futures::stream::unfold(Some(response), |response| async move {
+                let mut response = response?;
+                match response.chunk().await {
+                    Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+                    Ok(None) => None,
+                    Err(e) => {
+                        warn!("Streaming _changes feed ended with an error: {}", e);
+                        Some((Err(e), None))
+                    }
+                }
+            });
+            return axum_response_builder
+                .body(AxumBody::from_stream(chunks))
+                .map_err(|e| anyhow!("Failed to build streaming response: {}", e));
+        }
+
+        // ストリーミングレスポンスでなく、完全なボディを取得してからレスポンスを返す
+        // 特にchunkedエンコーディングの場合に問題が発生することがあるため
         let body_bytes = match response.bytes().await {
             Ok(bytes) => {
                 debug!("Successfully read response body: {} bytes", bytes.len());
@@ -387,9 +1207,7 @@ impl CouchDbClient {
 
     /// デフォルトデータベース名を取得
     pub fn get_dbname(&self) -> String {
-        // 必要に応じてフィールド追加後、ここで返す
-        // 仮実装: "obsidian" を返す
-        "obsidian".to_string()
+        self.default_db.clone()
     }
 }
 
@@ -402,16 +1220,29 @@ impl CouchDbRepository for CouchDbClient {
         db_name: &str,
         doc_id: &str,
     ) -> Result<CouchDbDocument, DomainError> {
-        let url = format!("{}/{}/{}", self.base_url, db_name, doc_id);
-        debug!("Getting document: {}/{}", db_name, doc_id);
+        self.get_document_with_options(db_name, doc_id, &GetDocumentOptions::default())
+            .await
+    }
+
+    /// リビジョンや添付ファイルのオプションを指定してドキュメントを取得
+    async fn get_document_with_options(
+        &self,
+        db_name: &str,
+        doc_id: &str,
+        options: &GetDocumentOptions,
+    ) -> Result<CouchDbDocument, DomainError> {
+        let url = document_url(self.base_url(), db_name, doc_id);
+        debug!("Getting document: {}/{} ({:?})", db_name, doc_id, options);
 
         let response = self
-            .client
-            .get(&url)
-            .basic_auth(&self.username, Some(&self.password))
-            .send()
+            .send(
+                self.client
+                    .get(&url)
+                    .query(&options.query_params())
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
             .await
-            .map_err(|e| DomainError::CouchDbError(format!("Failed to get document: {}", e)))?;
+            .map_err(|e| request_error("get document", e))?;
 
         if response.status() == StatusCode::NOT_FOUND {
             return Err(DomainError::CouchDbError(format!(
@@ -427,12 +1258,7 @@ impl CouchDbRepository for CouchDbClient {
             )));
         }
 
-        let doc = response
-            .json::<CouchDbDocument>()
-            .await
-            .map_err(|e| DomainError::InvalidMessage(format!("Failed to parse document: {}", e)))?;
-
-        Ok(doc)
+        parse_json_response(response, "document").await
     }
 
     /// ドキュメントを保存
@@ -441,72 +1267,248 @@ impl CouchDbRepository for CouchDbClient {
         db_name: &str,
         doc: CouchDbDocument,
     ) -> Result<CouchDbDocument, DomainError> {
-        let doc_id = doc.id.clone();
-        let url = format!("{}/{}/{}", self.base_url, db_name, doc_id);
-        debug!("Saving document: {}/{}", db_name, doc_id);
+        match self.put_document(db_name, doc).await? {
+            PutDocumentOutcome::Saved(doc) => Ok(doc),
+            PutDocumentOutcome::Conflict => Err(DomainError::CouchDbError(format!(
+                "Failed to save document with status: {}",
+                StatusCode::CONFLICT
+            ))),
+        }
+    }
+
+    /// ドキュメントが存在しない場合のみ保存し、存在する場合は既存のドキュメントを返す
+    async fn create_document_if_absent(
+        &self,
+        db_name: &str,
+        doc: CouchDbDocument,
+    ) -> Result<CouchDbDocument, DomainError> {
+        let url = document_url(self.base_url(), db_name, &doc.id);
+        debug!("Creating document if absent: {}/{}", db_name, doc.id);
+
+        let response = self
+            .send(
+                self.client
+                    .head(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("check document", e))?;
+
+        match response.status() {
+            status if status.is_success() => {
+                debug!("Document {} already exists, leaving it unchanged", doc.id);
+                self.get_document(db_name, &doc.id).await
+            }
+            StatusCode::NOT_FOUND => {
+                // 確認後に別のクライアントが作成した場合は409になるため、既存のものを返す
+                let doc_id = doc.id.clone();
+                match self.put_document(db_name, doc).await? {
+                    PutDocumentOutcome::Saved(doc) => Ok(doc),
+                    PutDocumentOutcome::Conflict => self.get_document(db_name, &doc_id).await,
+                }
+            }
+            status => Err(DomainError::CouchDbError(format!(
+                "Failed to check document with status: {}",
+                status
+            ))),
+        }
+    }
+
+    /// ドキュメントを削除
+    async fn delete_document(
+        &self,
+        db_name: &str,
+        doc_id: &str,
+        rev: &str,
+    ) -> Result<(), DomainError> {
+        // If-Match形式のrevも受け付け、明らかに不正なrevは送信前に拒否する
+        let rev = normalize_rev(rev)?;
+        let url = document_url(self.base_url(), db_name, doc_id);
+        debug!("Deleting document: {}/{} (rev: {})", db_name, doc_id, rev);
+
+        let response = self
+            .send(
+                self.client
+                    .delete(&url)
+                    .query(&[("rev", &rev)])
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("delete document", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to delete document with status: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// ドキュメントをCOPYメソッドで複製
+    async fn copy_document(
+        &self,
+        db_name: &str,
+        src_id: &str,
+        dest_id: &str,
+    ) -> Result<CouchDbDocument, DomainError> {
+        let url = document_url(self.base_url(), db_name, src_id);
+        debug!("Copying document: {}/{} -> {}", db_name, src_id, dest_id);
+
+        // COPYは拡張メソッドのため明示的に生成する
+        let copy_method = Method::from_bytes(b"COPY")
+            .map_err(|e| DomainError::HttpProxyError(format!("Invalid COPY method: {}", e)))?;
 
         let response = self
-            .client
-            .put(&url)
-            .basic_auth(&self.username, Some(&self.password))
-            .json(&doc)
-            .send()
+            .send(
+                self.client
+                    .request(copy_method, &url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    // CouchDBは複製先のIDをURLデコードして扱うため、同じ形式でエンコードする
+                    .header("Destination", encode_path_segment(dest_id)),
+            )
             .await
-            .map_err(|e| DomainError::CouchDbError(format!("Failed to save document: {}", e)))?;
+            .map_err(|e| request_error("copy document", e))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(DomainError::CouchDbError(format!(
+                "Document {} not found",
+                src_id
+            )));
+        }
 
         if !response.status().is_success() {
             return Err(DomainError::CouchDbError(format!(
-                "Failed to save document with status: {}",
+                "Failed to copy document with status: {}",
+                response.status()
+            )));
+        }
+
+        // 複製先のドキュメントを取得して返す
+        self.get_document(db_name, dest_id).await
+    }
+
+    /// _bulk_getで複数のドキュメントをまとめて取得
+    async fn bulk_get(
+        &self,
+        db_name: &str,
+        requests: Vec<(String, Option<String>)>,
+    ) -> Result<Vec<CouchDbDocument>, DomainError> {
+        let url = format!("{}/{}/_bulk_get", self.base_url(), db_name);
+        debug!("Bulk getting {} documents from {}", requests.len(), db_name);
+
+        let docs: Vec<Value> = requests
+            .into_iter()
+            .map(|(id, rev)| match rev {
+                Some(rev) => serde_json::json!({"id": id, "rev": rev}),
+                None => serde_json::json!({"id": id}),
+            })
+            .collect();
+
+        let response = self
+            .send(
+                self.client
+                    .post(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .header("Accept", "application/json")
+                    .json(&serde_json::json!({"docs": docs})),
+            )
+            .await
+            .map_err(|e| request_error("bulk get documents", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to bulk get documents with status: {}",
                 response.status()
             )));
         }
 
-        // 必要なフィールドだけを含む構造体を定義
         #[derive(Deserialize)]
-        struct RevOnly {
-            rev: String,
+        struct BulkGetResponse {
+            results: Vec<BulkGetResult>,
         }
 
-        let save_response = response.json::<RevOnly>().await.map_err(|e| {
-            DomainError::InvalidMessage(format!("Failed to parse save response: {}", e))
-        })?;
+        #[derive(Deserialize)]
+        struct BulkGetResult {
+            id: String,
+            docs: Vec<BulkGetEntry>,
+        }
 
-        // 更新された_revを持つドキュメントを返す
-        let mut updated_doc = doc;
-        updated_doc.rev = Some(save_response.rev);
+        #[derive(Deserialize)]
+        struct BulkGetEntry {
+            ok: Option<CouchDbDocument>,
+            error: Option<Value>,
+        }
+
+        let bulk_response: BulkGetResponse =
+            parse_json_response(response, "bulk get response").await?;
+
+        // 結果をフラットにし、取得できなかったドキュメントは読み飛ばす
+        let mut documents = Vec::new();
+        for result in bulk_response.results {
+            for entry in result.docs {
+                match (entry.ok, entry.error) {
+                    (Some(doc), _) => documents.push(doc),
+                    (None, error) => {
+                        debug!("Skipping document {} in bulk get: {:?}", result.id, error)
+                    }
+                }
+            }
+        }
 
-        Ok(updated_doc)
+        Ok(documents)
     }
 
-    /// ドキュメントを削除
-    async fn delete_document(
+    /// _bulk_docsで複数のドキュメントをまとめて書き込む
+    ///
+    /// `new_edits` がfalseの場合は `_rev` と `_revisions` を変更せずに送り、
+    /// CouchDBにリビジョン履歴をそのまま保存させる（レプリケーションと同じ動作）。
+    async fn bulk_docs(
         &self,
         db_name: &str,
-        doc_id: &str,
-        rev: &str,
-    ) -> Result<(), DomainError> {
-        let url = format!("{}/{}/{}?rev={}", self.base_url, db_name, doc_id, rev);
-        debug!("Deleting document: {}/{} (rev: {})", db_name, doc_id, rev);
+        docs: Vec<CouchDbDocument>,
+        new_edits: bool,
+    ) -> Result<Vec<Value>, DomainError> {
+        let url = format!("{}{}/_bulk_docs", self.base_url(), db_name);
+        debug!(
+            "Bulk writing {} documents to {} (new_edits: {})",
+            docs.len(),
+            db_name,
+            new_edits
+        );
+
+        let mut body = serde_json::json!({ "docs": docs });
+        if !new_edits {
+            body["new_edits"] = Value::Bool(false);
+        }
 
         let response = self
-            .client
-            .delete(&url)
-            .basic_auth(&self.username, Some(&self.password))
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .json(&body),
+            )
             .await
-            .map_err(|e| DomainError::CouchDbError(format!("Failed to delete document: {}", e)))?;
+            .map_err(|e| request_error("bulk write documents", e))?;
 
         if !response.status().is_success() {
             return Err(DomainError::CouchDbError(format!(
-                "Failed to delete document with status: {}",
+                "Failed to bulk write documents with status: {}",
                 response.status()
             )));
         }
 
-        Ok(())
+        parse_json_response(response, "bulk docs response").await
     }
 
     /// ビューに対してクエリを実行
+    ///
+    /// 結果はCouchDBが返す `rows` の順序（キー順、`descending` 指定時は逆順）のまま返す。
+    /// `options` に `keys` 配列がある場合はPOSTで問い合わせる。
+    /// `partition` を指定するとパーティション化されたデータベースの
+    /// `{db}/_partition/{partition}/_design/...` に問い合わせる。
     async fn query_view(
         &self,
         db_name: &str,
@@ -514,33 +1516,51 @@ impl CouchDbRepository for CouchDbClient {
         view_name: &str,
         options: Value,
     ) -> Result<Vec<CouchDbDocument>, DomainError> {
+        let partition = options.get("partition").and_then(Value::as_str);
+        let db_path = match partition {
+            // 呼び出し元が指定した値でパスが変わらないよう、1つのセグメントとしてエンコードする
+            Some(partition) => format!("{}/_partition/{}", db_name, encode_path_segment(partition)),
+            None => db_name.to_string(),
+        };
         let url = format!(
             "{}/{}/_design/{}/_view/{}",
-            self.base_url, db_name, design_doc, view_name
+            self.base_url(),
+            db_path,
+            design_doc,
+            view_name
         );
         debug!(
             "Querying view: {}/{}/_design/{}/_view/{}",
-            self.base_url, db_name, design_doc, view_name
+            self.base_url(),
+            db_path,
+            design_doc,
+            view_name
         );
 
-        let mut request = self
-            .client
-            .get(&url)
-            .basic_auth(&self.username, Some(&self.password));
+        // `keys` は配列のためクエリ文字列では渡さず、POSTのボディで送る
+        let keys = options.get("keys").filter(|keys| keys.is_array());
+        let mut request = match keys {
+            Some(keys) => self
+                .client
+                .post(&url)
+                .json(&serde_json::json!({ "keys": keys })),
+            None => self.client.get(&url),
+        }
+        .basic_auth(&self.username, Some(&self.password));
 
-        // オプションがオブジェクトの場合、クエリパラメータとして追加
+        // オプションがオブジェクトの場合、文字列の値をクエリパラメータとして追加
         if let Some(obj) = options.as_object() {
-            for (key, value) in obj {
+            for (key, value) in obj.iter().filter(|(key, _)| *key != "partition") {
                 if let Some(value_str) = value.as_str() {
                     request = request.query(&[(key, value_str)]);
                 }
             }
         }
 
-        let response = request
-            .send()
+        let response = self
+            .send(request)
             .await
-            .map_err(|e| DomainError::CouchDbError(format!("Failed to query view: {}", e)))?;
+            .map_err(|e| request_error("query view", e))?;
 
         if !response.status().is_success() {
             return Err(DomainError::CouchDbError(format!(
@@ -559,9 +1579,7 @@ impl CouchDbRepository for CouchDbClient {
             doc: Option<CouchDbDocument>,
         }
 
-        let view_response = response.json::<ViewResponse>().await.map_err(|e| {
-            DomainError::InvalidMessage(format!("Failed to parse view response: {}", e))
-        })?;
+        let view_response: ViewResponse = parse_json_response(response, "view response").await?;
 
         let docs = view_response
             .rows
@@ -572,6 +1590,46 @@ impl CouchDbRepository for CouchDbClient {
         Ok(docs)
     }
 
+    /// `limit=0` でビューを問い合わせ、インデックスの構築を開始させる
+    ///
+    /// CouchDBはインデックスが最新になるまで応答しないため、完了まで待つことになる。
+    async fn warm_view(
+        &self,
+        db_name: &str,
+        design_doc: &str,
+        view_name: &str,
+    ) -> Result<(), DomainError> {
+        let url = format!(
+            "{}{}/_design/{}/_view/{}",
+            self.base_url(),
+            db_name,
+            design_doc,
+            view_name
+        );
+        debug!("Warming view: {}/{}/{}", db_name, design_doc, view_name);
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .query(&[("limit", "0")]),
+            )
+            .await
+            .map_err(|e| request_error("warm view", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to warm view {}/{} with status: {}",
+                design_doc,
+                view_name,
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
     /// データベースの存在を確認し、必要に応じて作成
     async fn ensure_database(&self, db_name: &str) -> Result<(), DomainError> {
         match self.database_exists(db_name).await {
@@ -599,7 +1657,7 @@ impl CouchDbRepository for CouchDbClient {
         target: &str,
         options: Value,
     ) -> Result<Value, DomainError> {
-        let url = format!("{}/_replicate", self.base_url);
+        let url = format!("{}/_replicate", self.base_url());
         debug!("Replicating from {} to {}", source, target);
 
         let mut replication_body = serde_json::json!({
@@ -617,15 +1675,14 @@ impl CouchDbRepository for CouchDbClient {
         }
 
         let response = self
-            .client
-            .post(&url)
-            .basic_auth(&self.username, Some(&self.password))
-            .json(&replication_body)
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .json(&replication_body),
+            )
             .await
-            .map_err(|e| {
-                DomainError::CouchDbError(format!("Failed to start replication: {}", e))
-            })?;
+            .map_err(|e| request_error("start replication", e))?;
 
         if !response.status().is_success() {
             return Err(DomainError::CouchDbError(format!(
@@ -634,16 +1691,295 @@ impl CouchDbRepository for CouchDbClient {
             )));
         }
 
-        let result = response.json::<Value>().await.map_err(|e| {
-            DomainError::InvalidMessage(format!("Failed to parse replication response: {}", e))
-        })?;
+        parse_json_response(response, "replication response").await
+    }
 
-        Ok(result)
+    /// ヘルスチェック用のメソッドでサーバーの応答を確認
+    async fn ping(&self) -> Result<(), DomainError> {
+        CouchDbClient::ping(self)
+            .await
+            .map_err(|e| DomainError::CouchDbError(e.to_string()))
+    }
+
+    /// サーバーのバージョン情報を取得
+    async fn server_info(&self) -> Result<ServerInfo, DomainError> {
+        let url = format!("{}/", self.base_url());
+        debug!("Getting CouchDB server info");
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("get server info", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to get server info with status: {}",
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "server info").await
+    }
+
+    /// クラスターを構成するノードを取得（`_membership` がない場合は単一ノードとみなす）
+    async fn membership(&self) -> Result<Membership, DomainError> {
+        let url = format!("{}_membership", self.base_url());
+        debug!("Getting CouchDB cluster membership");
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("get membership", e))?;
+
+        // CouchDB 1.x などエンドポイントを持たないサーバーは単一ノード
+        if response.status() == StatusCode::NOT_FOUND {
+            debug!("CouchDB has no _membership endpoint, assuming a single node");
+            return Ok(Membership::default());
+        }
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to get membership with status: {}",
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "membership").await
+    }
+
+    /// データベースの情報を取得
+    async fn database_info(&self, db_name: &str) -> Result<DatabaseInfo, DomainError> {
+        let url = format!("{}{}", self.base_url(), db_name);
+        debug!("Getting database info: {}", db_name);
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("get database info", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to get database info of {} with status: {}",
+                db_name,
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "database info").await
+    }
+
+    /// データベース一覧を取得
+    async fn list_databases(&self) -> Result<Vec<String>, DomainError> {
+        let url = format!("{}_all_dbs", self.base_url());
+        debug!("Listing databases");
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("list databases", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to list databases with status: {}",
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "database list").await
+    }
+
+    /// 実行中のタスクを取得
+    async fn active_tasks(&self) -> Result<Value, DomainError> {
+        let url = format!("{}_active_tasks", self.base_url());
+        debug!("Getting active tasks");
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("get active tasks", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to get active tasks with status: {}",
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "active tasks").await
+    }
+
+    /// `_replicator` のレプリケーションの状態を取得
+    async fn scheduler_docs(&self) -> Result<Value, DomainError> {
+        let url = format!("{}_scheduler/docs", self.base_url());
+        debug!("Getting replication scheduler docs");
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("get scheduler docs", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to get scheduler docs with status: {}",
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "scheduler docs").await
+    }
+
+    /// データベースのリビジョン保持数を取得
+    async fn get_revs_limit(&self, db_name: &str) -> Result<u64, DomainError> {
+        let url = format!("{}{}/_revs_limit", self.base_url(), db_name);
+        debug!("Getting revs limit: {}", db_name);
+
+        let response = self
+            .send(
+                self.client
+                    .get(&url)
+                    .basic_auth(&self.username, Some(&self.password)),
+            )
+            .await
+            .map_err(|e| request_error("get revs limit", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to get revs limit with status: {}",
+                response.status()
+            )));
+        }
+
+        parse_json_response(response, "revs limit").await
+    }
+
+    /// データベースのリビジョン保持数を設定
+    async fn set_revs_limit(&self, db_name: &str, limit: u64) -> Result<(), DomainError> {
+        let url = format!("{}{}/_revs_limit", self.base_url(), db_name);
+        debug!("Setting revs limit: {} -> {}", db_name, limit);
+
+        let response = self
+            .send(
+                self.client
+                    .put(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .json(&limit),
+            )
+            .await
+            .map_err(|e| request_error("set revs limit", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to set revs limit with status: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// `_users` データベースにユーザーを作成
+    async fn create_user(
+        &self,
+        name: &str,
+        password: &str,
+        roles: Vec<String>,
+    ) -> Result<(), DomainError> {
+        let user_id = format!("org.couchdb.user:{}", name);
+        // ユーザー名に `/` や `?` が含まれても別のドキュメントを指さないようエンコードする
+        let url = format!(
+            "{}_users/org.couchdb.user:{}",
+            self.base_url(),
+            encode_path_segment(name)
+        );
+        debug!("Creating CouchDB user: {}", name);
+
+        let response = self
+            .send(
+                self.client
+                    .put(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .json(&serde_json::json!({
+                        "_id": user_id,
+                        "name": name,
+                        "password": password,
+                        "roles": roles,
+                        "type": "user",
+                    })),
+            )
+            .await
+            .map_err(|e| request_error("create user", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to create user {} with status: {}",
+                name,
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// データベースのセキュリティ設定を変更
+    async fn set_db_security(
+        &self,
+        db_name: &str,
+        members: Vec<String>,
+        admins: Vec<String>,
+    ) -> Result<(), DomainError> {
+        let url = format!("{}{}/_security", self.base_url(), db_name);
+        debug!("Setting security of {}", db_name);
+
+        let response = self
+            .send(
+                self.client
+                    .put(&url)
+                    .basic_auth(&self.username, Some(&self.password))
+                    .json(&serde_json::json!({
+                        "admins": { "names": admins, "roles": [] },
+                        "members": { "names": members, "roles": [] },
+                    })),
+            )
+            .await
+            .map_err(|e| request_error("set security", e))?;
+
+        if !response.status().is_success() {
+            return Err(DomainError::CouchDbError(format!(
+                "Failed to set security of {} with status: {}",
+                db_name,
+                response.status()
+            )));
+        }
+
+        Ok(())
     }
 
     /// CouchDBサーバーのベースURLを取得
     fn get_base_url(&self) -> String {
-        self.base_url.clone()
+        self.base_url().to_string()
     }
 
     /// 認証情報を取得
@@ -671,4 +2007,57 @@ impl CouchDbRepository for CouchDbClient {
             ))),
         }
     }
+
+    /// ボディをバッファせずにHTTPリクエストをCouchDBに転送する
+    async fn forward_request_stream(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        headers: HeaderMap,
+        body: AxumBody,
+    ) -> Result<Response<AxumBody>, DomainError> {
+        self.http_forward_request_stream(method, path, query, headers, body)
+            .await
+            .map_err(|e| DomainError::CouchDbError(format!("Failed to forward request: {}", e)))
+    }
+}
+
+/// ストリーミングで転送するリクエストボディを受け渡すチャネルの容量（チャンク数）
+const STREAMING_BODY_CHANNEL_CAPACITY: usize = 8;
+
+/// チャネルから受け取ったチャンクを順に返すストリーム
+///
+/// reqwestのボディは `Sync` である必要があるため、axumのボディを別タスクで読み、
+/// `Sync` なチャネルの受信側を経由して渡す。
+struct ChannelStream(tokio::sync::mpsc::Receiver<Result<Bytes, axum::Error>>);
+
+impl futures::Stream for ChannelStream {
+    type Item = Result<Frame<Bytes>, axum::Error>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.get_mut()
+            .0
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data)))
+    }
+}
+
+/// axumのリクエストボディを、バッファせずに上流へ送るreqwestのボディに変換する
+fn streaming_request_body(body: AxumBody) -> reqwest::Body {
+    let (sender, receiver) = tokio::sync::mpsc::channel(STREAMING_BODY_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        let mut chunks = body.into_data_stream();
+        while let Some(chunk) = chunks.next().await {
+            let failed = chunk.is_err();
+            // 上流への送信が終わっていれば（受信側が閉じていれば）読むのをやめる
+            if sender.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    reqwest::Body::wrap(StreamBody::new(ChannelStream(receiver)))
 }
@@ -0,0 +1,131 @@
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::http::{header, HeaderValue, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio::net::UnixStream;
+use tracing::debug;
+
+use crate::infrastructure::couchdb::UpstreamError;
+
+/// Unixドメインソケットで接続する場合にHTTPクライアントが使うベースURL
+///
+/// ホスト名はソケットの先のCouchDBへの `Host` ヘッダーにだけ使われ、名前解決はしない。
+pub const UNIX_SOCKET_BASE_URL: &str = "http://localhost/";
+
+/// CouchDBへの接続方法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpstreamTransport {
+    /// `http://` / `https://` のURLにTCPで接続する
+    Tcp { url: String },
+    /// `unix:///run/couchdb.sock` のようにUnixドメインソケットで接続する
+    Unix { socket_path: PathBuf },
+}
+
+impl UpstreamTransport {
+    /// CouchDBのURLから接続方法を判定する
+    pub fn parse(url: &str) -> Self {
+        match url.strip_prefix("unix://") {
+            Some(path) => UpstreamTransport::Unix {
+                socket_path: PathBuf::from(path.trim_end_matches('/')),
+            },
+            None => UpstreamTransport::Tcp {
+                url: url.to_string(),
+            },
+        }
+    }
+}
+
+/// URLに関係なく、常に指定したUnixドメインソケットに接続するコネクター
+#[derive(Debug, Clone)]
+pub struct UnixConnector {
+    socket_path: Arc<PathBuf>,
+}
+
+impl UnixConnector {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self {
+            socket_path: Arc::new(socket_path),
+        }
+    }
+}
+
+impl tower::Service<Uri> for UnixConnector {
+    type Response = TokioIo<UnixStream>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let socket_path = Arc::clone(&self.socket_path);
+        Box::pin(async move {
+            debug!("Connecting to CouchDB socket {}", socket_path.display());
+            UnixStream::connect(socket_path.as_path())
+                .await
+                .map(TokioIo::new)
+        })
+    }
+}
+
+/// Unixドメインソケット経由でCouchDBにリクエストを送るHTTPクライアント
+///
+/// リクエストの組み立てはTCPの場合と同じくreqwestで行い、送信だけをこのクライアントが担う。
+/// ソケットのパーミッションで接続元を制限できるよう、TCPのポートは開かない。
+#[derive(Clone)]
+pub struct UnixHttpClient {
+    client: Client<UnixConnector, reqwest::Body>,
+    socket_path: PathBuf,
+    user_agent: HeaderValue,
+}
+
+impl UnixHttpClient {
+    pub fn new(socket_path: PathBuf, user_agent: &str) -> Self {
+        let client =
+            Client::builder(TokioExecutor::new()).build(UnixConnector::new(socket_path.clone()));
+        Self {
+            client,
+            socket_path,
+            user_agent: HeaderValue::from_str(user_agent)
+                .unwrap_or_else(|_| HeaderValue::from_static("livesync-proxy")),
+        }
+    }
+
+    /// 接続先のソケットのパス
+    pub fn socket_path(&self) -> &PathBuf {
+        &self.socket_path
+    }
+
+    /// reqwestで組み立てたリクエストをソケット経由で送信する
+    ///
+    /// `timeout` はレスポンスヘッダーを受け取るまでの上限（Noneの場合は制限しない）。
+    pub async fn execute(
+        &self,
+        request: reqwest::Request,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::Response, UpstreamError> {
+        let timeout = request.timeout().copied().or(timeout);
+        let mut request = axum::http::Request::try_from(request)?;
+        request
+            .headers_mut()
+            .entry(header::USER_AGENT)
+            .or_insert_with(|| self.user_agent.clone());
+
+        let send = self.client.request(request);
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, send)
+                .await
+                .map_err(|_| UpstreamError::Timeout(timeout))??,
+            None => send.await?,
+        };
+        Ok(reqwest::Response::from(response.map(reqwest::Body::wrap)))
+    }
+}
@@ -1,5 +1,16 @@
 // Web関連のモジュール
+pub mod admin;
+pub mod auth;
+pub mod capture;
+pub mod chaos;
+pub mod concurrency;
+pub mod error;
 pub mod handlers;
 pub mod health;
+pub mod inflight;
+pub mod listener;
+pub mod logs;
 pub mod metrics;
+pub mod reload;
 pub mod server;
+pub mod write_queue;
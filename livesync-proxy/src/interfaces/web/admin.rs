@@ -0,0 +1,760 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::future::join_all;
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::domain::models::{DatabaseInfo, DomainError};
+use crate::infrastructure::config::AdminConfig;
+use crate::infrastructure::couchdb::{
+    CHANGES_TIMEOUT_SECS, DEFAULT_TIMEOUT_SECS, LONGPOLL_TIMEOUT_SECS,
+};
+use crate::interfaces::web::auth::{AuthError, StaticTokenAuthenticator};
+use crate::interfaces::web::handlers::status_payload;
+use crate::interfaces::web::metrics::DatabaseActivity;
+use crate::interfaces::web::server::AppState;
+use crate::interfaces::web::write_queue::QueuedWrite;
+
+/// メンテナンス中に返す `Retry-After` の秒数
+pub const MAINTENANCE_RETRY_AFTER_SECS: u64 = 60;
+
+/// レプリケーション進捗を `_active_tasks` から取得する間隔
+pub const REPLICATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `/api/databases/{db}/summary` でCouchDBから取得した情報を再利用する期間
+pub const DATABASE_SUMMARY_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// データベース情報を短時間だけ保持するキャッシュ
+///
+/// 同期状況の画面から繰り返し呼ばれても、CouchDBへの問い合わせを増やさないようにする。
+#[derive(Default)]
+pub struct DatabaseInfoCache {
+    entries: Mutex<HashMap<String, (Instant, DatabaseInfo)>>,
+}
+
+impl DatabaseInfoCache {
+    /// `ttl` 以内に保存した情報を返す
+    pub fn get(&self, db: &str, ttl: Duration) -> Option<DatabaseInfo> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(db)
+            .filter(|(stored_at, _)| stored_at.elapsed() < ttl)
+            .map(|(_, info)| info.clone())
+    }
+
+    pub fn insert(&self, db: &str, info: DatabaseInfo) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(db.to_string(), (Instant::now(), info));
+    }
+}
+
+/// 管理者APIのエラーレスポンスを構築する
+fn admin_error(status: StatusCode, error: &str, reason: &str) -> Response {
+    (
+        status,
+        Json(serde_json::json!({
+            "error": error,
+            "reason": reason,
+        })),
+    )
+        .into_response()
+}
+
+/// リクエストが有効な管理者トークンを持っているか
+pub fn has_admin_token(headers: &HeaderMap, config: &AdminConfig) -> bool {
+    StaticTokenAuthenticator::check(config, headers).is_ok()
+}
+
+/// 管理者の認証を要求するミドルウェア
+///
+/// 認証方法は `AppState::with_admin_authenticator` で差し替えられ、指定がなければ
+/// `ADMIN_TOKEN` の静的トークンで認証する。認証された呼び出し元はリクエストの拡張に格納する。
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let result = match &state.admin_authenticator {
+        Some(authenticator) => authenticator.authorize(req.headers()).await,
+        None => StaticTokenAuthenticator::check(&state.admin_config, req.headers()),
+    };
+
+    match result {
+        Ok(principal) => {
+            debug!(
+                "Admin request to {} authorized as {}",
+                req.uri().path(),
+                principal.name
+            );
+            req.extensions_mut().insert(principal);
+            next.run(req).await
+        }
+        Err(e) => {
+            match e {
+                AuthError::Disabled => {
+                    warn!("Admin endpoint requested but ADMIN_TOKEN is not configured")
+                }
+                _ => warn!("Rejected admin request to {}: {}", req.uri().path(), e),
+            }
+            admin_error(e.status(), e.error_code(), &e.to_string())
+        }
+    }
+}
+
+/// メンテナンスモード切り替えのリクエスト
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+}
+
+/// メンテナンスモードを切り替えるハンドラー
+pub async fn maintenance_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MaintenanceRequest>,
+) -> impl IntoResponse {
+    let previous = state.maintenance.swap(request.enabled, Ordering::SeqCst);
+    if previous != request.enabled {
+        if request.enabled {
+            warn!("Maintenance mode enabled: /db requests will return 503");
+        } else {
+            info!("Maintenance mode disabled: resuming proxying");
+        }
+    }
+
+    Json(serde_json::json!({
+        "ok": true,
+        "maintenance": request.enabled,
+    }))
+}
+
+/// メンテナンス中の `/db` リクエストに返す503レスポンスを構築する
+pub fn maintenance_response() -> Response {
+    let mut response = admin_error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "maintenance",
+        "The proxy is in maintenance mode, please retry later",
+    );
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, MAINTENANCE_RETRY_AFTER_SECS.into());
+    response
+}
+
+/// `MAINTENANCE_PAGE_PATH` が未設定の場合に返す組み込みのメンテナンスページ
+pub const DEFAULT_MAINTENANCE_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="ja">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>Maintenance - LiveSync Proxy</title>
+</head>
+<body>
+  <h1>メンテナンス中です</h1>
+  <p>LiveSync Proxy は現在メンテナンス中です。しばらくしてから再度アクセスしてください。</p>
+  <p>The proxy is in maintenance mode, please retry later.</p>
+</body>
+</html>
+"#;
+
+/// メンテナンス中の `/` と静的ファイルに返すHTMLの503レスポンスを構築する
+///
+/// 指定されたファイルを読めない場合は組み込みのページで代用する。
+pub async fn maintenance_page_response(page_path: Option<&str>) -> Response {
+    let page = match page_path {
+        Some(path) => match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to read maintenance page {}: {}", path, e);
+                DEFAULT_MAINTENANCE_PAGE.as_bytes().to_vec()
+            }
+        },
+        None => DEFAULT_MAINTENANCE_PAGE.as_bytes().to_vec(),
+    };
+
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        page,
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, MAINTENANCE_RETRY_AFTER_SECS.into());
+    response
+}
+
+/// メンテナンス中は静的ファイルの代わりにメンテナンスページを返すミドルウェア
+pub async fn maintenance_page(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.is_maintenance() {
+        let page_path = state.proxy_config().maintenance_page_path.clone();
+        return maintenance_page_response(page_path.as_deref()).await;
+    }
+    next.run(request).await
+}
+
+/// ドメインエラーを管理者APIのエラーレスポンスに変換する
+fn domain_error_response(e: DomainError) -> Response {
+    match e {
+        DomainError::Timeout(_) => {
+            admin_error(StatusCode::GATEWAY_TIMEOUT, "timeout", &e.to_string())
+        }
+        e => admin_error(StatusCode::BAD_GATEWAY, "upstream_error", &e.to_string()),
+    }
+}
+
+/// 実際に使われている設定を秘密情報を伏せて返すハンドラー
+///
+/// `/db` プロキシの設定は再読み込み後の値を表示する。CouchDBのベースURLと
+/// タイムアウトはクライアントが実際に使う値を `derived` に含める。
+pub async fn config_handler(State(state): State<Arc<AppState>>) -> Response {
+    let Some(app_config) = &state.app_config else {
+        return admin_error(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "Configuration is not available",
+        );
+    };
+
+    let mut payload = app_config.redacted();
+    payload["proxy"] = state.proxy_config().redacted();
+    payload["derived"] = serde_json::json!({
+        "couchdb_base_url": state.livesync_service.get_couchdb_url(),
+        "timeouts_secs": {
+            "default": DEFAULT_TIMEOUT_SECS,
+            "changes": CHANGES_TIMEOUT_SECS,
+            "longpoll": LONGPOLL_TIMEOUT_SECS,
+        },
+    });
+    Json(payload).into_response()
+}
+
+/// データベース一覧を構築する
+pub async fn databases_payload(state: &AppState) -> Result<Value, DomainError> {
+    let databases = state.livesync_service.list_databases().await?;
+    Ok(serde_json::json!(databases))
+}
+
+/// 実行中タスク一覧を構築する
+pub async fn tasks_payload(state: &AppState) -> Result<Value, DomainError> {
+    state.livesync_service.active_tasks().await
+}
+
+/// `_scheduler/docs` からレプリケーションごとの状態を抜き出す
+///
+/// `state` は `running`・`crashed`・`completed` など。エラーがあれば `info.error` を `error` に入れる。
+pub fn replication_statuses(scheduler_docs: &Value) -> Vec<Value> {
+    scheduler_docs
+        .get("docs")
+        .and_then(Value::as_array)
+        .map(|docs| {
+            docs.iter()
+                .map(|doc| {
+                    serde_json::json!({
+                        "doc_id": doc.get("doc_id").cloned().unwrap_or(Value::Null),
+                        "database": doc.get("database").cloned().unwrap_or(Value::Null),
+                        "source": doc.get("source").cloned().unwrap_or(Value::Null),
+                        "target": doc.get("target").cloned().unwrap_or(Value::Null),
+                        "state": doc.get("state").cloned().unwrap_or(Value::Null),
+                        "error": doc.pointer("/info/error").cloned().unwrap_or(Value::Null),
+                        "error_count": doc.get("error_count").cloned().unwrap_or(Value::Null),
+                        "last_updated": doc.get("last_updated").cloned().unwrap_or(Value::Null),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 永続レプリケーション（`_replicator`）の状態を返すハンドラー
+///
+/// 実行中のものしか分からない `_active_tasks` と違い、失敗や完了したものも含む。
+pub async fn replication_status_handler(State(state): State<Arc<AppState>>) -> Response {
+    match state.livesync_service.scheduler_docs().await {
+        Ok(docs) => Json(serde_json::json!({
+            "replications": replication_statuses(&docs),
+        }))
+        .into_response(),
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// データベース一覧を返すハンドラー
+pub async fn databases_handler(State(state): State<Arc<AppState>>) -> Response {
+    match databases_payload(&state).await {
+        Ok(payload) => Json(payload).into_response(),
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// データベースの情報を返すハンドラー
+pub async fn database_info_handler(
+    State(state): State<Arc<AppState>>,
+    Path(db): Path<String>,
+) -> Response {
+    match state.livesync_service.database_info(&db).await {
+        Ok(info) => Json(info).into_response(),
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// データベース情報とプロキシで観測した同期の状況をまとめる
+pub fn database_summary(info: &DatabaseInfo, activity: &DatabaseActivity) -> Value {
+    serde_json::json!({
+        "db": info.db_name,
+        "doc_count": info.doc_count,
+        "doc_del_count": info.doc_del_count,
+        "update_seq": info.update_seq,
+        "sizes": info.sizes,
+        "proxy": {
+            "requests": activity.requests,
+            "errors": activity.errors,
+            "bytes": activity.bytes,
+            "last_activity": activity.last_activity.map(|time| time.to_rfc3339()),
+        },
+    })
+}
+
+/// データベースごとの同期の概要を返すハンドラー
+///
+/// CouchDBのデータベース情報は `DATABASE_SUMMARY_CACHE_TTL` の間キャッシュする。
+pub async fn database_summary_handler(
+    State(state): State<Arc<AppState>>,
+    Path(db): Path<String>,
+) -> Response {
+    let info = match state
+        .database_info_cache
+        .get(&db, DATABASE_SUMMARY_CACHE_TTL)
+    {
+        Some(info) => info,
+        None => match state.livesync_service.database_info(&db).await {
+            Ok(info) => {
+                state.database_info_cache.insert(&db, info.clone());
+                info
+            }
+            Err(e) => return domain_error_response(e),
+        },
+    };
+    let activity = state.metrics_state.database_activity(&db);
+    Json(database_summary(&info, &activity)).into_response()
+}
+
+/// ビューのインデックスを事前に構築し、かかった時間を返すハンドラー
+///
+/// コンパクションやデプロイの後、クライアントを向ける前に実行しておくと
+/// 最初のビュー問い合わせが遅くなるのを避けられる。
+pub async fn warm_view_handler(
+    State(state): State<Arc<AppState>>,
+    Path((db, ddoc, view)): Path<(String, String, String)>,
+) -> Response {
+    match state.livesync_service.warm_view(&db, &ddoc, &view).await {
+        Ok(elapsed) => Json(serde_json::json!({
+            "db": db,
+            "ddoc": ddoc,
+            "view": view,
+            "elapsed_ms": elapsed.as_millis() as u64,
+        }))
+        .into_response(),
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// 実行中タスク一覧を返すハンドラー
+pub async fn tasks_handler(State(state): State<Arc<AppState>>) -> Response {
+    match tasks_payload(&state).await {
+        Ok(payload) => Json(payload).into_response(),
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// リビジョン保持数の変更リクエスト
+#[derive(Debug, Deserialize)]
+pub struct RevsLimitRequest {
+    pub limit: i64,
+}
+
+/// 入力値のエラーを400、それ以外を502とするエラーレスポンスを構築する
+fn invalid_request_error_response(e: DomainError) -> Response {
+    match e {
+        DomainError::InvalidMessage(reason) => {
+            admin_error(StatusCode::BAD_REQUEST, "bad_request", &reason)
+        }
+        e => domain_error_response(e),
+    }
+}
+
+/// データベースのリビジョン保持数を返すハンドラー
+pub async fn get_revs_limit_handler(
+    State(state): State<Arc<AppState>>,
+    Path(db): Path<String>,
+) -> Response {
+    match state.livesync_service.get_revs_limit(&db).await {
+        Ok(limit) => Json(serde_json::json!({
+            "db": db,
+            "revs_limit": limit,
+        }))
+        .into_response(),
+        Err(e) => invalid_request_error_response(e),
+    }
+}
+
+/// データベースのリビジョン保持数を変更するハンドラー
+pub async fn set_revs_limit_handler(
+    State(state): State<Arc<AppState>>,
+    Path(db): Path<String>,
+    Json(request): Json<RevsLimitRequest>,
+) -> Response {
+    match state
+        .livesync_service
+        .set_revs_limit(&db, request.limit)
+        .await
+    {
+        Ok(limit) => {
+            info!("Set revs limit of {} to {}", db, limit);
+            Json(serde_json::json!({
+                "ok": true,
+                "db": db,
+                "revs_limit": limit,
+            }))
+            .into_response()
+        }
+        Err(e) => invalid_request_error_response(e),
+    }
+}
+
+/// ユーザー作成リクエスト
+#[derive(Debug, Deserialize)]
+pub struct CreateUserRequest {
+    pub name: String,
+    pub password: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// CouchDBのユーザーを作成するハンドラー
+pub async fn create_user_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateUserRequest>,
+) -> Response {
+    match state
+        .livesync_service
+        .create_user(&request.name, &request.password, request.roles)
+        .await
+    {
+        Ok(()) => {
+            info!("Created CouchDB user {}", request.name);
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({
+                    "ok": true,
+                    "id": format!("org.couchdb.user:{}", request.name),
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => invalid_request_error_response(e),
+    }
+}
+
+/// データベースのセキュリティ設定の変更リクエスト
+#[derive(Debug, Deserialize)]
+pub struct SecurityRequest {
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub admins: Vec<String>,
+}
+
+/// データベースにアクセスできるユーザーを設定するハンドラー
+pub async fn set_security_handler(
+    State(state): State<Arc<AppState>>,
+    Path(db): Path<String>,
+    Json(request): Json<SecurityRequest>,
+) -> Response {
+    match state
+        .livesync_service
+        .set_db_security(&db, request.members, request.admins)
+        .await
+    {
+        Ok(()) => {
+            info!("Updated security of {}", db);
+            Json(serde_json::json!({ "ok": true, "db": db })).into_response()
+        }
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// 処理中の `/db` リクエストを古い順に返すハンドラー
+pub async fn inflight_handler(State(state): State<Arc<AppState>>) -> Json<Vec<Value>> {
+    Json(
+        state
+            .inflight
+            .snapshot()
+            .iter()
+            .map(|request| request.to_json())
+            .collect(),
+    )
+}
+
+/// 再送を待つ書き込みと、再送時に競合した書き込みを返すハンドラー
+pub async fn write_queue_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let to_json = |writes: Vec<QueuedWrite>| -> Vec<Value> {
+        writes.iter().map(QueuedWrite::to_json).collect()
+    };
+    Json(serde_json::json!({
+        "pending": to_json(state.write_queue.snapshot()),
+        "conflicts": to_json(state.write_queue.conflicts()),
+    }))
+}
+
+/// 解消した競合を競合リストから取り除くハンドラー
+pub async fn remove_write_conflict_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Response {
+    match state.write_queue.remove_conflict(id) {
+        Some(write) => {
+            info!(
+                "Removed conflicted write {}: {} {}",
+                id, write.method, write.path
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        None => admin_error(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            &format!("Conflicted write {} was not found", id),
+        ),
+    }
+}
+
+/// 記録したリクエストの一覧を返すハンドラー
+pub async fn captured_requests_handler(State(state): State<Arc<AppState>>) -> Json<Vec<Value>> {
+    Json(
+        state
+            .request_capture
+            .snapshot()
+            .iter()
+            .map(|request| request.to_json())
+            .collect(),
+    )
+}
+
+/// 記録したリクエストをCouchDBへ再送し、そのレスポンスを返すハンドラー
+pub async fn replay_handler(State(state): State<Arc<AppState>>, Path(id): Path<u64>) -> Response {
+    let Some(request) = state.request_capture.get(id) else {
+        return admin_error(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            &format!("Captured request {} was not found", id),
+        );
+    };
+
+    info!(
+        "Replaying captured request {}: {} {}",
+        id, request.method, request.path
+    );
+    let service = state.service_for_path(&request.path);
+    match service
+        .forward_request(
+            &request.method,
+            &request.path,
+            request.query.clone(),
+            request.headers.clone(),
+            request.body.clone(),
+        )
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => domain_error_response(e),
+    }
+}
+
+/// バッチ実行する個々の操作
+#[derive(Debug, Deserialize)]
+pub struct BatchOperation {
+    pub op: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+/// 操作を1つ実行する
+async fn run_batch_operation(state: &AppState, operation: BatchOperation) -> Value {
+    debug!(
+        "Running batch operation: {} {}",
+        operation.op, operation.args
+    );
+    let result = match operation.op.as_str() {
+        "status" => Ok(status_payload(state).await),
+        "databases" => databases_payload(state).await,
+        "tasks" => tasks_payload(state).await,
+        other => Err(DomainError::InvalidMessage(format!(
+            "Unknown batch operation: {}",
+            other
+        ))),
+    };
+
+    match result {
+        Ok(result) => serde_json::json!({
+            "op": operation.op,
+            "ok": true,
+            "result": result,
+        }),
+        Err(e) => serde_json::json!({
+            "op": operation.op,
+            "ok": false,
+            "error": e.to_string(),
+        }),
+    }
+}
+
+/// 複数の管理操作を並行して実行し、結果を配列で返すハンドラー
+pub async fn batch_handler(
+    State(state): State<Arc<AppState>>,
+    Json(operations): Json<Vec<BatchOperation>>,
+) -> Json<Vec<Value>> {
+    let results = join_all(
+        operations
+            .into_iter()
+            .map(|operation| run_batch_operation(&state, operation)),
+    )
+    .await;
+    Json(results)
+}
+
+/// 進捗をストリーミングするレプリケーションのリクエスト
+#[derive(Debug, Deserialize)]
+pub struct ReplicateRequest {
+    pub source: String,
+    pub target: String,
+    #[serde(default)]
+    pub options: Value,
+}
+
+/// `_active_tasks` から対象レプリケーションの `docs_read` と `docs_written` の合計を求める
+fn replication_progress(tasks: &Value, request: &ReplicateRequest) -> (u64, u64) {
+    let matches = |task: &Value, field: &str, name: &str| {
+        task.get(field)
+            .and_then(Value::as_str)
+            .is_some_and(|value| value.contains(name))
+    };
+
+    tasks
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|task| task.get("type").and_then(Value::as_str) == Some("replication"))
+        .filter(|task| {
+            matches(task, "source", &request.source) && matches(task, "target", &request.target)
+        })
+        .fold((0, 0), |(read, written), task| {
+            let count = |field: &str| task.get(field).and_then(Value::as_u64).unwrap_or(0);
+            (read + count("docs_read"), written + count("docs_written"))
+        })
+}
+
+/// SSEのイベントを構築する
+fn sse_event(name: &str, data: Value) -> Event {
+    Event::default().event(name).data(data.to_string())
+}
+
+/// レプリケーションを開始し、完了までの進捗をSSEで送信するハンドラー
+///
+/// `start` の後、`_active_tasks` の `docs_read`/`docs_written` の増分を `progress` として送り、
+/// 完了時に `done`（失敗時は `error`）を送って終了する。クライアントが切断した場合は
+/// ポーリングを停止する。
+pub async fn replicate_stream_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReplicateRequest>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (tx, rx) = tokio::sync::mpsc::channel::<Event>(16);
+
+    tokio::spawn(async move {
+        info!(
+            "Starting streamed replication from {} to {}",
+            request.source, request.target
+        );
+        let start = serde_json::json!({"source": request.source, "target": request.target});
+        if tx.send(sse_event("start", start)).await.is_err() {
+            return;
+        }
+
+        let service = Arc::clone(&state.livesync_service);
+        let replication =
+            service.handle_replication(&request.source, &request.target, request.options.clone());
+        tokio::pin!(replication);
+
+        let mut interval = tokio::time::interval(REPLICATION_POLL_INTERVAL);
+        let mut last = (0, 0);
+        let finished = loop {
+            tokio::select! {
+                result = &mut replication => break result,
+                _ = tx.closed() => {
+                    debug!("Replication stream client disconnected");
+                    return;
+                }
+                _ = interval.tick() => {
+                    let tasks = match service.active_tasks().await {
+                        Ok(tasks) => tasks,
+                        Err(e) => {
+                            debug!("Failed to poll replication progress: {}", e);
+                            continue;
+                        }
+                    };
+                    let (read, written) = replication_progress(&tasks, &request);
+                    if (read, written) == last {
+                        continue;
+                    }
+                    let progress = serde_json::json!({
+                        "docs_read": read.saturating_sub(last.0),
+                        "docs_written": written.saturating_sub(last.1),
+                        "total_docs_read": read,
+                        "total_docs_written": written,
+                    });
+                    last = (read, written);
+                    if tx.send(sse_event("progress", progress)).await.is_err() {
+                        debug!("Replication stream client disconnected");
+                        return;
+                    }
+                }
+            }
+        };
+
+        let event = match finished {
+            Ok(result) => sse_event("done", result),
+            Err(e) => {
+                warn!("Streamed replication failed: {}", e);
+                sse_event("error", serde_json::json!({"error": e.to_string()}))
+            }
+        };
+        let _ = tx.send(event).await;
+    });
+
+    let events = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok(event), rx))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
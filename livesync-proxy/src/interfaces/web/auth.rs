@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use axum::http::{header, HeaderMap, StatusCode};
+
+use crate::infrastructure::config::AdminConfig;
+
+/// 管理者APIの認証に成功した呼び出し元
+///
+/// 認証後のリクエストの拡張に格納されるため、ハンドラーから参照できる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+}
+
+/// 管理者APIの認証エラー
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Admin API is disabled because ADMIN_TOKEN is not configured")]
+    Disabled,
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+}
+
+impl AuthError {
+    /// レスポンスのステータスコード
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Disabled | Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// `error` フィールドに入れる識別子
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::Disabled | Self::Forbidden(_) => "forbidden",
+            Self::Unauthorized(_) => "unauthorized",
+        }
+    }
+}
+
+/// 管理者APIへのリクエストを認証する
+///
+/// 既定の `StaticTokenAuthenticator` の代わりに実装を差し替えることで、
+/// 外部サービスやJWTによる検証を組み込める。
+#[async_trait]
+pub trait AdminAuthenticator: Send + Sync {
+    /// リクエストヘッダーから呼び出し元を認証する
+    async fn authorize(&self, headers: &HeaderMap) -> Result<Principal, AuthError>;
+}
+
+/// `ADMIN_TOKEN` と一致するBearerトークンだけを受け付ける既定の認証
+#[derive(Debug, Clone, Default)]
+pub struct StaticTokenAuthenticator {
+    config: AdminConfig,
+}
+
+/// 静的トークンで認証された呼び出し元の名前
+pub const STATIC_TOKEN_PRINCIPAL: &str = "admin";
+
+impl StaticTokenAuthenticator {
+    pub fn new(config: AdminConfig) -> Self {
+        Self { config }
+    }
+
+    /// 設定されたトークンでヘッダーを検証する
+    pub fn check(config: &AdminConfig, headers: &HeaderMap) -> Result<Principal, AuthError> {
+        let Some(expected) = config.token.as_deref() else {
+            return Err(AuthError::Disabled);
+        };
+        match bearer_token(headers) {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+                Ok(Principal {
+                    name: STATIC_TOKEN_PRINCIPAL.to_string(),
+                })
+            }
+            _ => Err(AuthError::Unauthorized(
+                "A valid admin bearer token is required".to_string(),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl AdminAuthenticator for StaticTokenAuthenticator {
+    async fn authorize(&self, headers: &HeaderMap) -> Result<Principal, AuthError> {
+        Self::check(&self.config, headers)
+    }
+}
+
+/// タイミング攻撃を避けるため、長さが同じ場合は全バイトを比較する
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `Authorization: Bearer <token>` ヘッダーからトークンを取り出す
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
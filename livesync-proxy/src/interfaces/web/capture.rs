@@ -0,0 +1,148 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use axum::http::{header, HeaderMap};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::infrastructure::couchdb::UPSTREAM_AUTHORIZATION_HEADER;
+
+/// 記録しないヘッダー（認証情報を含むもの）
+const REDACTED_HEADERS: &[header::HeaderName] = &[
+    header::AUTHORIZATION,
+    header::PROXY_AUTHORIZATION,
+    header::COOKIE,
+];
+
+/// 再送のために記録したプロキシリクエスト
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub id: u64,
+    pub method: String,
+    /// CouchDBへ転送したパス（`/db` を除いたもの）
+    pub path: String,
+    pub query: Option<String>,
+    /// 認証関連のヘッダーを除いたリクエストヘッダー
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub captured_at: DateTime<Utc>,
+}
+
+impl CapturedRequest {
+    /// ボディのハッシュ値（同じボディかどうかを見比べるためのもの）
+    pub fn body_hash(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.body.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// 管理者APIで返すJSON表現（ボディそのものは含めない）
+    pub fn to_json(&self) -> Value {
+        let headers: serde_json::Map<String, Value> = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned()),
+                )
+            })
+            .collect();
+        serde_json::json!({
+            "id": self.id,
+            "method": self.method,
+            "path": self.path,
+            "query": self.query,
+            "headers": headers,
+            "body_length": self.body.len(),
+            "body_hash": self.body_hash(),
+            "captured_at": self.captured_at.to_rfc3339(),
+        })
+    }
+}
+
+/// 直近のプロキシリクエストを保持するリングバッファ
+///
+/// 同期の失敗を調査する際に、同じリクエストをCouchDBへ再送できるようにする。
+/// 上限が0の場合は何も記録しない。
+#[derive(Default)]
+pub struct RequestCapture {
+    capacity: AtomicUsize,
+    next_id: AtomicU64,
+    requests: Mutex<VecDeque<CapturedRequest>>,
+}
+
+impl RequestCapture {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: AtomicUsize::new(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// 保持する件数の上限を変更する（超過分は古いものから捨てる）
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::SeqCst);
+        let mut requests = self.requests.lock().unwrap();
+        while requests.len() > capacity {
+            requests.pop_front();
+        }
+    }
+
+    /// リクエストを記録し、採番したIDを返す（無効な場合はNone）
+    pub fn record(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        headers: &HeaderMap,
+        body: &Bytes,
+    ) -> Option<u64> {
+        let capacity = self.capacity.load(Ordering::SeqCst);
+        if capacity == 0 {
+            return None;
+        }
+
+        let mut headers = headers.clone();
+        for name in REDACTED_HEADERS {
+            headers.remove(name);
+        }
+        headers.remove(UPSTREAM_AUTHORIZATION_HEADER);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let request = CapturedRequest {
+            id,
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.map(str::to_string),
+            headers,
+            body: body.clone(),
+            captured_at: Utc::now(),
+        };
+
+        let mut requests = self.requests.lock().unwrap();
+        while requests.len() >= capacity {
+            requests.pop_front();
+        }
+        requests.push_back(request);
+        Some(id)
+    }
+
+    /// 記録したリクエストを取得する
+    pub fn get(&self, id: u64) -> Option<CapturedRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|request| request.id == id)
+            .cloned()
+    }
+
+    /// 記録したリクエストを古い順に返す
+    pub fn snapshot(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::infrastructure::config::ChaosConfig;
+use crate::interfaces::web::error::ProxyError;
+
+/// 0.0以上1.0未満の乱数を返す
+fn random_unit() -> f64 {
+    let (bits, _) = uuid::Uuid::new_v4().as_u64_pair();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 設定された確率で遅延または502を注入する
+///
+/// 遅延は待機してからNoneを返し、障害を注入する場合はクライアントに返すエラーを返す。
+/// `enabled` が設定されていない場合は何もしない。
+pub async fn inject_fault(config: &ChaosConfig, method: &str, path: &str) -> Option<ProxyError> {
+    if !config.enabled {
+        return None;
+    }
+
+    if config.delay_ms > 0 && random_unit() < config.delay_probability {
+        warn!(
+            "CHAOS: delaying {} {} by {} ms",
+            method, path, config.delay_ms
+        );
+        tokio::time::sleep(Duration::from_millis(config.delay_ms)).await;
+    }
+
+    if random_unit() < config.fault_probability {
+        warn!("CHAOS: injecting 502 for {} {}", method, path);
+        return Some(ProxyError::InjectedFault);
+    }
+
+    None
+}
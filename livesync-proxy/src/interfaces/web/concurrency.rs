@@ -0,0 +1,157 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axum::http::{header, HeaderMap};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::infrastructure::couchdb::ChangesFeedKind;
+
+/// クライアントごとの同時リクエスト数を制限する
+///
+/// longpollがスロットを占有し続けても他のクライアントが枯渇しないよう、
+/// 同一クライアントの同時実行数だけを制限する。
+pub struct ClientConcurrencyLimiter {
+    max_per_client: AtomicUsize,
+    clients: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+/// 確保したスロット（ドロップ時に解放される）
+pub struct ClientSlot {
+    key: String,
+    clients: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ClientConcurrencyLimiter {
+    /// 1クライアントあたりの上限を指定して作成（0は無制限）
+    pub fn new(max_per_client: usize) -> Self {
+        Self {
+            max_per_client: AtomicUsize::new(max_per_client),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 制限が有効かどうか
+    pub fn is_enabled(&self) -> bool {
+        self.max_per_client.load(Ordering::Relaxed) > 0
+    }
+
+    /// 1クライアントあたりの上限を変更する
+    ///
+    /// 処理中のリクエストがあるクライアントには、それらが完了した後の新しい枠から適用される。
+    pub fn set_max_per_client(&self, max_per_client: usize) {
+        self.max_per_client.store(max_per_client, Ordering::Relaxed);
+    }
+
+    /// スロットの確保を試みる（上限に達している場合はNone）
+    pub fn try_acquire(&self, key: &str) -> Option<ClientSlot> {
+        let semaphore = {
+            let mut clients = self.clients.lock().unwrap();
+            clients
+                .entry(key.to_string())
+                .or_insert_with(|| {
+                    Arc::new(Semaphore::new(self.max_per_client.load(Ordering::Relaxed)))
+                })
+                .clone()
+        };
+
+        semaphore.try_acquire_owned().ok().map(|permit| ClientSlot {
+            key: key.to_string(),
+            clients: Arc::clone(&self.clients),
+            permit: Some(permit),
+        })
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+
+        // 使用中のスロットがなくなったクライアントは削除してマップの肥大化を防ぐ
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(semaphore) = clients.get(&self.key) {
+            if Arc::strong_count(semaphore) == 1 {
+                clients.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// サーバー全体で同時に保持するlongpoll/continuousの接続数を制限する
+///
+/// longpollは最大120秒接続を占有するため、急増してもファイルディスクリプタを
+/// 使い切らないよう上限を設ける。通常のリクエストは制限しない。
+pub struct LongpollLimiter {
+    max_connections: AtomicUsize,
+    active: Arc<AtomicUsize>,
+}
+
+/// 確保したlongpollの枠（ドロップ時に解放される）
+pub struct LongpollSlot {
+    active: Arc<AtomicUsize>,
+}
+
+impl LongpollLimiter {
+    /// 上限を指定して作成（0は無制限）
+    pub fn new(max_connections: usize) -> Self {
+        Self {
+            max_connections: AtomicUsize::new(max_connections),
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// 上限を変更する（処理中の接続はそのまま維持される）
+    pub fn set_max_connections(&self, max_connections: usize) {
+        self.max_connections
+            .store(max_connections, Ordering::Relaxed);
+    }
+
+    /// 現在保持しているlongpollの数
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// 枠の確保を試みる（上限に達している場合はNone）
+    pub fn try_acquire(&self) -> Option<LongpollSlot> {
+        let max_connections = self.max_connections.load(Ordering::Relaxed);
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                (max_connections == 0 || active < max_connections).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| LongpollSlot {
+                active: Arc::clone(&self.active),
+            })
+    }
+}
+
+impl Drop for LongpollSlot {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// `_changes` へのリクエストがlongpollやcontinuousなど接続を保持するフィードか
+pub fn is_long_lived_feed(path: &str, query: Option<&str>) -> bool {
+    ChangesFeedKind::detect(path, query).is_some_and(ChangesFeedKind::is_long_lived)
+}
+
+/// リクエストのクライアント識別子を求める
+///
+/// 認証情報があればそのハッシュを、なければクライアントIPを使う。
+pub fn client_key(headers: &HeaderMap, client_ip: Option<IpAddr>) -> String {
+    if let Some(auth) = headers.get(header::AUTHORIZATION) {
+        let mut hasher = DefaultHasher::new();
+        auth.as_bytes().hash(&mut hasher);
+        return format!("auth:{:016x}", hasher.finish());
+    }
+
+    match client_ip {
+        Some(ip) => format!("ip:{}", ip),
+        None => "anonymous".to_string(),
+    }
+}
@@ -0,0 +1,149 @@
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::domain::models::DomainError;
+use crate::infrastructure::config::ErrorResponseFormat;
+use crate::interfaces::web::server::ALLOWED_DB_METHODS;
+
+/// longpollの上限に達したときにクライアントへ再試行を促すまでの秒数
+pub const LONGPOLL_RETRY_AFTER_SECS: u64 = 5;
+
+/// `/db` プロキシで発生するエラー
+///
+/// CouchDBと同じ `{"error": ..., "reason": ...}` 形式のJSONに変換される。
+/// テキスト形式が設定されている場合は `into_response_with_format` を使う。
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyError {
+    #[error("Method {0} is not supported by the proxy")]
+    MethodNotAllowed(String),
+
+    #[error("Too many concurrent requests from this client")]
+    TooManyRequests,
+
+    #[error("Request headers exceed the configured limits")]
+    HeaderFieldsTooLarge,
+
+    #[error("Failed to read request body: {0}")]
+    RequestBody(String),
+
+    #[error("Expectation failed: {0}")]
+    ExpectationFailed(String),
+
+    #[error("Path must not contain '.' or '..' segments")]
+    PathTraversal,
+
+    #[error("Failed to forward request to CouchDB: {0}")]
+    Upstream(String),
+
+    #[error("CouchDB did not respond in time: {0}")]
+    Timeout(String),
+
+    #[error("Failed to process response: {0}")]
+    ResponseBody(String),
+
+    #[error("Response exceeds the proxy buffer limit of {limit} bytes; request fewer documents at a time")]
+    ResponseTooLarge { limit: usize },
+
+    #[error("Fault injected by chaos mode")]
+    InjectedFault,
+
+    #[error("CouchDB is currently unavailable")]
+    CouchDbUnavailable,
+
+    #[error("Too many open longpoll connections, retry later")]
+    TooManyLongpolls,
+}
+
+impl ProxyError {
+    /// レスポンスのステータスコード
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            Self::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            Self::HeaderFieldsTooLarge => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            Self::RequestBody(_) | Self::ResponseBody(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ExpectationFailed(_) => StatusCode::EXPECTATION_FAILED,
+            Self::PathTraversal => StatusCode::BAD_REQUEST,
+            Self::Upstream(_) | Self::InjectedFault => StatusCode::BAD_GATEWAY,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::ResponseTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::CouchDbUnavailable | Self::TooManyLongpolls => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// `error` フィールドに入れる識別子
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::MethodNotAllowed(_) => "method_not_allowed",
+            Self::TooManyRequests => "too_many_requests",
+            Self::HeaderFieldsTooLarge => "request_header_fields_too_large",
+            Self::RequestBody(_) => "request_body_error",
+            Self::ExpectationFailed(_) => "expectation_failed",
+            Self::PathTraversal => "bad_request",
+            Self::Upstream(_) => "bad_gateway",
+            Self::Timeout(_) => "timeout",
+            Self::ResponseBody(_) => "response_body_error",
+            Self::ResponseTooLarge { .. } => "payload_too_large",
+            Self::InjectedFault => "chaos_fault",
+            Self::CouchDbUnavailable => "service_unavailable",
+            Self::TooManyLongpolls => "too_many_longpolls",
+        }
+    }
+
+    /// 指定した形式のボディでレスポンスに変換する
+    ///
+    /// テキスト形式では `error` と `reason` をそれぞれ1行ずつ出力する。
+    pub fn into_response_with_format(self, format: ErrorResponseFormat) -> Response {
+        let (content_type, body) = match format {
+            ErrorResponseFormat::Json => (
+                "application/json",
+                serde_json::json!({
+                    "error": self.error_code(),
+                    "reason": self.to_string(),
+                })
+                .to_string(),
+            ),
+            ErrorResponseFormat::Text => (
+                "text/plain; charset=utf-8",
+                format!("error: {}\nreason: {}\n", self.error_code(), self),
+            ),
+        };
+        let mut builder = Response::builder()
+            .status(self.status())
+            .header(header::CONTENT_TYPE, content_type);
+
+        match self {
+            Self::MethodNotAllowed(_) => {
+                builder = builder.header(header::ALLOW, ALLOWED_DB_METHODS.join(", "));
+            }
+            Self::TooManyRequests => {
+                builder = builder.header(header::RETRY_AFTER, "1");
+            }
+            Self::TooManyLongpolls => {
+                builder = builder.header(header::RETRY_AFTER, LONGPOLL_RETRY_AFTER_SECS);
+            }
+            _ => {}
+        }
+
+        builder.body(Body::from(body)).unwrap()
+    }
+}
+
+/// 上流へのリクエストの失敗を変換する（タイムアウトは504、それ以外は502）
+impl From<DomainError> for ProxyError {
+    fn from(e: DomainError) -> Self {
+        match e {
+            DomainError::Timeout(reason) => Self::Timeout(reason),
+            e => Self::Upstream(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> Response {
+        self.into_response_with_format(ErrorResponseFormat::Json)
+    }
+}
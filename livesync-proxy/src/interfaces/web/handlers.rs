@@ -1,19 +1,309 @@
 // This file is generated automatically
 
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use axum::{
     body::{to_bytes, Body},
-    extract::State,
-    http::{header, Request, StatusCode},
-    response::{IntoResponse, Response},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+    response::IntoResponse,
     Json,
 };
+use base64::{engine::general_purpose, Engine as _};
 use serde_json::Value;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use crate::domain::models::ChangesCheckpoint;
+use crate::infrastructure::config::ProxyConfig;
+use crate::infrastructure::couchdb::{
+    LongpollAborted, UPSTREAM_AUTHORIZATION_HEADER, UPSTREAM_TIMEOUT_HEADER,
+};
+use crate::interfaces::web::admin::has_admin_token;
+use crate::interfaces::web::concurrency::is_long_lived_feed;
+use crate::interfaces::web::error::ProxyError;
 use crate::interfaces::web::server::AppState;
+use crate::interfaces::web::write_queue::is_queueable_write;
+use crate::utils::{extract_client_ip, percent_decode};
+
+/// プロキシのバージョンを通知するレスポンスヘッダー名
+pub const PROXY_VERSION_HEADER: &str = "x-livesync-proxy";
+
+/// プロキシ経由のレスポンスに共通ヘッダーを付与する
+///
+/// 上流から受け取ったヘッダーのうち、`RESPONSE_HEADER_POLICY` で許可されないものは取り除く。
+pub fn apply_proxy_headers(headers: &mut HeaderMap, config: &ProxyConfig) {
+    let denied: Vec<_> = headers
+        .keys()
+        .filter(|name| !config.response_header_policy.permits(name.as_str()))
+        .cloned()
+        .collect();
+    for name in denied {
+        headers.remove(&name);
+    }
+
+    if config.version_header {
+        headers.insert(
+            PROXY_VERSION_HEADER,
+            HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+        );
+    }
+}
+
+/// リクエストのクライアントIPを取得する（接続情報がない場合はNone）
+pub fn request_client_ip(req: &Request<Body>, config: &ProxyConfig) -> Option<IpAddr> {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| extract_client_ip(*peer, req.headers(), &config.trusted_proxies))
+}
+
+/// タイムアウト上書きヘッダーの値を解釈し、上限値に丸める
+pub fn clamp_upstream_timeout(value: &str, max_secs: u64) -> Option<u64> {
+    value
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|secs| *secs > 0)
+        .map(|secs| secs.min(max_secs))
+}
+
+/// 上流の429を再試行する場合の待機時間を返す
+///
+/// 冪等なGET/HEADで、`Retry-After` が秒数で上限以下の場合のみ再試行する（上限0で無効）。
+pub fn retry_after_delay(
+    method: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    max_secs: u64,
+) -> Option<Duration> {
+    if max_secs == 0 || status != StatusCode::TOO_MANY_REQUESTS || !matches!(method, "GET" | "HEAD")
+    {
+        return None;
+    }
+    headers
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|secs| *secs <= max_secs)
+        .map(Duration::from_secs)
+}
+
+/// `attachments=true` などで返される `multipart/related` のレスポンスか
+///
+/// このレスポンスのContent-Typeには境界文字列が含まれるため、値を書き換えずに転送する必要がある。
+pub fn is_multipart_related(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("multipart/related")
+        })
+}
+
+/// パスに `.` や `..` のセグメントが含まれるか
+///
+/// CouchDB側で別のデータベースやエンドポイントを指さないよう、パーセントエンコーディングを
+/// デコードしてから判定する（`%2e%2e` や、二重にエンコードされた `%252e%252e` も対象）。
+/// `my.note` のようにドットを含むだけのドキュメントIDは該当しない。
+pub fn has_path_traversal(path: &str) -> bool {
+    let once = percent_decode(path);
+    let twice = percent_decode(&once);
+    [path, once.as_str(), twice.as_str()].iter().any(|path| {
+        path.split(['/', '\\'])
+            .any(|segment| segment == "." || segment == "..")
+    })
+}
+
+/// プロキシがバッファするリクエストボディの上限（10MB）
+pub const REQUEST_BODY_LIMIT: usize = 1024 * 1024 * 10;
+
+/// ボディをバッファせずに上流へ流すリクエストなら、その `Content-Length` を返す
+///
+/// `Content-Length` が `threshold` バイトを超える場合が対象（`threshold` が0の場合は無効）。
+/// 長さが分からないchunkedのリクエストは従来どおりバッファする。
+pub fn streamed_request_length(headers: &HeaderMap, threshold: u64) -> Option<u64> {
+    if threshold == 0 {
+        return None;
+    }
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|length| *length > threshold)
+}
+
+/// `Expect` ヘッダーに応えられない場合、その理由を返す
+///
+/// `100-continue` 以外の期待値は扱えない。`100-continue` でも `Content-Length` が
+/// 上限を超える場合は、ボディを受け取っても転送できないため断る。
+pub fn expectation_failure(headers: &HeaderMap, body_limit: usize) -> Option<String> {
+    let expect = headers.get(header::EXPECT)?;
+    let expect = expect.to_str().unwrap_or_default().trim();
+    if !expect.eq_ignore_ascii_case("100-continue") {
+        return Some(format!("unsupported expectation: {}", expect));
+    }
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<usize>().ok())?;
+    (content_length > body_limit).then(|| {
+        format!(
+            "request body of {} bytes exceeds the limit of {} bytes",
+            content_length, body_limit
+        )
+    })
+}
+
+/// 上流がContent-Typeを返さなかった場合に、ボディの先頭から推定する
+///
+/// `{`・`[` で始まればJSON、`<` で始まればHTML、それ以外はバイナリとみなす。
+/// ボディが空の場合は推定せずNoneを返す。
+pub fn sniff_content_type(body: &[u8]) -> Option<&'static str> {
+    let first = body.iter().find(|byte| !byte.is_ascii_whitespace())?;
+    Some(match first {
+        b'{' | b'[' => "application/json",
+        b'<' => "text/html",
+        _ => "application/octet-stream",
+    })
+}
+
+/// デバッグログに整形して出力するJSONボディの最大バイト数
+pub const PRETTY_DEBUG_JSON_MAX_BYTES: usize = 1000;
+
+/// 小さなJSONボディをデバッグログ用にインデント付きで再シリアライズする
+///
+/// 上限を超える場合やJSONとして読めない場合はNoneを返す。
+pub fn pretty_json_for_debug(body: &[u8]) -> Option<String> {
+    if body.len() >= PRETTY_DEBUG_JSON_MAX_BYTES {
+        return None;
+    }
+    let value: Value = serde_json::from_slice(body).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+/// 閾値を超えたリクエストを警告ログに出力する（出力した場合はtrue）
+///
+/// longpollなどの待機が前提のフィードは対象外。閾値が0の場合は出力しない。
+pub fn log_slow_request(
+    method: &str,
+    path: &str,
+    query: Option<&str>,
+    status: u16,
+    duration: Duration,
+    threshold: Duration,
+) -> bool {
+    if threshold.is_zero() || duration <= threshold || is_long_lived_feed(path, query) {
+        return false;
+    }
+
+    warn!(
+        "Slow request: {} {} -> {} in {} ms (threshold {} ms)",
+        method,
+        path,
+        status,
+        duration.as_millis(),
+        threshold.as_millis()
+    );
+    true
+}
+
+/// 書き換え前のクライアント向けのパスを上流に伝えるヘッダー名
+pub const ORIGINAL_PATH_HEADER: &str = "x-original-path";
+/// 書き換え前のクエリ文字列を上流に伝えるヘッダー名
+pub const ORIGINAL_QUERY_HEADER: &str = "x-original-query";
+/// 上流のリダイレクト先をプロキシ経由のパスに書き換える
+///
+/// CouchDBのベースURL配下を指す `Location`、またはCouchDBから見た絶対パスを
+/// `/db` 配下のパスにする。外部のホストなど書き換えられないものはNoneを返す。
+pub fn rewrite_upstream_location(location: &str, upstream_base: &str) -> Option<String> {
+    let base = upstream_base.trim_end_matches('/');
+    let rest = if let Some(rest) = location.strip_prefix(base) {
+        rest
+    } else if location.starts_with('/') && !location.starts_with("//") {
+        // ベースURLにパスが含まれる場合（`http://host/couchdb/` など）はそれも取り除く
+        let base_path = url::Url::parse(base)
+            .map(|url| url.path().trim_end_matches('/').to_string())
+            .unwrap_or_default();
+        location.strip_prefix(base_path.as_str())?
+    } else {
+        return None;
+    };
+
+    if !(rest.is_empty() || rest.starts_with('/') || rest.starts_with('?')) {
+        return None;
+    }
+    Some(format!("/db/{}", rest.trim_start_matches('/')))
+}
+
+/// 監査用ヘッダーに入れる値の最大長（バイト）
+const ORIGINAL_PATH_MAX_BYTES: usize = 2048;
+
+/// 値をヘッダーに入れられる1行の文字列にする
+///
+/// 表示可能なASCIIと空白以外（改行などの制御文字を含む）は `?` に置き換え、長すぎる値は切り詰める。
+pub fn header_safe_line(value: &str) -> HeaderValue {
+    let line: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() || c == ' ' {
+                c
+            } else {
+                '?'
+            }
+        })
+        .take(ORIGINAL_PATH_MAX_BYTES)
+        .collect();
+    HeaderValue::from_str(&line).expect("header-safe characters only")
+}
+
+/// 空のlongpollに対して合成するレスポンス（上流のボディが空の場合のみ使う）
+pub const EMPTY_LONGPOLL_BODY: &str = r#"{"results":[],"last_seq":"0"}"#;
+
+/// `_changes` のレスポンスから `last_seq` をCouchDBが返した表記のまま取り出す
+pub fn changes_last_seq(body: &[u8]) -> Option<&str> {
+    serde_json::from_slice::<ChangesCheckpoint>(body)
+        .ok()
+        .map(|checkpoint| checkpoint.last_seq.get())
+}
+
+/// 直接の接続元が信頼済みプロキシか、管理者トークンを持つ場合に信頼する
+fn is_trusted_source(req: &Request<Body>, state: &AppState) -> bool {
+    is_trusted_peer(req, &state.proxy_config())
+        || has_admin_token(req.headers(), &state.admin_config)
+}
+
+/// 接続元が信頼できる上流プロキシか
+fn is_trusted_peer(req: &Request<Body>, proxy_config: &ProxyConfig) -> bool {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .is_some_and(|ConnectInfo(peer)| {
+            let ip = peer.ip();
+            proxy_config
+                .trusted_proxies
+                .iter()
+                .any(|net| net.contains(&ip))
+        })
+}
+
+/// 信頼できるプロキシが認証したユーザー名を伝えるリクエストヘッダー名
+pub const FORWARDED_USER_HEADER: &str = "x-auth-user";
+
+/// 転送されたユーザーに対応するCouchDBの `Authorization` の値を返す
+fn forwarded_user_authorization(user: &str, proxy_config: &ProxyConfig) -> Option<HeaderValue> {
+    let credentials = proxy_config
+        .forwarded_auth_users
+        .iter()
+        .find(|entry| entry.user == user)?;
+    let encoded = general_purpose::STANDARD
+        .encode(format!("{}:{}", credentials.username, credentials.password));
+    let mut value = HeaderValue::from_str(&format!("Basic {}", encoded)).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
 
 /// HTTP proxy handler for Obsidian LiveSync
 /// This handler proxies HTTP requests to the CouchDB server
@@ -28,74 +318,269 @@ pub async fn http_proxy_handler(
     let method = req.method().clone();
     let uri_path = req.uri().path().to_string();
     let query = req.uri().query().map(String::from);
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
 
-    info!("CouchDB proxy request: {} {}", method, uri_path);
+    // リロードされても1リクエストの間は同じ設定を使う
+    let proxy_config = state.proxy_config();
+
+    let client_ip = request_client_ip(&req, &proxy_config);
+
+    info!(
+        "CouchDB proxy request: {} {} (client: {:?})",
+        method, uri_path, client_ip
+    );
 
     // /dbプレフィックスを除去
     let stripped_path = uri_path.trim_start_matches("/db").trim_start_matches("/");
 
+    // `..` などで対象のデータベースの外を指すパスは転送しない
+    if has_path_traversal(stripped_path) {
+        warn!("Rejecting path with traversal segments: {}", uri_path);
+        let mut response =
+            ProxyError::PathTraversal.into_response_with_format(proxy_config.error_response_format);
+        apply_proxy_headers(response.headers_mut(), &proxy_config);
+
+        // メトリクスを記録
+        state
+            .metrics_state
+            .record_request_duration(&uri_path, method.as_str(), start);
+        state
+            .metrics_state
+            .record_request(&uri_path, method.as_str(), 400, user_agent.as_deref())
+            .await;
+        return response;
+    }
+
     // CouchDBへのパスをマッピング
-    let couchdb_path = if stripped_path.is_empty() {
+    // （設定により `/db` 自体はCouchDBのルートではなくデフォルトデータベースに向ける）
+    let couchdb_path = if stripped_path.is_empty() && proxy_config.map_root_to_default_db {
+        debug!("Mapping /db to default database {}", state.default_db);
+        format!("/{}", state.default_db)
+    } else if stripped_path.is_empty() {
         "/".to_string()
     } else {
         format!("/{}", stripped_path)
     };
 
+    // タイムアウト上書きは信頼できる送信元からのみ受け付ける
+    let trusted_source = is_trusted_source(&req, &state);
+    // 転送されたユーザーは接続元が信頼できるプロキシの場合のみ受け付ける
+    let trusted_peer = is_trusted_peer(&req, &proxy_config);
+
     // リクエストのヘッダーとボディを抽出
     let (parts, body) = req.into_parts();
-    let headers = parts.headers;
+    let mut headers = parts.headers;
 
-    // ボディをバイト列に変換
-    let body_bytes = match to_bytes(body, 1024 * 1024 * 10).await {
-        // 10MB制限
-        Ok(bytes) => bytes,
-        Err(e) => {
-            debug!("Failed to read request body: {}", e);
-            let response = Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .header(header::CONTENT_TYPE, "application/json")
-                .body(Body::from(format!(
-                    r#"{{"error":"Failed to read request body: {}"}}"#,
-                    e
-                )))
-                .unwrap();
+    // 監査用に書き換え前のパスを伝える（クライアントが付けた同名のヘッダーは上書きする）
+    if proxy_config.forward_original_path {
+        headers.insert(ORIGINAL_PATH_HEADER, header_safe_line(&uri_path));
+        match query.as_deref() {
+            Some(query) => {
+                headers.insert(ORIGINAL_QUERY_HEADER, header_safe_line(query));
+            }
+            None => {
+                headers.remove(ORIGINAL_QUERY_HEADER);
+            }
+        }
+    }
 
-            // メトリクスを記録
-            state
-                .metrics_state
-                .record_request_duration(&uri_path, method.as_str(), start);
-            state
-                .metrics_state
-                .record_request(&uri_path, method.as_str(), 500)
-                .await;
+    if let Some(value) = headers.remove(UPSTREAM_TIMEOUT_HEADER) {
+        let clamped = value
+            .to_str()
+            .ok()
+            .and_then(|v| clamp_upstream_timeout(v, proxy_config.max_upstream_timeout_secs));
+        match clamped {
+            Some(secs) if trusted_source => {
+                debug!("Honoring upstream timeout override: {} seconds", secs);
+                headers.insert(UPSTREAM_TIMEOUT_HEADER, HeaderValue::from(secs));
+            }
+            _ => debug!("Ignoring upstream timeout override from untrusted or invalid request"),
+        }
+    }
 
-            return response;
+    // 上流の認証情報はクライアントから指定させない
+    headers.remove(UPSTREAM_AUTHORIZATION_HEADER);
+    if !proxy_config.forwarded_auth_users.is_empty() {
+        if let Some(user) = headers.remove(FORWARDED_USER_HEADER) {
+            let authorization = user
+                .to_str()
+                .ok()
+                .filter(|_| trusted_peer)
+                .and_then(|user| forwarded_user_authorization(user.trim(), &proxy_config));
+            match authorization {
+                Some(value) => {
+                    debug!("Using upstream credentials mapped to the forwarded user");
+                    headers.insert(UPSTREAM_AUTHORIZATION_HEADER, value);
+                }
+                None => debug!("Ignoring forwarded user from untrusted peer or unknown user"),
+            }
         }
-    };
+    }
+
+    // 設定したサイズを超えるボディはバッファせずに上流へ流す
+    let streamed_length =
+        streamed_request_length(&headers, proxy_config.stream_request_body_threshold);
+
+    // `Expect: 100-continue` にはボディを読み始めた時点で100が返るため、
+    // 受け付けられないリクエストはボディを読む前に417で断る
+    // （ヘッダーはそのまま上流へ転送し、上流の417もそのまま返す。
+    // ストリーミングで転送するボディはバッファしないため上限の対象外）
+    if let Some(reason) =
+        expectation_failure(&headers, REQUEST_BODY_LIMIT).filter(|_| streamed_length.is_none())
+    {
+        debug!("Rejecting request before reading its body: {}", reason);
+        let mut response = ProxyError::ExpectationFailed(reason)
+            .into_response_with_format(proxy_config.error_response_format);
+        apply_proxy_headers(response.headers_mut(), &proxy_config);
+
+        // メトリクスを記録
+        state
+            .metrics_state
+            .record_request_duration(&uri_path, method.as_str(), start);
+        state
+            .metrics_state
+            .record_request(&uri_path, method.as_str(), 417, user_agent.as_deref())
+            .await;
+
+        return response;
+    }
 
-    // リクエストをCouchDBに転送
-    let response = match state
-        .livesync_service
-        .forward_request(
+    let service = state.service_for_path(&couchdb_path);
+    let result = if let Some(length) = streamed_length {
+        // 記録・書き込みキュー・429の再試行はボディを保持する必要があるため対象外
+        info!(
+            "Streaming request body of {} bytes for {} {}",
+            length, method, couchdb_path
+        );
+        state.metrics_state.record_database_bytes(&uri_path, length);
+        service
+            .forward_request_stream(method.as_str(), &couchdb_path, query.clone(), headers, body)
+            .await
+    } else {
+        // ボディをバイト列に変換
+        let body_bytes = match to_bytes(body, REQUEST_BODY_LIMIT).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                debug!("Failed to read request body: {}", e);
+                let mut response = ProxyError::RequestBody(e.to_string())
+                    .into_response_with_format(proxy_config.error_response_format);
+                apply_proxy_headers(response.headers_mut(), &proxy_config);
+
+                // メトリクスを記録
+                state
+                    .metrics_state
+                    .record_request_duration(&uri_path, method.as_str(), start);
+                state
+                    .metrics_state
+                    .record_request(&uri_path, method.as_str(), 500, user_agent.as_deref())
+                    .await;
+
+                return response;
+            }
+        };
+
+        state
+            .metrics_state
+            .record_database_bytes(&uri_path, body_bytes.len() as u64);
+
+        // 設定されていれば、後から再送できるようにリクエストを記録する
+        if let Some(id) = state.request_capture.record(
             method.as_str(),
             &couchdb_path,
-            query.clone(),
-            headers,
-            body_bytes,
-        )
-        .await
-    {
+            query.as_deref(),
+            &headers,
+            &body_bytes,
+        ) {
+            debug!("Captured {} {} as request {}", method, couchdb_path, id);
+        }
+
+        // CouchDBの停止中の書き込みは、設定によりキューに入れて202を返す（復旧後に順に再送する）
+        if state.write_queue.is_enabled()
+            && is_queueable_write(method.as_str(), &couchdb_path)
+            && !state.health_state.couchdb_status.read().await.available
+        {
+            match state.write_queue.push(
+                method.as_str(),
+                &couchdb_path,
+                query.as_deref(),
+                &headers,
+                &body_bytes,
+            ) {
+                Some(id) => {
+                    info!(
+                        "CouchDB is unavailable, queued {} {} as write {}",
+                        method, couchdb_path, id
+                    );
+                    let mut response = (
+                        StatusCode::ACCEPTED,
+                        Json(serde_json::json!({"ok": true, "queued": true, "id": id})),
+                    )
+                        .into_response();
+                    apply_proxy_headers(response.headers_mut(), &proxy_config);
+
+                    // メトリクスを記録
+                    state
+                        .metrics_state
+                        .record_request_duration(&uri_path, method.as_str(), start);
+                    state
+                        .metrics_state
+                        .record_request(&uri_path, method.as_str(), 202, user_agent.as_deref())
+                        .await;
+
+                    return response;
+                }
+                None => warn!(
+                    "Write queue is full, forwarding {} {} without queueing",
+                    method, couchdb_path
+                ),
+            }
+        }
+
+        // リクエストをCouchDBに転送（プレフィックスに応じてバックエンドを選ぶ）
+        let forward = |headers: HeaderMap| {
+            service.forward_request(
+                method.as_str(),
+                &couchdb_path,
+                query.clone(),
+                headers,
+                body_bytes.clone(),
+            )
+        };
+        let mut result = forward(headers.clone()).await;
+
+        // 短い `Retry-After` 付きの429は設定により1回だけ待って再試行する
+        let retry_delay = result.as_ref().ok().and_then(|resp| {
+            retry_after_delay(
+                method.as_str(),
+                resp.status(),
+                resp.headers(),
+                proxy_config.retry_after_max_secs,
+            )
+        });
+        if let Some(delay) = retry_delay {
+            info!(
+                "CouchDB returned 429 for {} {}, retrying after {:?}",
+                method, couchdb_path, delay
+            );
+            tokio::time::sleep(delay).await;
+            result = forward(headers).await;
+        }
+        result
+    };
+
+    let mut response = match result {
         Ok(resp) => resp,
         Err(e) => {
             debug!("Failed to forward request to CouchDB: {}", e);
-            let response = Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .header(header::CONTENT_TYPE, "application/json")
-                .body(Body::from(format!(
-                    r#"{{"error":"Failed to forward request to CouchDB: {}"}}"#,
-                    e
-                )))
-                .unwrap();
+            // タイムアウトは504、それ以外は502で返す
+            let error = ProxyError::from(e);
+            let status = error.status().as_u16();
+            let mut response = error.into_response_with_format(proxy_config.error_response_format);
+            apply_proxy_headers(response.headers_mut(), &proxy_config);
 
             // メトリクスを記録
             state
@@ -103,16 +588,56 @@ pub async fn http_proxy_handler(
                 .record_request_duration(&uri_path, method.as_str(), start);
             state
                 .metrics_state
-                .record_request(&uri_path, method.as_str(), 502)
+                .record_request(&uri_path, method.as_str(), status, user_agent.as_deref())
                 .await;
 
             return response;
         }
     };
 
+    // 上流のリダイレクトはCouchDBのURLを見せず、プロキシ経由のパスに向け直す
+    if response.status().is_redirection() {
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        if let Some(location) = location {
+            match rewrite_upstream_location(&location, &service.get_couchdb_url()) {
+                Some(rewritten) => {
+                    debug!("Rewriting upstream Location {} to {}", location, rewritten);
+                    response
+                        .headers_mut()
+                        .insert(header::LOCATION, header_safe_line(&rewritten));
+                }
+                None => warn!(
+                    "CouchDB redirected {} {} outside of its base URL: {}",
+                    method, couchdb_path, location
+                ),
+            }
+        }
+    }
+
+    // プロキシ経由であることを示すヘッダーを付与
+    apply_proxy_headers(response.headers_mut(), &proxy_config);
+
+    // クライアント側の中断は通常のエラーと区別して集計する
+    if response.extensions().get::<LongpollAborted>().is_some() {
+        state.metrics_state.record_longpoll_abort().await;
+    }
+
     // レスポンスのステータスコードを取得
     let status_code = response.status().as_u16();
 
+    log_slow_request(
+        method.as_str(),
+        &uri_path,
+        query.as_deref(),
+        status_code,
+        start.elapsed(),
+        Duration::from_millis(proxy_config.slow_request_threshold_ms),
+    );
+
     // メトリクスを記録
     state
         .metrics_state
@@ -126,19 +651,27 @@ pub async fn http_proxy_handler(
 
     tokio::spawn(async move {
         metrics_state
-            .record_request(&uri_path_clone, &method_str, status_code_clone)
+            .record_request(
+                &uri_path_clone,
+                &method_str,
+                status_code_clone,
+                user_agent.as_deref(),
+            )
             .await;
     });
 
     response
 }
 
-/// サーバーのステータス情報を返すハンドラー
-pub async fn status_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
+/// サーバーのステータス情報を構築する
+pub async fn status_payload(state: &AppState) -> Value {
+    // 上流のバージョンはヘルスチェックで取得済みの場合のみ含める（ここでは問い合わせない）
+    let server_info = state.health_state.server_info().await;
+
     // CouchDBの状態を取得
     let couchdb_status = state.health_state.couchdb_status.read().await;
 
-    Json(serde_json::json!({
+    serde_json::json!({
         "status": if couchdb_status.available { "ok" } else { "degraded" },
         "version": env!("CARGO_PKG_VERSION"),
         "services": {
@@ -147,10 +680,18 @@ pub async fn status_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
                 "last_checked": couchdb_status.last_checked.duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs(),
-                "error": couchdb_status.error_message
+                "error": couchdb_status.error_message,
+                "version": server_info.as_ref().map(|info| &info.version),
+                "features": server_info.as_ref().map(|info| &info.features),
+                "clustered": state.livesync_service.is_clustered()
             }
         }
-    }))
+    })
+}
+
+/// サーバーのステータス情報を返すハンドラー
+pub async fn status_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
+    Json(status_payload(&state).await)
 }
 
 /// デバッグ用の単純なハンドラー
@@ -1,13 +1,64 @@
-use axum::{extract::State, routing::get, Json, Router};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 use crate::application::services::LiveSyncService;
-use crate::infrastructure::couchdb::CouchDbClient;
+use crate::domain::models::ServerInfo;
+use crate::interfaces::web::server::AppState;
+
+/// チェック結果を有効とみなす最大の経過時間の既定値（秒）
+///
+/// バックオフによるチェック間隔の上限（5分）より長くしておく。
+pub const DEFAULT_MAX_STATUS_AGE_SECS: u64 = 600;
+
+/// ヘルスチェック間隔に加えるジッターの割合の既定値（±20%）
+pub const DEFAULT_JITTER_FRACTION: f64 = 0.2;
+
+/// レディネスで確認したドキュメント数を再利用する期間の既定値
+pub const DEFAULT_DOC_COUNT_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// ヘルスチェック間隔のジッターに使う乱数生成器（SplitMix64）
+///
+/// 暗号用途ではない。シードを指定すると同じ系列を再現できる。
+#[derive(Debug, Clone)]
+pub struct JitterRng {
+    state: u64,
+}
+
+impl JitterRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // レプリカごとに異なる系列になるようランダムなシードで作成する
+    pub fn from_entropy() -> Self {
+        Self::new(uuid::Uuid::new_v4().as_u64_pair().0)
+    }
+
+    /// 0.0以上1.0未満の乱数を返す
+    pub fn next_unit(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// 間隔を ±`fraction` の範囲でランダムにずらす
+///
+/// 複数のレプリカが同じ間隔で一斉にCouchDBへ問い合わせないようにする。
+pub fn jittered_interval(interval: Duration, fraction: f64, rng: &mut JitterRng) -> Duration {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let factor = 1.0 + fraction * (2.0 * rng.next_unit() - 1.0);
+    interval.mul_f64(factor)
+}
 
 // ヘルスチェックの状態
 pub struct HealthState {
@@ -21,6 +72,22 @@ pub struct HealthState {
     status: RwLock<HealthStatus>,
     last_couchdb_check: RwLock<Option<Instant>>,
     couchdb_errors: RwLock<u32>,
+    // バックグラウンドのヘルスチェックを停止するための通知
+    shutdown: Notify,
+    // これより古いチェック結果は信頼しない（0で無効）
+    max_status_age: Duration,
+    // チェック間隔とバックオフに加えるジッターの割合
+    jitter_fraction: f64,
+    // レディネスに必要なデータベースと最小ドキュメント数（0で無効）
+    min_doc_count_db: String,
+    min_doc_count: u64,
+    // 直近に確認したドキュメント数と確認時刻
+    doc_count_cache: RwLock<Option<(Instant, u64)>>,
+    doc_count_cache_ttl: Duration,
+    // 起動時に作成できなかったデータベース
+    failed_databases: Vec<String>,
+    // 直近に取得できたCouchDBのバージョン情報（ステータスAPIはこれを返す）
+    server_info: RwLock<Option<ServerInfo>>,
 }
 
 // CouchDBの状態
@@ -38,6 +105,8 @@ pub struct HealthResponse {
     pub uptime_seconds: u64,
     pub version: String,
     pub services: ServiceStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 // サービスの状態
@@ -70,6 +139,123 @@ impl HealthState {
             status: RwLock::new(HealthStatus::Healthy),
             last_couchdb_check: RwLock::new(None),
             couchdb_errors: RwLock::new(0),
+            shutdown: Notify::new(),
+            max_status_age: Duration::from_secs(DEFAULT_MAX_STATUS_AGE_SECS),
+            jitter_fraction: DEFAULT_JITTER_FRACTION,
+            min_doc_count_db: String::new(),
+            min_doc_count: 0,
+            doc_count_cache: RwLock::new(None),
+            doc_count_cache_ttl: DEFAULT_DOC_COUNT_CACHE_TTL,
+            failed_databases: Vec::new(),
+            server_info: RwLock::new(None),
+        }
+    }
+
+    // 指定したデータベースのドキュメント数が最小値に達するまでレディネスを失敗させる（0で無効）
+    pub fn with_min_doc_count(mut self, db_name: &str, min_doc_count: u64) -> Self {
+        self.min_doc_count_db = db_name.to_string();
+        self.min_doc_count = min_doc_count;
+        self
+    }
+
+    // 起動時に作成できなかったデータベースを指定する（サービスは継続し、理由として報告する）
+    pub fn with_failed_databases(mut self, databases: Vec<String>) -> Self {
+        self.failed_databases = databases;
+        self
+    }
+
+    // 一部のデータベースを作成できなかった場合、その理由を返す
+    pub fn failed_databases_reason(&self) -> Option<String> {
+        (!self.failed_databases.is_empty()).then(|| {
+            format!(
+                "Failed to ensure databases: {}",
+                self.failed_databases.join(", ")
+            )
+        })
+    }
+
+    // 確認したドキュメント数を再利用する期間を指定する
+    pub fn with_doc_count_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.doc_count_cache_ttl = ttl;
+        self
+    }
+
+    // ドキュメント数の要件を満たしていない場合、その理由を返す
+    //
+    // レディネスのプローブごとに評価するが、短い期間は前回の件数を再利用する。
+    pub async fn unmet_doc_count(&self) -> Option<String> {
+        if self.min_doc_count == 0 {
+            return None;
+        }
+
+        let cached = self
+            .doc_count_cache
+            .read()
+            .await
+            .filter(|(checked_at, _)| checked_at.elapsed() < self.doc_count_cache_ttl)
+            .map(|(_, count)| count);
+        let doc_count = match cached {
+            Some(count) => count,
+            None => match self
+                .livesync_service
+                .database_info(&self.min_doc_count_db)
+                .await
+            {
+                Ok(info) => {
+                    *self.doc_count_cache.write().await = Some((Instant::now(), info.doc_count));
+                    info.doc_count
+                }
+                Err(e) => {
+                    return Some(format!(
+                        "Failed to get document count of {}: {}",
+                        self.min_doc_count_db, e
+                    ))
+                }
+            },
+        };
+
+        (doc_count < self.min_doc_count).then(|| {
+            format!(
+                "Database {} has {} documents, waiting for at least {}",
+                self.min_doc_count_db, doc_count, self.min_doc_count
+            )
+        })
+    }
+
+    // チェック間隔とバックオフに加えるジッターの割合を指定する（0で無効、最大1.0）
+    pub fn with_jitter_fraction(mut self, jitter_fraction: f64) -> Self {
+        self.jitter_fraction = jitter_fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    // チェック結果を有効とみなす最大の経過時間を指定する（0で無効）
+    pub fn with_max_status_age(mut self, max_status_age: Duration) -> Self {
+        self.max_status_age = max_status_age;
+        self
+    }
+
+    // 最後のチェックから最大経過時間を過ぎているか
+    pub fn is_stale(&self, status: &CouchDbStatus) -> bool {
+        !self.max_status_age.is_zero()
+            && SystemTime::now()
+                .duration_since(status.last_checked)
+                .unwrap_or_default()
+                > self.max_status_age
+    }
+
+    // 直近に取得できたCouchDBのバージョン情報を返す（未取得の場合はNone）
+    pub async fn server_info(&self) -> Option<ServerInfo> {
+        self.server_info.read().await.clone()
+    }
+
+    // CouchDBのバージョン情報を取得し直す（取得できなかった場合は前回の値を残す）
+    pub async fn refresh_server_info(&self) {
+        match tokio::time::timeout(Duration::from_secs(5), self.livesync_service.server_info())
+            .await
+        {
+            Ok(Ok(info)) => *self.server_info.write().await = Some(info),
+            Ok(Err(e)) => debug!("Failed to get CouchDB server info: {}", e),
+            Err(_) => debug!("Getting CouchDB server info timed out"),
         }
     }
 
@@ -88,8 +274,8 @@ impl HealthState {
         }
     }
 
-    // バックグラウンドでヘルスチェックを開始する
-    pub fn start_background_health_check(self: &Arc<Self>) {
+    // バックグラウンドでヘルスチェックを開始する（`stop()` で終了する）
+    pub fn start_background_health_check(self: &Arc<Self>) -> JoinHandle<()> {
         let health_state = Arc::clone(self);
 
         tokio::spawn(async move {
@@ -98,80 +284,86 @@ impl HealthState {
                 health_state.check_interval
             );
 
-            // 初期間隔を設定
-            let mut current_interval = health_state.check_interval;
+            // 初期間隔を設定（レプリカ間で同期しないようジッターを加える）
+            let mut rng = JitterRng::from_entropy();
+            let jitter = health_state.jitter_fraction;
+            let mut current_interval =
+                jittered_interval(health_state.check_interval, jitter, &mut rng);
 
             loop {
-                tokio::time::sleep(current_interval).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(current_interval) => {}
+                    _ = health_state.shutdown.notified() => {
+                        info!("Stopping background health check");
+                        break;
+                    }
+                }
                 debug!("Performing CouchDB health check");
 
-                let couchdb_url = health_state.livesync_service.get_couchdb_url();
-                let couchdb_auth = health_state.livesync_service.get_couchdb_auth();
-
-                if let Some((username, password)) = couchdb_auth {
-                    let couchdb_client = CouchDbClient::new(&couchdb_url, &username, &password);
-
-                    // タイムアウト付きPing
-                    let ping_result = tokio::time::timeout(
-                        Duration::from_secs(5), // 5秒タイムアウト
-                        couchdb_client.ping(),
-                    )
-                    .await;
-
-                    // エラーケースを適切に処理
-                    match ping_result {
-                        // 正常応答
-                        Ok(Ok(_)) => {
-                            // 成功したので連続失敗カウンターをリセット
-                            health_state.consecutive_failures.store(0, Ordering::SeqCst);
-                            // 通常の間隔に戻す
-                            current_interval = health_state.check_interval;
-                            health_state.update_couchdb_status(true, None).await;
-                            health_state.record_couchdb_success().await;
-                        }
-                        // エラー（CouchDBエラーまたはタイムアウト）
-                        _ => {
-                            let error_msg = match &ping_result {
-                                Ok(Err(e)) => format!("CouchDB connection error: {}", e),
-                                Err(_) => "CouchDB connection timed out".to_string(),
-                                _ => "Unknown error".to_string(),
-                            };
-
-                            // 連続失敗カウンターを増加
-                            let failures = health_state
-                                .consecutive_failures
-                                .fetch_add(1, Ordering::SeqCst)
-                                + 1;
-
-                            // バックオフ戦略: 2^n秒（最大max_check_intervalまで）
-                            let backoff_secs = std::cmp::min(
-                                2u64.pow(failures),
-                                health_state.max_check_interval.as_secs(),
-                            );
-
-                            warn!(
-                                "CouchDB health check failed {} times in a row. Next check in {} seconds. Error: {}", 
-                                failures, backoff_secs, error_msg
-                            );
-
-                            // 次回のチェック間隔を計算
-                            current_interval = Duration::from_secs(backoff_secs);
-
-                            health_state
-                                .update_couchdb_status(false, Some(error_msg))
-                                .await;
-                            health_state.record_couchdb_error().await;
-                        }
+                // サービスが使う設定済みのクライアントでタイムアウト付きPing
+                // （接続設定・TLSの最小バージョン・Unixソケットをそのまま使う）
+                let ping_result = tokio::time::timeout(
+                    Duration::from_secs(5), // 5秒タイムアウト
+                    health_state.livesync_service.ping(),
+                )
+                .await;
+
+                // エラーケースを適切に処理
+                match ping_result {
+                    // 正常応答
+                    Ok(Ok(_)) => {
+                        // 成功したので連続失敗カウンターをリセット
+                        health_state.consecutive_failures.store(0, Ordering::SeqCst);
+                        // 通常の間隔に戻す
+                        current_interval =
+                            jittered_interval(health_state.check_interval, jitter, &mut rng);
+                        health_state.update_couchdb_status(true, None).await;
+                        health_state.record_couchdb_success().await;
+                        health_state.refresh_server_info().await;
+                    }
+                    // エラー（CouchDBエラーまたはタイムアウト）
+                    _ => {
+                        let error_msg = match &ping_result {
+                            Ok(Err(e)) => format!("CouchDB connection error: {}", e),
+                            Err(_) => "CouchDB connection timed out".to_string(),
+                            _ => "Unknown error".to_string(),
+                        };
+
+                        // 連続失敗カウンターを増加
+                        let failures = health_state
+                            .consecutive_failures
+                            .fetch_add(1, Ordering::SeqCst)
+                            + 1;
+
+                        // バックオフ戦略: 2^n秒（最大max_check_intervalまで）
+                        let backoff_secs = std::cmp::min(
+                            2u64.pow(failures),
+                            health_state.max_check_interval.as_secs(),
+                        );
+
+                        // 次回のチェック間隔を計算
+                        current_interval =
+                            jittered_interval(Duration::from_secs(backoff_secs), jitter, &mut rng);
+
+                        warn!(
+                            "CouchDB health check failed {} times in a row. Next check in {:.1} seconds. Error: {}",
+                            failures, current_interval.as_secs_f64(), error_msg
+                        );
+
+                        health_state
+                            .update_couchdb_status(false, Some(error_msg))
+                            .await;
+                        health_state.record_couchdb_error().await;
                     }
-                } else {
-                    let error_msg = "No CouchDB authentication credentials available".to_string();
-                    health_state
-                        .update_couchdb_status(false, Some(error_msg))
-                        .await;
-                    health_state.record_couchdb_error().await;
                 }
             }
-        });
+        })
+    }
+
+    /// バックグラウンドのヘルスチェックを停止する
+    pub fn stop(&self) {
+        // 待機中でなくても次の待機で受け取れるよう、通知を保持するnotify_oneを使う
+        self.shutdown.notify_one();
     }
 
     /// ヘルスチェック状態を設定
@@ -235,7 +427,17 @@ pub async fn health_handler(State(state): State<Arc<HealthState>>) -> Json<Healt
 
     let couchdb_status = state.couchdb_status.read().await.clone();
 
-    let status = if couchdb_status.available {
+    // バックグラウンドのチェックが止まっている場合は最後の結果を信用しない
+    let reason = state.is_stale(&couchdb_status).then(|| {
+        warn!(
+            "Last CouchDB health check is older than {:?}",
+            state.max_status_age
+        );
+        "health check stale".to_string()
+    });
+    let reason = reason.or_else(|| state.failed_databases_reason());
+
+    let status = if couchdb_status.available && reason.is_none() {
         "healthy"
     } else {
         "degraded"
@@ -248,9 +450,60 @@ pub async fn health_handler(State(state): State<Arc<HealthState>>) -> Json<Healt
         services: ServiceStatus {
             couchdb: couchdb_status,
         },
+        reason,
     })
 }
 
+// プロセスの生存確認（依存サービスの状態に関係なく200を返す）
+pub async fn liveness_handler() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "alive" }))
+}
+
+// リクエストを受け付けられる状態かの確認
+pub async fn readiness_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.is_maintenance() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "maintenance" })),
+        );
+    }
+
+    let couchdb_status = state.health_state.couchdb_status.read().await;
+    if !couchdb_status.available {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "unavailable",
+                "reason": couchdb_status.error_message,
+            })),
+        );
+    }
+    drop(couchdb_status);
+
+    // データベースの投入が終わるまではトラフィックを受け付けない
+    if let Some(reason) = state.health_state.unmet_doc_count().await {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "not_seeded",
+                "reason": reason,
+            })),
+        );
+    }
+
+    // 一部のデータベースを作成できなくてもトラフィックは受け付ける
+    match state.health_state.failed_databases_reason() {
+        Some(reason) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "status": "ready", "reason": reason })),
+        ),
+        None => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "status": "ready" })),
+        ),
+    }
+}
+
 // ヘルスチェックのルーターを作成
 pub fn create_health_router<S>(state: Arc<HealthState>) -> Router<S> {
     Router::new()
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// 処理中のプロキシリクエスト
+#[derive(Debug, Clone)]
+pub struct InFlightRequest {
+    pub id: u64,
+    pub method: String,
+    pub path: String,
+    pub started_at: DateTime<Utc>,
+    started: Instant,
+}
+
+impl InFlightRequest {
+    /// 管理者APIで返すJSON表現
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "method": self.method,
+            "path": self.path,
+            "started_at": self.started_at.to_rfc3339(),
+            "age_ms": self.started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+/// 処理中のプロキシリクエストの一覧
+///
+/// longpollなどが滞留した際に、何が止まっているかを確認するために使う。
+#[derive(Default)]
+pub struct InFlightRegistry {
+    next_id: AtomicU64,
+    requests: Arc<Mutex<HashMap<u64, InFlightRequest>>>,
+}
+
+/// 登録したリクエスト（ドロップ時に一覧から削除される）
+pub struct InFlightGuard {
+    id: u64,
+    requests: Arc<Mutex<HashMap<u64, InFlightRequest>>>,
+}
+
+impl InFlightRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// リクエストを登録する
+    ///
+    /// 返されたガードがドロップされると、エラーで中断した場合も含めて登録が解除される。
+    pub fn track(&self, method: &str, path: &str) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let request = InFlightRequest {
+            id,
+            method: method.to_string(),
+            path: path.to_string(),
+            started_at: Utc::now(),
+            started: Instant::now(),
+        };
+        self.requests.lock().unwrap().insert(id, request);
+
+        InFlightGuard {
+            id,
+            requests: Arc::clone(&self.requests),
+        }
+    }
+
+    /// 処理中のリクエストを古い順に返す
+    pub fn snapshot(&self) -> Vec<InFlightRequest> {
+        let mut requests: Vec<InFlightRequest> =
+            self.requests.lock().unwrap().values().cloned().collect();
+        requests.sort_by_key(|request| (request.started, request.id));
+        requests
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.requests.lock().unwrap().remove(&self.id);
+    }
+}
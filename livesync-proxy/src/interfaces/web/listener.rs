@@ -0,0 +1,205 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::serve::{Listener, ListenerExt};
+use axum::Router;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Sleep};
+use tracing::{debug, warn};
+
+/// リクエストヘッダーの終端
+const HEADER_TERMINATOR: &[u8] = b"\r\n\r\n";
+
+/// HTTP/2（h2c）の接続プリフェイスの先頭
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0";
+
+/// リクエスト行とヘッダーを一定時間内に受け取れない接続を切断するリスナー
+///
+/// ヘッダーを少しずつ送り続けて接続を占有するクライアント（slowloris）への対策。
+/// ボディや上流のタイムアウトとは独立に、ヘッダーの受信だけを制限する。
+pub struct HeaderTimeoutListener {
+    inner: TcpListener,
+    timeout: Option<Duration>,
+}
+
+impl HeaderTimeoutListener {
+    /// `timeout` がNoneの場合は制限しない
+    pub fn new(inner: TcpListener, timeout: Option<Duration>) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl Listener for HeaderTimeoutListener {
+    type Io = HeaderTimeoutIo<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let (stream, addr) = Listener::accept(&mut self.inner).await;
+        (HeaderTimeoutIo::new(stream, self.timeout), addr)
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+/// ヘッダーの受信中だけ期限を設ける接続
+///
+/// 読み取ったバイト列から `\r\n\r\n` を探し、見つかるまでに期限を過ぎた場合は
+/// `TimedOut` エラーを返して接続を閉じさせる。レスポンスを書き込んだ後に次の
+/// リクエストの最初のバイトが届くと、keep-aliveの次のリクエスト用に期限を設定し直す。
+pub struct HeaderTimeoutIo<S> {
+    inner: S,
+    timeout: Option<Duration>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    /// `HEADER_TERMINATOR` のうち一致済みのバイト数
+    matched: usize,
+    /// ヘッダーを受信中か
+    reading_headers: bool,
+    /// 最初のバイト列を受け取ったか
+    started: bool,
+}
+
+impl<S> HeaderTimeoutIo<S> {
+    fn new(inner: S, timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            deadline: timeout.map(|timeout| Box::pin(sleep(timeout))),
+            timeout,
+            matched: 0,
+            reading_headers: true,
+            started: false,
+        }
+    }
+
+    /// 読み取ったバイト列でヘッダーの終端まで進んだかを更新する
+    fn observe(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == HEADER_TERMINATOR[self.matched] {
+                self.matched += 1;
+            } else {
+                self.matched = usize::from(byte == HEADER_TERMINATOR[0]);
+            }
+            if self.matched == HEADER_TERMINATOR.len() {
+                self.reading_headers = false;
+                self.deadline = None;
+                self.matched = 0;
+                return;
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HeaderTimeoutIo<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(deadline) = this.deadline.as_mut() {
+            if deadline.as_mut().poll(cx).is_ready() {
+                warn!("Closing connection that did not send its request headers in time");
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request headers were not received in time",
+                )));
+            }
+        }
+
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = &buf.filled()[filled..];
+            // HTTP/2のフレームにはヘッダーの終端がないため、期限を設けない
+            if !read.is_empty() && !this.started {
+                this.started = true;
+                if read.starts_with(HTTP2_PREFACE) {
+                    this.timeout = None;
+                    this.deadline = None;
+                    this.reading_headers = false;
+                }
+            }
+            if !read.is_empty() && this.reading_headers {
+                // keep-aliveの次のリクエストは最初のバイトが届いた時点から計る
+                if this.deadline.is_none() {
+                    if let Some(timeout) = this.timeout {
+                        this.deadline = Some(Box::pin(sleep(timeout)));
+                    }
+                }
+                this.observe(read);
+            }
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HeaderTimeoutIo<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // レスポンスを書き込んだら、次に届くのは新しいリクエストのヘッダー
+        if !this.reading_headers {
+            this.reading_headers = true;
+            this.matched = 0;
+        }
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if !this.reading_headers {
+            this.reading_headers = true;
+            this.matched = 0;
+        }
+        Pin::new(&mut this.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// ヘッダーの受信期限を設けてルーターを配信する
+///
+/// 接続元アドレスは `ConnectInfo<SocketAddr>` としてリクエストに渡される。
+/// `shutdown` が完了すると新しい接続の受け付けをやめ、処理中のリクエストを待って終了する。
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    header_read_timeout: Option<Duration>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    match header_read_timeout {
+        Some(timeout) => debug!("Request headers must arrive within {:?}", timeout),
+        None => debug!("Request header read timeout is disabled"),
+    }
+    // `tap_io` で包むと、独自のリスナーでも `ConnectInfo<SocketAddr>` を受け取れる
+    let listener = HeaderTimeoutListener::new(listener, header_read_timeout).tap_io(|_| {});
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown)
+    .await
+}
@@ -0,0 +1,184 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event as TracingEvent, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::interfaces::web::server::AppState;
+
+/// ログ配信用に保持するイベント数（これを超えて遅れたクライアントの分は破棄する）
+pub const LOG_STREAM_CAPACITY: usize = 256;
+
+/// 配信するログイベント
+#[derive(Debug, Clone)]
+pub struct LogEvent {
+    pub timestamp: DateTime<Utc>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogEvent {
+    /// SSEで送るJSON表現
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "timestamp": self.timestamp.to_rfc3339(),
+            "level": self.level.as_str(),
+            "target": self.target,
+            "message": self.message,
+        })
+    }
+}
+
+/// `tracing` のイベントを購読中のクライアントに配信する
+#[derive(Debug, Clone)]
+pub struct LogBroadcaster {
+    sender: broadcast::Sender<LogEvent>,
+}
+
+impl LogBroadcaster {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// 以降に記録されるログイベントを購読する
+    pub fn subscribe(&self) -> broadcast::Receiver<LogEvent> {
+        self.sender.subscribe()
+    }
+
+    /// サブスクライバーに追加してイベントを取り込むレイヤー
+    pub fn layer(&self) -> LogBroadcastLayer {
+        LogBroadcastLayer {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl Default for LogBroadcaster {
+    fn default() -> Self {
+        Self::new(LOG_STREAM_CAPACITY)
+    }
+}
+
+/// `tracing` のイベントをブロードキャストチャネルに書き込むレイヤー
+pub struct LogBroadcastLayer {
+    sender: broadcast::Sender<LogEvent>,
+}
+
+impl<S: Subscriber> Layer<S> for LogBroadcastLayer {
+    fn on_event(&self, event: &TracingEvent<'_>, _ctx: Context<'_, S>) {
+        // 購読者がいない場合はメッセージの整形も行わない
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let _ = self.sender.send(LogEvent {
+            timestamp: Utc::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// イベントの `message` と他のフィールドを1行にまとめる
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.message.is_empty() {
+            self.message.push(' ');
+        }
+        if field.name() == "message" {
+            self.message.push_str(&format!("{:?}", value));
+        } else {
+            self.message
+                .push_str(&format!("{}={:?}", field.name(), value));
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogStreamQuery {
+    /// 配信する最も詳細なレベル（既定は `info`）
+    pub level: Option<String>,
+}
+
+/// サーバーのログをSSEで配信するハンドラー
+///
+/// `level` より詳細なイベントは送らない。`RUST_LOG` で除外されたイベントは配信されない。
+/// 受信が遅れたクライアントは古いイベントを読み飛ばし、`lagged` イベントで件数を通知する。
+pub async fn log_stream_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LogStreamQuery>,
+) -> Response {
+    let max_level = match query.level.as_deref().map(Level::from_str) {
+        None => Level::INFO,
+        Some(Ok(level)) => level,
+        Some(Err(_)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "bad_request",
+                    "reason": "level must be one of trace, debug, info, warn, error",
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let receiver = state.log_broadcaster.subscribe();
+    Sse::new(log_events(receiver, max_level))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn log_events(
+    receiver: broadcast::Receiver<LogEvent>,
+    max_level: Level,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) if event.level <= max_level => {
+                    let sse = Event::default()
+                        .event("log")
+                        .data(event.to_json().to_string());
+                    return Some((Ok(sse), receiver));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let sse = Event::default()
+                        .event("lagged")
+                        .data(serde_json::json!({ "skipped": skipped }).to_string());
+                    return Some((Ok(sse), receiver));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
@@ -1,32 +1,208 @@
-use axum::response::IntoResponse;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use axum::{extract::State, routing::get, Router};
+use chrono::{DateTime, Utc};
 use metrics::{counter, histogram};
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::info;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::utils::percent_decode;
+
+/// プロセス全体で共有するPrometheusレコーダーのハンドル（インストールに失敗した場合はNone）
+static RECORDER_HANDLE: OnceLock<Option<PrometheusHandle>> = OnceLock::new();
+
+/// データベースごとに個別のラベルを付ける最大数（超過分は `other` にまとめる）
+pub const MAX_DATABASE_LABELS: usize = 32;
+
+/// 終了時にPushgatewayへ送るメトリクスのジョブ名
+pub const PUSHGATEWAY_JOB: &str = "livesync-proxy";
+
+/// 終了時にPushgatewayへ送る際のタイムアウト（終了を長く止めないため短くする）
+const PUSHGATEWAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// クライアントのバージョンごとに個別のラベルを付ける最大数（超過分は `other` にまとめる）
+pub const MAX_CLIENT_VERSION_LABELS: usize = 16;
+
+/// User-AgentからLiveSyncプラグインを識別するための製品名
+const LIVESYNC_USER_AGENT_PRODUCTS: &[&str] = &["obsidian-livesync", "self-hosted-livesync"];
+
+/// LiveSyncプラグインのUser-Agentからバージョンを取り出す
+///
+/// `obsidian-livesync/0.23.5` のような製品トークンを探し、ラベルとして安全な
+/// 文字だけで構成されたバージョンのみを返す。
+pub fn parse_client_version(user_agent: &str) -> Option<&str> {
+    user_agent.split_whitespace().find_map(|token| {
+        let (product, version) = token.split_once('/')?;
+        let known = LIVESYNC_USER_AGENT_PRODUCTS
+            .iter()
+            .any(|name| product.eq_ignore_ascii_case(name));
+        let valid = !version.is_empty()
+            && version.len() <= 32
+            && version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+        (known && valid).then_some(version)
+    })
+}
+
+/// `/db/{db}/...` 形式のパスから対象データベース名を取り出す
+pub fn extract_database(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("/db")?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+    rest.split('/').find(|segment| !segment.is_empty())
+}
+
+/// メトリクスとログで使うHTTPメソッド名に正規化する
+pub fn normalize_method(method: &str) -> String {
+    method.to_ascii_uppercase()
+}
+
+/// メトリクスとアクセスログで使うルートテンプレートにパスを正規化する
+///
+/// URLデコードと連続スラッシュの除去を行い、`/db` 配下のデータベース名や
+/// ドキュメントIDを `{db}` や `{doc}` に置き換える。`_` で始まるCouchDBの
+/// エンドポイント名はそのまま残す。上流へ転送するパスには使用しないこと。
+pub fn normalize_path(path: &str) -> String {
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode)
+        .collect();
+
+    if segments.first().map(String::as_str) != Some("db") {
+        return format!("/{}", segments.join("/"));
+    }
+
+    let mut template = vec!["db".to_string()];
+    let mut rest = segments[1..].iter();
+
+    // データベース名（`_all_dbs` などのサーバーレベルのエンドポイントは残す）
+    match rest.next() {
+        Some(db) if db.starts_with('_') => template.push(db.clone()),
+        Some(_) => template.push("{db}".to_string()),
+        None => return "/db".to_string(),
+    }
+
+    while let Some(segment) = rest.next() {
+        match segment.as_str() {
+            "_design" | "_local" => {
+                template.push(segment.clone());
+                if rest.next().is_some() {
+                    let placeholder = if segment == "_design" {
+                        "{ddoc}"
+                    } else {
+                        "{doc}"
+                    };
+                    template.push(placeholder.to_string());
+                }
+            }
+            "_view" | "_show" | "_list" | "_update" | "_rewrite" => {
+                template.push(segment.clone());
+                if rest.next().is_some() {
+                    template.push("{name}".to_string());
+                }
+            }
+            s if s.starts_with('_') => template.push(segment.clone()),
+            _ => {
+                // ドキュメントID以降は添付ファイル名（スラッシュを含み得る）としてまとめる
+                template.push("{doc}".to_string());
+                if rest.next().is_some() {
+                    template.push("{attachment}".to_string());
+                }
+                break;
+            }
+        }
+    }
+
+    format!("/{}", template.join("/"))
+}
 
 /// メトリクス収集状態
 pub struct MetricsState {
-    pub recorder_handle: PrometheusHandle,
+    /// メトリクスが無効な場合やレコーダーがない場合はNone
+    pub recorder_handle: Option<PrometheusHandle>,
     pub request_counts: RwLock<RequestCounts>,
+    /// ラベルとして使用済みのデータベース名（カーディナリティの上限管理用）
+    database_labels: Mutex<HashSet<String>>,
+    /// ラベルとして使用済みのクライアントバージョン
+    client_version_labels: Mutex<HashSet<String>>,
+    /// データベースのラベルごとの同期の状況
+    database_activity: Mutex<HashMap<String, DatabaseActivity>>,
+}
+
+/// データベースごとにプロキシが観測した同期の状況
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseActivity {
+    pub requests: u64,
+    pub errors: u64,
+    /// リクエストとレスポンスのボディの合計バイト数
+    pub bytes: u64,
+    pub last_activity: Option<DateTime<Utc>>,
 }
 
 /// リクエスト数の集計
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct RequestCounts {
     pub total: u64,
     pub success: u64,
     pub error: u64,
     pub longpoll_requests: u64,
     pub longpoll_errors: u64,
+    /// クライアント側で中断されたlongpoll（エラーには数えない）
+    pub longpoll_aborts: u64,
     pub bulk_docs_requests: u64,
     pub bulk_docs_errors: u64,
 }
 
+impl RequestCounts {
+    /// 定期的にログへ出力する集計の1行
+    pub fn summary_line(&self) -> String {
+        format!(
+            "Request summary: total={} success={} error={} longpoll={} (errors={}, aborts={}) bulk_docs={} (errors={})",
+            self.total,
+            self.success,
+            self.error,
+            self.longpoll_requests,
+            self.longpoll_errors,
+            self.longpoll_aborts,
+            self.bulk_docs_requests,
+            self.bulk_docs_errors
+        )
+    }
+}
+
 impl MetricsState {
     /// 新しいメトリクス状態を作成
     pub fn new() -> Self {
+        // グローバルレコーダーは一度しかインストールできないため、ハンドルを共有する
+        let recorder_handle = RECORDER_HANDLE.get_or_init(Self::install_recorder).clone();
+        Self::with_recorder_handle(recorder_handle)
+    }
+
+    /// レコーダーをインストールせず、`/metrics` を無効にしたメトリクス状態を作成
+    pub fn disabled() -> Self {
+        Self::with_recorder_handle(None)
+    }
+
+    fn with_recorder_handle(recorder_handle: Option<PrometheusHandle>) -> Self {
+        Self {
+            recorder_handle,
+            request_counts: RwLock::new(RequestCounts::default()),
+            database_labels: Mutex::new(HashSet::new()),
+            client_version_labels: Mutex::new(HashSet::new()),
+            database_activity: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Prometheusレコーダーをインストール（失敗した場合はメトリクスなしで動作を続ける）
+    fn install_recorder() -> Option<PrometheusHandle> {
         let builder = PrometheusBuilder::new();
         let builder = builder
             .set_buckets_for_metric(
@@ -37,22 +213,15 @@ impl MetricsState {
             )
             .expect("Failed to set duration buckets");
 
-        let recorder_handle = builder
+        builder
             .install_recorder()
-            .expect("Failed to install recorder");
-
-        Self {
-            recorder_handle,
-            request_counts: RwLock::new(RequestCounts {
-                total: 0,
-                success: 0,
-                error: 0,
-                longpoll_requests: 0,
-                longpoll_errors: 0,
-                bulk_docs_requests: 0,
-                bulk_docs_errors: 0,
-            }),
-        }
+            .map_err(|e| {
+                warn!(
+                    "Failed to install metrics recorder, /metrics is disabled: {}",
+                    e
+                )
+            })
+            .ok()
     }
 
     /// リクエスト処理時間を記録
@@ -64,12 +233,62 @@ impl MetricsState {
     /// リクエスト処理時間を直接値で記録
     pub fn record_request_duration_value(&self, path: &str, _method: &str, duration: Duration) {
         let seconds = duration.as_secs_f64();
-        let metric_name = format!("http_request_duration_seconds_{}", path.replace("/", "_"));
+        let metric_name = format!(
+            "http_request_duration_seconds_{}",
+            normalize_path(path).replace("/", "_")
+        );
         histogram!(metric_name).record(seconds);
     }
 
+    /// パスに対応するデータベースのラベル値を返す
+    ///
+    /// `_all_dbs` などのサーバーレベルのエンドポイントは `_server`、
+    /// 上限を超えた新しいデータベースは `other` になる。
+    pub fn database_label(&self, path: &str) -> String {
+        let database = match extract_database(path) {
+            Some(database) if !database.starts_with('_') => database,
+            _ => return "_server".to_string(),
+        };
+
+        let mut labels = self.database_labels.lock().unwrap();
+        if labels.contains(database) {
+            return database.to_string();
+        }
+        if labels.len() < MAX_DATABASE_LABELS {
+            labels.insert(database.to_string());
+            return database.to_string();
+        }
+        "other".to_string()
+    }
+
+    /// User-Agentに対応するクライアントバージョンのラベル値を返す
+    ///
+    /// LiveSyncプラグインとして識別できないUser-Agentと、上限を超えた
+    /// 新しいバージョンは `other` になる。
+    pub fn client_version_label(&self, user_agent: Option<&str>) -> String {
+        let Some(version) = user_agent.and_then(parse_client_version) else {
+            return "other".to_string();
+        };
+
+        let mut labels = self.client_version_labels.lock().unwrap();
+        if labels.contains(version) {
+            return version.to_string();
+        }
+        if labels.len() < MAX_CLIENT_VERSION_LABELS {
+            labels.insert(version.to_string());
+            return version.to_string();
+        }
+        "other".to_string()
+    }
+
     /// リクエストを記録（基本形）
-    pub async fn record_request(&self, path: &str, method: &str, status_code: u16) {
+    pub async fn record_request(
+        &self,
+        path: &str,
+        method: &str,
+        status_code: u16,
+        user_agent: Option<&str>,
+    ) {
         let is_success = status_code < 400;
         let is_longpoll = path.contains("/_changes") && path.contains("feed=longpoll");
         let is_bulk_docs = path.contains("/_bulk_docs");
@@ -83,15 +302,48 @@ impl MetricsState {
             _ => "5xx",
         };
 
+        // メトリクスとログはルートテンプレートに正規化したパスで記録する
+        let route = normalize_path(path);
+        let method = normalize_method(method);
+
         // ラベル付きのカスタムメトリクス名を作成してカウンター更新
         let metric_name = format!(
             "http_requests_path_{}_method_{}_status_{}",
-            path.replace("/", "_"),
+            route.replace("/", "_"),
             method,
             status_range
         );
         counter!(metric_name).increment(1);
 
+        // CouchDBプロキシへのリクエストはデータベース別にも集計
+        if extract_database(path).is_some() {
+            {
+                let mut activity = self.database_activity.lock().unwrap();
+                let activity = activity.entry(self.database_label(path)).or_default();
+                activity.requests += 1;
+                if !is_success {
+                    activity.errors += 1;
+                }
+                activity.last_activity = Some(Utc::now());
+            }
+
+            counter!(
+                "http_requests_by_database_total",
+                "database" => self.database_label(path),
+                "method" => method.clone(),
+                "status" => status_range
+            )
+            .increment(1);
+
+            // プラグインのバージョン別にも集計し、問題のあるリリースを見つけやすくする
+            counter!(
+                "http_requests_by_client_version_total",
+                "client_version" => self.client_version_label(user_agent),
+                "status" => status_range
+            )
+            .increment(1);
+        }
+
         // 内部カウンタを更新
         let mut counts = self.request_counts.write().await;
         counts.total += 1;
@@ -122,7 +374,7 @@ impl MetricsState {
         // リクエスト処理の詳細をログに記録
         let log_message = format!(
             "Request: {} {} -> {} (Total: {}, Success: {}, Error: {})",
-            method, path, status_code, counts.total, counts.success, counts.error
+            method, route, status_code, counts.total, counts.success, counts.error
         );
 
         if is_longpoll {
@@ -140,6 +392,85 @@ impl MetricsState {
         }
     }
 
+    /// リクエスト数の集計を一定間隔でinfoログに出力するタスクを起動する
+    ///
+    /// Prometheusで収集していない環境でも、稼働状況を確認できるようにする。
+    pub fn spawn_summary_log(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let state = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // 起動直後の集計は空のため、最初のティックは読み飛ばす
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                info!("{}", state.request_counts.read().await.summary_line());
+            }
+        })
+    }
+
+    /// 終了時に最後のメトリクスをログへ出力し、指定があればPushgatewayへ送る
+    ///
+    /// プロセスの終了後にスクレイプしても取れない最後の区間を残すためのもの。
+    /// 出力した内容を返す（レコーダーがない場合はNone）。
+    pub async fn flush_on_shutdown(&self, pushgateway_url: Option<&str>) -> Option<String> {
+        let Some(handle) = &self.recorder_handle else {
+            info!("Metrics are disabled, nothing to flush on shutdown");
+            return None;
+        };
+        let rendered = handle.render();
+        info!("Final metrics snapshot:\n{}", rendered);
+
+        if let Some(base_url) = pushgateway_url {
+            let url = format!(
+                "{}/metrics/job/{}",
+                base_url.trim_end_matches('/'),
+                PUSHGATEWAY_JOB
+            );
+            let result = reqwest::Client::new()
+                .put(&url)
+                .timeout(PUSHGATEWAY_TIMEOUT)
+                .body(rendered.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => info!("Pushed final metrics to {}", url),
+                Err(e) => warn!("Failed to push final metrics to {}: {}", url, e),
+            }
+        }
+        Some(rendered)
+    }
+
+    /// データベースへのリクエストで転送したボディのバイト数を記録
+    pub fn record_database_bytes(&self, path: &str, bytes: u64) {
+        if extract_database(path).is_none() {
+            return;
+        }
+        let label = self.database_label(path);
+        self.database_activity
+            .lock()
+            .unwrap()
+            .entry(label)
+            .or_default()
+            .bytes += bytes;
+    }
+
+    /// データベースの同期の状況（リクエストがまだない場合は空の値）
+    pub fn database_activity(&self, database: &str) -> DatabaseActivity {
+        self.database_activity
+            .lock()
+            .unwrap()
+            .get(database)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// クライアント側で中断されたlongpollを記録
+    pub async fn record_longpoll_abort(&self) {
+        counter!("longpoll_aborts_total").increment(1);
+        self.request_counts.write().await.longpoll_aborts += 1;
+    }
+
     // HTTPプロキシリクエストを記録（互換性のために残す）
     pub fn record_http_proxy_request(
         &self,
@@ -150,8 +481,8 @@ impl MetricsState {
     ) {
         let metric_name = format!(
             "http_request_duration_seconds_path_{}_method_{}",
-            path.replace("/", "_"),
-            method
+            normalize_path(&path).replace("/", "_"),
+            normalize_method(&method)
         );
         let duration = elapsed.as_secs_f64();
         histogram!(metric_name).record(duration);
@@ -182,8 +513,13 @@ impl Default for MetricsState {
 }
 
 /// メトリクスエンドポイントハンドラー
-pub async fn metrics_handler(State(state): State<Arc<MetricsState>>) -> impl IntoResponse {
-    state.recorder_handle.render()
+///
+/// レコーダーがない場合は空のボディで503を返す。
+pub async fn metrics_handler(State(state): State<Arc<MetricsState>>) -> Response {
+    match &state.recorder_handle {
+        Some(handle) => handle.render().into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
 }
 
 // メトリクスのルーターを作成
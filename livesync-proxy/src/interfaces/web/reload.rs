@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::infrastructure::config::AppConfig;
+use crate::interfaces::web::server::AppState;
+
+/// 新しい設定を反映し、変更内容を返す
+///
+/// 実行中に変更できるのは `proxy` の設定のみで、それ以外の変更は再起動が必要な旨をログに出す。
+/// 再起動が必要な項目は反映しないため、`current` の `proxy` だけを更新する。
+pub fn apply_config_reload(state: &AppState, current: &mut AppConfig, new: AppConfig) {
+    if let Err(e) = new.validate() {
+        warn!(
+            "Ignoring configuration reload, the new configuration is invalid: {}",
+            e
+        );
+        return;
+    }
+
+    let changes = current.changes(&new);
+    if changes.is_empty() {
+        info!("Configuration reloaded, nothing changed");
+        return;
+    }
+
+    if !changes.applied.is_empty() {
+        state.update_proxy_config(new.proxy.clone());
+        current.proxy = new.proxy;
+        info!(
+            "Configuration reloaded, applied: {}",
+            changes.applied.join(", ")
+        );
+    }
+    if !changes.restart_required.is_empty() {
+        warn!(
+            "Configuration changes require a restart and were not applied: {}",
+            changes.restart_required.join(", ")
+        );
+    }
+}
+
+/// SIGHUPを受け取るたびに設定を読み込み直すタスクを開始する
+///
+/// シグナルの登録はこの関数内で行うため、戻った時点でSIGHUPを送っても
+/// プロセスが終了することはない。
+#[cfg(unix)]
+pub fn spawn_config_reload<F>(
+    state: Arc<AppState>,
+    mut current: AppConfig,
+    load: F,
+) -> std::io::Result<JoinHandle<()>>
+where
+    F: Fn() -> AppConfig + Send + 'static,
+{
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+            apply_config_reload(&state, &mut current, load());
+        }
+    }))
+}
@@ -1,6 +1,7 @@
 use std::net::SocketAddr;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use anyhow::Result;
@@ -8,39 +9,289 @@ use axum::{
     body::{to_bytes, Body},
     extract::State,
     http::{header, HeaderMap, HeaderName, HeaderValue, Method, Response, StatusCode, Uri},
+    middleware,
     response::IntoResponse,
-    routing::{any, get},
+    routing::{any, delete, get, post, put},
     Router,
 };
+use http_body_util::LengthLimitError;
+use tower::Layer;
 use tower_http::{
     cors::{AllowOrigin, CorsLayer},
     services::ServeDir,
     trace::TraceLayer,
 };
-use tracing::{debug, error, info};
-
-use super::handlers::{debug_handler, http_proxy_handler, status_handler};
+use tracing::{debug, error, info, warn};
+
+use super::admin::{
+    batch_handler, captured_requests_handler, config_handler, create_user_handler,
+    database_info_handler, database_summary_handler, databases_handler, get_revs_limit_handler,
+    inflight_handler, maintenance_handler, maintenance_page, maintenance_page_response,
+    maintenance_response, remove_write_conflict_handler, replay_handler, replicate_stream_handler,
+    replication_status_handler, require_admin, set_revs_limit_handler, set_security_handler,
+    tasks_handler, warm_view_handler, write_queue_handler, DatabaseInfoCache,
+};
+use super::auth::AdminAuthenticator;
+use super::capture::RequestCapture;
+use super::chaos::inject_fault;
+use super::concurrency::{
+    client_key, is_long_lived_feed, ClientConcurrencyLimiter, LongpollLimiter,
+};
+use super::error::ProxyError;
+use super::handlers::{
+    apply_proxy_headers, changes_last_seq, debug_handler, http_proxy_handler, is_multipart_related,
+    pretty_json_for_debug, request_client_ip, sniff_content_type, status_handler,
+    streamed_request_length, EMPTY_LONGPOLL_BODY, PRETTY_DEBUG_JSON_MAX_BYTES,
+    PROXY_VERSION_HEADER,
+};
+use super::inflight::InFlightRegistry;
+use super::listener::serve;
+use super::logs::{log_stream_handler, LogBroadcaster};
+#[cfg(unix)]
+use super::reload::spawn_config_reload;
+use super::write_queue::{
+    is_queueable_write, spawn_write_queue_replay, WriteQueue, WRITE_QUEUE_REPLAY_INTERVAL,
+};
 use crate::application::services::LiveSyncService;
+use crate::infrastructure::config::{AdminConfig, AppConfig, ProxyConfig};
+use crate::infrastructure::couchdb::{ChangesFeedKind, CouchDbClient};
 use crate::interfaces::web::health::HealthState;
 use crate::interfaces::web::metrics::MetricsState;
 
+/// `/db` 配下で受け付けるHTTPメソッド（COPYはCouchDB固有のメソッド）
+pub const ALLOWED_DB_METHODS: [&str; 7] =
+    ["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS", "COPY"];
+
+/// プリフライト結果をキャッシュしてよい秒数
+const CORS_MAX_AGE_SECS: u64 = 3600;
+
+/// CORSで許可するオリジンの明示的なリスト
+pub const CORS_ALLOWED_ORIGINS: &[&str] = &[
+    "app://obsidian.md",
+    "capacitor://localhost",
+    "http://localhost",
+];
+
+/// CORSで許可するメソッドの明示的なリスト（`/db` で受け付けるメソッドと一致させる）
+fn cors_allowed_methods() -> Vec<Method> {
+    ALLOWED_DB_METHODS
+        .iter()
+        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+        .collect()
+}
+
+/// CORSで許可するヘッダーの明示的なリスト - CORSの制約に対応するため
+fn cors_allowed_headers() -> Vec<HeaderName> {
+    vec![
+        HeaderName::from_static("accept"),
+        HeaderName::from_static("authorization"),
+        HeaderName::from_static("content-type"),
+        HeaderName::from_static("origin"),
+        HeaderName::from_static("referer"),
+        HeaderName::from_static("x-csrf-token"),
+        HeaderName::from_static("if-match"),
+        HeaderName::from_static("destination"),
+        HeaderName::from_static("x-requested-with"),
+        HeaderName::from_static("x-pouchdb-read-quorum"),
+        HeaderName::from_static("x-pouchdb-write-quorum"),
+        HeaderName::from_static("content-length"),
+        HeaderName::from_static("cache-control"),
+        HeaderName::from_static("pragma"),
+    ]
+}
+
+/// パスのプレフィックスごとに振り分けるCouchDBバックエンド
+pub struct BackendRoute {
+    /// `/db` 以下のプレフィックス（前後のスラッシュを除いたもの）
+    pub prefix: String,
+    pub service: Arc<LiveSyncService>,
+}
+
+impl BackendRoute {
+    /// CouchDBへのパス（`/team-a/doc` など）がこのプレフィックス配下か
+    ///
+    /// `team-a` は `team-a` と `team-a/...` に一致し、`team-abc` には一致しない。
+    pub fn matches(&self, couchdb_path: &str) -> bool {
+        let path = couchdb_path.trim_start_matches('/');
+        path.strip_prefix(self.prefix.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
 /// アプリケーションの状態を管理する構造体
 pub struct AppState {
     pub livesync_service: Arc<LiveSyncService>,
     pub health_state: Arc<HealthState>,
     pub metrics_state: Arc<MetricsState>,
     pub static_dir: String,
+    /// `/db` プロキシの設定（SIGHUPで再読み込みされるため `proxy_config()` で取得する）
+    proxy_config: RwLock<Arc<ProxyConfig>>,
+    pub admin_config: AdminConfig,
+    /// 管理者APIの認証（Noneの場合は `admin_config` の静的トークンを使う）
+    pub admin_authenticator: Option<Arc<dyn AdminAuthenticator>>,
+    /// メンテナンスモード中は `/db` へのリクエストを503で返す
+    pub maintenance: AtomicBool,
+    /// クライアントごとの `/db` 同時リクエスト数の制限
+    pub client_limiter: ClientConcurrencyLimiter,
+    /// サーバー全体のlongpoll/continuous接続数の制限
+    pub longpoll_limiter: LongpollLimiter,
+    /// `/db` 自体へのリクエストを書き換える先のデフォルトデータベース
+    pub default_db: String,
+    /// 処理中の `/db` リクエストの一覧
+    pub inflight: InFlightRegistry,
+    /// `/api/replay` で再送するために記録した直近の `/db` リクエスト
+    pub request_capture: RequestCapture,
+    /// CouchDBの停止中に受け付け、復旧後に再送する書き込み
+    pub write_queue: WriteQueue,
+    /// `/api/databases/{db}/summary` で再利用するデータベース情報
+    pub database_info_cache: DatabaseInfoCache,
+    /// `/api/logs/stream` に配信するログ
+    pub log_broadcaster: LogBroadcaster,
+    /// 起動時に読み込んだ設定（`/api/config` で表示する）
+    pub app_config: Option<AppConfig>,
+    /// `/db` のプレフィックスごとのバックエンド（一致しない場合は `livesync_service`）
+    pub backend_routes: Vec<BackendRoute>,
 }
 
 impl AppState {
-    pub fn new(service: Arc<LiveSyncService>, health_state: Arc<HealthState>) -> Self {
+    pub fn new(
+        service: Arc<LiveSyncService>,
+        health_state: Arc<HealthState>,
+        proxy_config: ProxyConfig,
+    ) -> Self {
         Self {
             livesync_service: service,
             health_state,
             metrics_state: Arc::new(MetricsState::new()),
             static_dir: "/app/static".to_string(),
+            client_limiter: ClientConcurrencyLimiter::new(proxy_config.max_concurrent_per_client),
+            longpoll_limiter: LongpollLimiter::new(proxy_config.max_longpoll_connections),
+            request_capture: RequestCapture::new(proxy_config.capture_requests),
+            write_queue: WriteQueue::new(proxy_config.write_queue_capacity),
+            database_info_cache: DatabaseInfoCache::default(),
+            proxy_config: RwLock::new(Arc::new(proxy_config)),
+            admin_config: AdminConfig::default(),
+            admin_authenticator: None,
+            default_db: "obsidian".to_string(),
+            inflight: InFlightRegistry::new(),
+            log_broadcaster: LogBroadcaster::default(),
+            app_config: None,
+            backend_routes: Vec::new(),
+            maintenance: AtomicBool::new(false),
         }
     }
+
+    /// 管理者APIの設定を指定する
+    pub fn with_admin_config(mut self, admin_config: AdminConfig) -> Self {
+        self.admin_config = admin_config;
+        self
+    }
+
+    /// 管理者APIの認証方法を差し替える（JWTや外部サービスでの検証など）
+    pub fn with_admin_authenticator(mut self, authenticator: Arc<dyn AdminAuthenticator>) -> Self {
+        self.admin_authenticator = Some(authenticator);
+        self
+    }
+
+    /// メトリクスの収集状態を差し替える
+    pub fn with_metrics_state(mut self, metrics_state: Arc<MetricsState>) -> Self {
+        self.metrics_state = metrics_state;
+        self
+    }
+
+    /// `/api/config` で表示する設定を指定する
+    pub fn with_app_config(mut self, app_config: AppConfig) -> Self {
+        self.app_config = Some(app_config);
+        self
+    }
+
+    /// ログ配信に使うブロードキャスターを指定する
+    pub fn with_log_broadcaster(mut self, log_broadcaster: LogBroadcaster) -> Self {
+        self.log_broadcaster = log_broadcaster;
+        self
+    }
+
+    /// プレフィックス配下の `/db` リクエストを別のCouchDBに振り分ける
+    pub fn with_backend_route(mut self, prefix: &str, service: Arc<LiveSyncService>) -> Self {
+        self.backend_routes.push(BackendRoute {
+            prefix: prefix.trim_end_matches("/*").trim_matches('/').to_string(),
+            service,
+        });
+        self
+    }
+
+    /// CouchDBへのパスを処理するサービスを選ぶ（最も長く一致したプレフィックスを優先）
+    pub fn service_for_path(&self, couchdb_path: &str) -> &Arc<LiveSyncService> {
+        self.backend_routes
+            .iter()
+            .filter(|route| route.matches(couchdb_path))
+            .max_by_key(|route| route.prefix.len())
+            .map_or(&self.livesync_service, |route| &route.service)
+    }
+
+    /// デフォルトデータベース名を指定する
+    pub fn with_default_db(mut self, default_db: &str) -> Self {
+        self.default_db = default_db.to_string();
+        self
+    }
+
+    /// 現在の `/db` プロキシの設定
+    pub fn proxy_config(&self) -> Arc<ProxyConfig> {
+        Arc::clone(&self.proxy_config.read().unwrap())
+    }
+
+    /// `/db` プロキシの設定を差し替える（次のリクエストから反映される）
+    pub fn update_proxy_config(&self, proxy_config: ProxyConfig) {
+        self.client_limiter
+            .set_max_per_client(proxy_config.max_concurrent_per_client);
+        self.longpoll_limiter
+            .set_max_connections(proxy_config.max_longpoll_connections);
+        self.request_capture
+            .set_capacity(proxy_config.capture_requests);
+        self.write_queue
+            .set_capacity(proxy_config.write_queue_capacity);
+        *self.proxy_config.write().unwrap() = Arc::new(proxy_config);
+    }
+
+    /// メンテナンスモード中かどうか
+    pub fn is_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::SeqCst)
+    }
+}
+
+/// 起動時にログへ出力するエンドポイントの一覧を組み立てる
+///
+/// 解決済みの設定から、待ち受けアドレス・`/db` の転送先・主要な機能ルートと
+/// その認証要件を数行にまとめる。
+pub fn startup_banner(addr: SocketAddr, config: &AppConfig) -> Vec<String> {
+    let mut lines = vec![
+        format!("Listening on http://{}", addr),
+        format!(
+            "  /db       -> {} (default database: {})",
+            config.couchdb.url, config.couchdb.dbname
+        ),
+    ];
+    for route in &config.couchdb.routes {
+        lines.push(format!("  /db/{}/* -> {}", route.prefix, route.url));
+    }
+    lines.push(format!(
+        "  /metrics  {}",
+        if config.metrics.enabled {
+            "enabled, no auth"
+        } else {
+            "disabled (503)"
+        }
+    ));
+    lines.push("  /ws       not implemented (501)".to_string());
+    lines.push(format!(
+        "  /api/*    {}",
+        if config.admin.token.is_some() {
+            "admin endpoints enabled, bearer token required"
+        } else {
+            "admin endpoints disabled (ADMIN_TOKEN is not set)"
+        }
+    ));
+    lines
 }
 
 /// Webサーバーを起動する関数
@@ -48,50 +299,141 @@ pub async fn start_web_server(
     addr: SocketAddr,
     service: Arc<LiveSyncService>,
     health_state: Arc<HealthState>,
+    config: AppConfig,
+    log_broadcaster: LogBroadcaster,
 ) -> Result<()> {
     // アプリケーション状態の作成
-    let app_state = Arc::new(AppState::new(service, health_state.clone()));
+    let mut app_state = AppState::new(service, health_state, config.proxy.clone())
+        .with_admin_config(config.admin.clone())
+        .with_log_broadcaster(log_broadcaster)
+        .with_app_config(config.clone())
+        .with_default_db(&config.couchdb.dbname);
+    for route in &config.couchdb.routes {
+        info!("Routing /db/{}/* to CouchDB at {}", route.prefix, route.url);
+        let client = CouchDbClient::builder(&route.url)
+            .credentials(&route.username, &route.password)
+            .probe_method(config.couchdb.probe_method)
+            .connection(config.couchdb.connection.clone())
+            .build();
+        let service = LiveSyncService::new(Arc::new(client))
+            .with_document_policy(config.document_policy.clone())
+            .with_document_transform(config.document_transform.clone());
+        app_state = app_state.with_backend_route(&route.prefix, Arc::new(service));
+    }
+    if config.proxy.chaos.is_active() {
+        warn!(
+            "CHAOS MODE IS ACTIVE: /db requests fail with probability {} and are delayed {} ms with probability {}",
+            config.proxy.chaos.fault_probability,
+            config.proxy.chaos.delay_ms,
+            config.proxy.chaos.delay_probability
+        );
+    }
+    if !config.metrics.enabled {
+        info!("Metrics are disabled, /metrics will return 503");
+        app_state = app_state.with_metrics_state(Arc::new(MetricsState::disabled()));
+    }
+    let app_state = Arc::new(app_state);
+    if let Some(interval) = config.metrics.summary_log_interval() {
+        info!("Logging request summary every {:?}", interval);
+        app_state.metrics_state.spawn_summary_log(interval);
+    }
+    // 書き込みキューは再読み込みで有効になることもあるため、常に再送を監視する
+    spawn_write_queue_replay(Arc::clone(&app_state), WRITE_QUEUE_REPLAY_INTERVAL);
+
+    // SIGHUPで `.env` と環境変数から設定を読み込み直す
+    #[cfg(unix)]
+    let _reload = match spawn_config_reload(
+        Arc::clone(&app_state),
+        config.clone(),
+        AppConfig::reload_from_env,
+    ) {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            error!(
+                "Failed to listen for SIGHUP, configuration reload is disabled: {}",
+                e
+            );
+            None
+        }
+    };
+
+    // ルーターの構築
+    let metrics_state = Arc::clone(&app_state.metrics_state);
+    let app = create_router(app_state);
+
+    // サーバーの起動
+    info!("Starting server on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    for line in startup_banner(listener.local_addr()?, &config) {
+        info!("{}", line);
+    }
+    // クライアントIPの判定に接続元アドレスを利用し、ヘッダーの受信には期限を設ける
+    serve(
+        listener,
+        app,
+        config.server.header_read_timeout(),
+        shutdown_signal(),
+    )
+    .await?;
+
+    // 終了直前のメトリクスを残す
+    if config.metrics.flush_on_shutdown {
+        metrics_state
+            .flush_on_shutdown(config.metrics.pushgateway_url.as_deref())
+            .await;
+    }
+
+    info!("Server shutdown gracefully");
+    Ok(())
+}
+
+/// Ctrl+C または SIGTERM を受け取るまで待機する
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => error!("Failed to listen for SIGTERM: {}", e),
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received");
+}
+
+/// すべてのルートとミドルウェアを組み込んだルーターを構築する
+pub fn create_router(app_state: Arc<AppState>) -> Router {
+    let health_state = app_state.health_state.clone();
 
     info!("Serving static files from {}", app_state.static_dir);
 
     // 静的ファイルハンドリング
-    // ServeDir サービスを使用
-    let static_service = ServeDir::new(&app_state.static_dir);
+    // ServeDir サービスを使用（メンテナンス中はメンテナンスページを返す）
+    let static_service = middleware::from_fn_with_state(app_state.clone(), maintenance_page)
+        .layer(ServeDir::new(&app_state.static_dir));
 
     // 許可するオリジンの明示的なリスト
-    let allowed_origins = AllowOrigin::list([
-        "app://obsidian.md".parse().unwrap(),
-        "capacitor://localhost".parse().unwrap(),
-        "http://localhost".parse().unwrap(),
-    ]);
-
-    // 許可するメソッドの明示的なリスト
-    let allowed_methods = vec![
-        Method::GET,
-        Method::POST,
-        Method::PUT,
-        Method::DELETE,
-        Method::HEAD,
-        Method::OPTIONS,
-    ];
-
-    // 許可するヘッダーの明示的なリスト - CORSの制約に対応するため
-    let allowed_headers = vec![
-        HeaderName::from_static("accept"),
-        HeaderName::from_static("authorization"),
-        HeaderName::from_static("content-type"),
-        HeaderName::from_static("origin"),
-        HeaderName::from_static("referer"),
-        HeaderName::from_static("x-csrf-token"),
-        HeaderName::from_static("if-match"),
-        HeaderName::from_static("destination"),
-        HeaderName::from_static("x-requested-with"),
-        HeaderName::from_static("x-pouchdb-read-quorum"),
-        HeaderName::from_static("x-pouchdb-write-quorum"),
-        HeaderName::from_static("content-length"),
-        HeaderName::from_static("cache-control"),
-        HeaderName::from_static("pragma"),
-    ];
+    let allowed_origins = AllowOrigin::list(
+        CORS_ALLOWED_ORIGINS
+            .iter()
+            .map(|origin| origin.parse().unwrap()),
+    );
 
     // 公開するレスポンスヘッダーのリスト
     let expose_headers = vec![
@@ -103,24 +445,57 @@ pub async fn start_web_server(
         HeaderName::from_static("x-couch-request-id"),
         HeaderName::from_static("x-couch-update-newrev"),
         HeaderName::from_static("x-couch-update-newseq"),
+        HeaderName::from_static(PROXY_VERSION_HEADER),
     ];
 
     // カスタムCORS設定 - credential=trueの場合はワイルドカードを使用不可
     let cors = CorsLayer::new()
         .allow_origin(allowed_origins)
-        .allow_methods(allowed_methods)
-        .allow_headers(allowed_headers)
+        .allow_methods(cors_allowed_methods())
+        .allow_headers(cors_allowed_headers())
         .expose_headers(expose_headers)
         .allow_credentials(true)
-        .max_age(Duration::from_secs(3600));
+        .max_age(Duration::from_secs(CORS_MAX_AGE_SECS));
 
     info!(
         "Serving static files from {} and index.html from {}/index.html",
         app_state.static_dir, app_state.static_dir
     );
 
+    // 管理者トークンが必要なエンドポイント
+    let admin_routes = Router::new()
+        .route("/api/maintenance", post(maintenance_handler))
+        .route("/api/databases", get(databases_handler))
+        .route("/api/databases/{db}/info", get(database_info_handler))
+        .route("/api/databases/{db}/summary", get(database_summary_handler))
+        .route("/api/tasks", get(tasks_handler))
+        .route("/api/warm/{db}/{ddoc}/{view}", post(warm_view_handler))
+        .route("/api/config", get(config_handler))
+        .route("/api/inflight", get(inflight_handler))
+        .route("/api/write-queue", get(write_queue_handler))
+        .route(
+            "/api/write-queue/conflicts/{id}",
+            delete(remove_write_conflict_handler),
+        )
+        .route("/api/replay", get(captured_requests_handler))
+        .route("/api/replay/{id}", post(replay_handler))
+        .route("/api/batch", post(batch_handler))
+        .route("/api/replicate/stream", post(replicate_stream_handler))
+        .route("/api/replicate/status", get(replication_status_handler))
+        .route("/api/logs/stream", get(log_stream_handler))
+        .route("/api/users", post(create_user_handler))
+        .route("/api/security/{db}", put(set_security_handler))
+        .route(
+            "/api/revs-limit/{db}",
+            get(get_revs_limit_handler).put(set_revs_limit_handler),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_admin,
+        ));
+
     // すべてのルートを直接定義したルーター
-    let app = Router::new()
+    Router::new()
         // APIエンドポイント
         .route("/api/status", get(status_handler))
         .route("/debug", get(debug_handler))
@@ -129,6 +504,10 @@ pub async fn start_web_server(
             "/health",
             get(super::health::health_handler).with_state(health_state),
         )
+        .route("/health/live", get(super::health::liveness_handler))
+        .route("/health/ready", get(super::health::readiness_handler))
+        // 管理者API
+        .merge(admin_routes)
         // メトリクス
         .route(
             "/metrics",
@@ -140,24 +519,100 @@ pub async fn start_web_server(
         .route("/db", any(db_proxy_handler))
         .route("/db/", any(db_proxy_handler))
         .route("/db/{*path}", any(db_proxy_handler))
+        // WebSocket（未対応であることをクライアントに伝える）
+        .route("/ws", any(websocket_unavailable_handler))
         // ルートパス
         .route("/", get(index_handler))
         // フォールバック
         .fallback(fallback_handler)
         // ミドルウェア
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            limit_request_headers,
+        ))
         .layer(TraceLayer::new_for_http())
         .layer(cors)
-        .with_state(app_state);
+        // CORSレイヤーより前に判定し、プリフライトも含めて拒否理由を返す
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            reject_disallowed_origin,
+        ))
+        .with_state(app_state)
+}
 
-    // サーバーの起動
-    info!("Starting server on {}", addr);
+/// 許可されていないオリジンからのリクエストを403で拒否するミドルウェア（`CORS_DEBUG` 有効時のみ）
+///
+/// 通常はCORSヘッダーを付けないだけでブラウザ側の原因不明の失敗になるため、
+/// デバッグ時は拒否したオリジンをJSONで返す。同一オリジンからのリクエストは通す。
+async fn reject_disallowed_origin(
+    State(state): State<Arc<AppState>>,
+    req: axum::http::Request<Body>,
+    next: middleware::Next,
+) -> Response<Body> {
+    if !state.proxy_config().cors_debug {
+        return next.run(req).await;
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    info!("Server listening on {}", listener.local_addr()?);
-    axum::serve(listener, app).await?;
+    let Some(origin) = req.headers().get(header::ORIGIN) else {
+        return next.run(req).await;
+    };
+    let origin = String::from_utf8_lossy(origin.as_bytes()).into_owned();
+    let same_origin = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .is_some_and(|host| {
+            origin
+                .split_once("://")
+                .is_some_and(|(_, authority)| authority == host)
+        });
+    if same_origin || CORS_ALLOWED_ORIGINS.contains(&origin.as_str()) {
+        return next.run(req).await;
+    }
 
-    info!("Server shutdown gracefully");
-    Ok(())
+    warn!("Rejecting request from disallowed origin: {}", origin);
+    let body = serde_json::json!({
+        "error": "forbidden",
+        "reason": format!("Origin {} is not allowed by the CORS policy", origin),
+        "origin": origin,
+        "allowed_origins": CORS_ALLOWED_ORIGINS,
+    });
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// ヘッダー数または合計サイズが上限を超えたリクエストを431で拒否するミドルウェア
+async fn limit_request_headers(
+    State(state): State<Arc<AppState>>,
+    req: axum::http::Request<Body>,
+    next: middleware::Next,
+) -> Response<Body> {
+    let config = state.proxy_config();
+    let headers = req.headers();
+    let count = headers.len();
+    let bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+
+    let too_many = config.max_header_count > 0 && count > config.max_header_count;
+    let too_large = config.max_header_bytes > 0 && bytes > config.max_header_bytes;
+    if too_many || too_large {
+        warn!(
+            "Rejecting request with oversized headers: {} {} ({} headers, {} bytes)",
+            req.method(),
+            req.uri().path(),
+            count,
+            bytes
+        );
+        return ProxyError::HeaderFieldsTooLarge
+            .into_response_with_format(config.error_response_format);
+    }
+
+    next.run(req).await
 }
 
 /// DBプロキシハンドラーラッパー - パスの確実なマッピングを行う
@@ -170,12 +625,106 @@ async fn db_proxy_handler(
     let method = req.method().to_string();
     let path = req.uri().path().to_string();
     let query = req.uri().query();
+    let proxy_config = state.proxy_config();
 
     info!("DB Proxy handling: {} {}", method, path);
 
-    // _changesエンドポイントのlongpoll検出
-    let is_longpoll =
-        path.contains("/_changes") && query.is_some_and(|q| q.contains("feed=longpoll"));
+    // 未対応のメソッドはCouchDBに転送せずに拒否する
+    if !ALLOWED_DB_METHODS.contains(&method.as_str()) {
+        warn!("Rejecting unsupported method on /db: {} {}", method, path);
+        return ProxyError::MethodNotAllowed(method)
+            .into_response_with_format(proxy_config.error_response_format);
+    }
+
+    // OPTIONSはCouchDBに転送せずにその場で応答する
+    // （通常はCORSレイヤーがルーティング前にすべてのOPTIONSへ応答するため、
+    // ここはレイヤーを経由しない場合の保険となる）
+    if method == "OPTIONS" {
+        debug!("Answering OPTIONS locally: {}", path);
+        let mut response = options_response();
+        apply_proxy_headers(response.headers_mut(), &proxy_config);
+        return response;
+    }
+
+    // メンテナンス中はCouchDBに転送しない
+    if state.is_maintenance() {
+        info!("Maintenance mode active, rejecting {} {}", method, path);
+        let mut response = maintenance_response();
+        apply_proxy_headers(response.headers_mut(), &proxy_config);
+        return response;
+    }
+
+    // 設定により、ヘルスチェックでCouchDBが停止中の間は転送を試みずに503を返す
+    // （書き込みキューが有効な場合、キューに入れられる書き込みは後段で受け付ける）
+    let queueable = state.write_queue.is_enabled()
+        && is_queueable_write(&method, path.trim_start_matches("/db"));
+    if proxy_config.fail_fast_when_unavailable
+        && !queueable
+        && !state.health_state.couchdb_status.read().await.available
+    {
+        info!(
+            "CouchDB is unavailable, failing fast for {} {}",
+            method, path
+        );
+        let mut response = ProxyError::CouchDbUnavailable
+            .into_response_with_format(proxy_config.error_response_format);
+        apply_proxy_headers(response.headers_mut(), &proxy_config);
+        return response;
+    }
+
+    // 同一クライアントの同時リクエスト数を制限する
+    // スロットはレスポンスの構築が終わるまで保持されるため、longpollの待機時間も含まれる
+    let _client_slot = if state.client_limiter.is_enabled() {
+        let key = client_key(req.headers(), request_client_ip(&req, &proxy_config));
+        match state.client_limiter.try_acquire(&key) {
+            Some(slot) => Some(slot),
+            None => {
+                warn!("Too many concurrent requests from client {}", key);
+                let mut response = ProxyError::TooManyRequests
+                    .into_response_with_format(proxy_config.error_response_format);
+                apply_proxy_headers(response.headers_mut(), &proxy_config);
+                return response;
+            }
+        }
+    } else {
+        None
+    };
+
+    // サーバー全体のlongpoll数を制限する（通常のリクエストは対象外）
+    // 枠はレスポンスの構築が終わるまで、つまりlongpollの待機中ずっと保持される
+    let _longpoll_slot = if is_long_lived_feed(&path, query) {
+        match state.longpoll_limiter.try_acquire() {
+            Some(slot) => Some(slot),
+            None => {
+                warn!(
+                    "Too many open longpoll connections ({}), rejecting {} {}",
+                    state.longpoll_limiter.active(),
+                    method,
+                    path
+                );
+                let mut response = ProxyError::TooManyLongpolls
+                    .into_response_with_format(proxy_config.error_response_format);
+                apply_proxy_headers(response.headers_mut(), &proxy_config);
+                return response;
+            }
+        }
+    } else {
+        None
+    };
+
+    // レスポンスの構築が終わるまで処理中のリクエストとして登録する
+    let _inflight = state.inflight.track(&method, &path);
+
+    // カオスモードでは転送前に遅延や障害を注入する
+    if let Some(fault) = inject_fault(&proxy_config.chaos, &method, &path).await {
+        let mut response = fault.into_response_with_format(proxy_config.error_response_format);
+        apply_proxy_headers(response.headers_mut(), &proxy_config);
+        return response;
+    }
+
+    // _changesエンドポイントのフィードの種類を検出
+    let feed = ChangesFeedKind::detect(&path, query);
+    let is_longpoll = feed == Some(ChangesFeedKind::Longpoll);
 
     // bulk_docsリクエストの検出（大きなデータ転送が予想される）
     let is_bulk_docs = path.contains("/_bulk_docs");
@@ -206,9 +755,29 @@ async fn db_proxy_handler(
         buffer_size, method, path
     );
 
+    // 大きなボディを流すリクエストは、レスポンスも組み立て直さずにそのまま返す
+    let streams_body =
+        streamed_request_length(req.headers(), proxy_config.stream_request_body_threshold)
+            .is_some();
+
     // リクエストをハンドラに渡す
+    let metrics_state = Arc::clone(&state.metrics_state);
     let orig_response = http_proxy_handler(state, req).await;
 
+    if streams_body {
+        info!(
+            "Returning response of streamed request as is: {} {}",
+            method, path
+        );
+        return orig_response.into_response();
+    }
+
+    // continuous・eventsourceは終わりがないため、バッファせずにそのまま流す
+    if feed.is_some_and(ChangesFeedKind::is_streaming) {
+        info!("Streaming {:?} _changes feed for {} {}", feed, method, path);
+        return orig_response.into_response();
+    }
+
     // 詳細なロギングのためにレスポンスを展開
     let (parts, body) = orig_response.into_response().into_parts();
     let status = parts.status;
@@ -217,24 +786,46 @@ async fn db_proxy_handler(
     info!("DB Proxy got initial response with status: {}", status);
     debug!("Response headers before processing: {:?}", headers);
 
-    // longpollリクエストの場合は特別な処理（AbortErrorが発生しやすい）
-    if is_longpoll && status == StatusCode::NO_CONTENT {
-        info!("Returning early for longpoll request with 204 status");
-        return Response::builder()
-            .status(status)
-            .header(header::CONTENT_TYPE, "application/json")
-            .body(Body::from(r#"{"results":[],"last_seq":"0"}"#))
-            .unwrap()
-            .into_response();
-    }
-
     match to_bytes(body, buffer_size).await {
         // 10MB制限
         Ok(bytes) => {
             info!("Successfully buffered response body: {} bytes", bytes.len());
-            if bytes.len() < 1000 {
-                // 小さいレスポンスはデバッグのために表示
-                debug!("Response body content: {}", String::from_utf8_lossy(&bytes));
+            metrics_state.record_database_bytes(&path, bytes.len() as u64);
+
+            // longpollの204は本当にボディが空の場合だけ空の結果を合成する（AbortErrorが発生しやすい）
+            if is_longpoll && status == StatusCode::NO_CONTENT && bytes.is_empty() {
+                info!("Synthesizing empty result for longpoll request with 204 status");
+                let mut response = Response::builder()
+                    .status(status)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(EMPTY_LONGPOLL_BODY))
+                    .unwrap()
+                    .into_response();
+                apply_proxy_headers(response.headers_mut(), &proxy_config);
+                return response;
+            }
+
+            // 上流の `last_seq` はボディごとそのまま返し、書き換えや既定値での補完はしない
+            if path.contains("/_changes") && status.is_success() {
+                match changes_last_seq(&bytes) {
+                    Some(last_seq) => debug!("Forwarding _changes last_seq {}", last_seq),
+                    None => debug!("_changes response for {} has no last_seq", path),
+                }
+            }
+            // multipartのボディは添付ファイルのバイナリを含むためログに出力しない
+            let is_multipart = is_multipart_related(&headers);
+            if is_multipart {
+                debug!("Response body is multipart/related, not logging its content");
+            } else if bytes.len() < PRETTY_DEBUG_JSON_MAX_BYTES {
+                // 小さいレスポンスはデバッグのために表示（設定によりJSONは整形する）
+                let pretty = proxy_config
+                    .pretty_debug_json
+                    .then(|| pretty_json_for_debug(&bytes))
+                    .flatten();
+                match pretty {
+                    Some(pretty) => debug!("Response body content:\n{}", pretty),
+                    None => debug!("Response body content: {}", String::from_utf8_lossy(&bytes)),
+                }
             }
 
             // マニュアルでレスポンスを構築 - HTTP/1.1互換の方法で
@@ -248,19 +839,25 @@ async fn db_proxy_handler(
             }
 
             // content-lengthを設定して、chunkedエンコーディングを確実に防ぐ
+            // 206 Partial Contentの場合も実際に受け取った部分ボディの長さとなり、
+            // Content-Rangeヘッダーはそのまま維持される
             response_headers.insert(
                 header::CONTENT_LENGTH,
                 HeaderValue::from_str(&bytes.len().to_string()).unwrap(),
             );
 
-            // content-typeヘッダーが確実に設定されるようにする
-            if !response_headers.contains_key(header::CONTENT_TYPE) {
-                response_headers.insert(
-                    header::CONTENT_TYPE,
-                    HeaderValue::from_static("application/json"),
-                );
+            // 上流がcontent-typeを返さなかった場合のみ、ボディの先頭から推定して補う
+            // （multipartのContent-Typeは境界文字列を含むため、上流の値をそのまま使う）
+            if !is_multipart && !response_headers.contains_key(header::CONTENT_TYPE) {
+                if let Some(content_type) = sniff_content_type(&bytes) {
+                    response_headers
+                        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+                }
             }
 
+            // プロキシ経由であることを示すヘッダーを付与
+            apply_proxy_headers(&mut response_headers, &proxy_config);
+
             info!("Built final response headers: {:?}", response_headers);
 
             // 新しいレスポンスを構築
@@ -278,27 +875,59 @@ async fn db_proxy_handler(
                 }
                 Err(e) => {
                     error!("Failed to build response: {}", e);
-                    Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(Body::from("Failed to build response"))
-                        .unwrap()
+                    ProxyError::ResponseBody(e.to_string())
+                        .into_response_with_format(proxy_config.error_response_format)
                 }
             }
         }
+        // バッファの上限を超えた場合は500ではなく413で上限を伝える
+        Err(e)
+            if std::error::Error::source(&e)
+                .is_some_and(|source| source.is::<LengthLimitError>()) =>
+        {
+            warn!(
+                "Response body for {} {} exceeds buffer size of {} bytes",
+                method, path, buffer_size
+            );
+            ProxyError::ResponseTooLarge { limit: buffer_size }
+                .into_response_with_format(proxy_config.error_response_format)
+        }
         Err(e) => {
             error!("Failed to buffer response body: {}", e);
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::from(format!("Failed to process response: {}", e)))
-                .unwrap()
+            ProxyError::ResponseBody(e.to_string())
+                .into_response_with_format(proxy_config.error_response_format)
         }
     }
 }
 
+/// `/db` へのOPTIONSに返す204レスポンスを構築する
+fn options_response() -> Response<Body> {
+    let methods: Vec<String> = cors_allowed_methods()
+        .iter()
+        .map(Method::to_string)
+        .collect();
+    let headers: Vec<String> = cors_allowed_headers()
+        .iter()
+        .map(HeaderName::to_string)
+        .collect();
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(header::ALLOW, ALLOWED_DB_METHODS.join(", "))
+        .header(header::ACCESS_CONTROL_ALLOW_METHODS, methods.join(", "))
+        .header(header::ACCESS_CONTROL_ALLOW_HEADERS, headers.join(", "))
+        .header(header::ACCESS_CONTROL_MAX_AGE, CORS_MAX_AGE_SECS)
+        .body(Body::empty())
+        .unwrap()
+}
+
 /// インデックスページを提供するハンドラー
-async fn index_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn index_handler(State(state): State<Arc<AppState>>) -> Response<Body> {
+    if state.is_maintenance() {
+        let page_path = state.proxy_config().maintenance_page_path.clone();
+        return maintenance_page_response(page_path.as_deref()).await;
+    }
     let index_path = format!("{}/index.html", state.static_dir);
-    serve_file(index_path).await
+    serve_file(index_path).await.into_response()
 }
 
 /// ファイルを提供する共通関数
@@ -330,11 +959,35 @@ async fn serve_file(path: String) -> impl IntoResponse {
     }
 }
 
+/// WebSocketのアップグレード要求に501を返すハンドラー
+///
+/// WebSocketでの同期はまだ有効にできないため、汎用の404ではなく
+/// `/db` のHTTPプロキシを使うよう案内するエラーを返す。
+async fn websocket_unavailable_handler() -> impl IntoResponse {
+    info!("Rejecting WebSocket request: WebSocket support is not enabled");
+    let body = serde_json::json!({
+        "error": "not_implemented",
+        "reason": "WebSocket sync is not enabled on this proxy; configure LiveSync to use the CouchDB endpoint at /db instead",
+    });
+    Response::builder()
+        .status(StatusCode::NOT_IMPLEMENTED)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
 /// フォールバックハンドラー
 async fn fallback_handler(uri: Uri) -> impl IntoResponse {
     info!("404 Not Found: {}", uri);
+    // CouchDBと同じ形式のエラーボディを返す
+    let body = serde_json::json!({
+        "error": "not_found",
+        "reason": "No route matches the requested path",
+        "path": uri.path(),
+    });
     Response::builder()
         .status(StatusCode::NOT_FOUND)
-        .body(Body::from(format!("404 - Not Found: {}", uri)))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
         .unwrap()
 }
@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::interfaces::web::server::AppState;
+
+/// キューに入れた書き込みを再送するか確認する間隔
+pub const WRITE_QUEUE_REPLAY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// CouchDBの停止中に受け付け、復旧後に再送する書き込み
+#[derive(Debug, Clone)]
+pub struct QueuedWrite {
+    pub id: u64,
+    pub method: String,
+    /// CouchDBへのパス（`/db` を除いたもの）
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub queued_at: DateTime<Utc>,
+}
+
+impl QueuedWrite {
+    /// 管理APIで返すJSON（認証情報を含むヘッダーは返さない）
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "method": self.method,
+            "path": self.path,
+            "query": self.query,
+            "body": String::from_utf8_lossy(&self.body),
+            "queued_at": self.queued_at.to_rfc3339(),
+        })
+    }
+}
+
+/// キューに入れられる書き込みか
+///
+/// ドキュメントへの `PUT`（設計ドキュメントや `_local` を含む）と `_bulk_docs` への `POST` が対象。
+/// データベース自体の作成やサーバーレベルのエンドポイントは対象外。
+pub fn is_queueable_write(method: &str, couchdb_path: &str) -> bool {
+    let segments: Vec<&str> = couchdb_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    match (method, segments.as_slice()) {
+        ("POST", [db, "_bulk_docs"]) => !db.starts_with('_'),
+        ("PUT", [db, rest @ ..]) if !rest.is_empty() => !db.starts_with('_'),
+        _ => false,
+    }
+}
+
+/// 再送の結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    /// CouchDBが受け付けた書き込み
+    pub replayed: usize,
+    /// 競合（409）のため反映できず、競合リストに移した書き込み
+    pub conflicts: usize,
+    /// その他の4xxで拒否されたため破棄した書き込み
+    pub rejected: usize,
+}
+
+/// CouchDBの停止中に受け付けた書き込みを順番に保持するキュー
+///
+/// 上限を超えた書き込みは受け付けない（上限が0の場合は無効）。
+/// 再送は受け付けた順に行い、CouchDBにまだ届かない場合は残りを次の機会に回す。
+/// 再送時に競合した書き込みは、管理APIで確認して解消できるよう競合リストに残す。
+#[derive(Default)]
+pub struct WriteQueue {
+    capacity: AtomicUsize,
+    next_id: AtomicU64,
+    writes: Mutex<VecDeque<QueuedWrite>>,
+    /// 再送時に409になった書き込み（管理者が取り除くまで保持する）
+    conflicts: Mutex<Vec<QueuedWrite>>,
+    /// 再送中は新たな再送を始めない（順序を保つため）
+    replaying: tokio::sync::Mutex<()>,
+}
+
+impl WriteQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: AtomicUsize::new(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// 保持する件数の上限を変更する（既にキューにある書き込みは捨てない）
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::SeqCst);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity.load(Ordering::SeqCst) > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.writes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 書き込みをキューに入れ、採番したIDを返す（無効または満杯の場合はNone）
+    pub fn push(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        headers: &HeaderMap,
+        body: &Bytes,
+    ) -> Option<u64> {
+        let capacity = self.capacity.load(Ordering::SeqCst);
+        let mut writes = self.writes.lock().unwrap();
+        if writes.len() >= capacity {
+            return None;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        writes.push_back(QueuedWrite {
+            id,
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.map(str::to_string),
+            headers: headers.clone(),
+            body: body.clone(),
+            queued_at: Utc::now(),
+        });
+        Some(id)
+    }
+
+    /// キューにある書き込みを古い順に返す
+    pub fn snapshot(&self) -> Vec<QueuedWrite> {
+        self.writes.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// 再送時に競合した書き込みを古い順に返す
+    pub fn conflicts(&self) -> Vec<QueuedWrite> {
+        self.conflicts.lock().unwrap().clone()
+    }
+
+    /// 解消済みの競合を競合リストから取り除く
+    pub fn remove_conflict(&self, id: u64) -> Option<QueuedWrite> {
+        let mut conflicts = self.conflicts.lock().unwrap();
+        let index = conflicts.iter().position(|write| write.id == id)?;
+        Some(conflicts.remove(index))
+    }
+
+    fn front(&self) -> Option<QueuedWrite> {
+        self.writes.lock().unwrap().front().cloned()
+    }
+
+    fn pop_front(&self) {
+        self.writes.lock().unwrap().pop_front();
+    }
+}
+
+/// キューに入れた書き込みを受け付けた順にCouchDBへ再送する
+///
+/// - 成功した書き込みはキューから取り除く
+/// - 409（競合）はCouchDB側に新しいリビジョンがあるため上書きせず、競合リストに移す
+///   （受け付け済みの書き込みを失わないよう、`/api/write-queue` で確認して解消する）
+/// - その他の4xxは再送しても成功しないため破棄する
+/// - 5xxや接続できない場合はそこで止め、残りは次の機会に同じ順序で再送する
+pub async fn replay_queued_writes(state: &AppState) -> ReplaySummary {
+    let queue = &state.write_queue;
+    let _replaying = queue.replaying.lock().await;
+    let mut summary = ReplaySummary::default();
+
+    while let Some(write) = queue.front() {
+        let service = state.service_for_path(&write.path);
+        let result = service
+            .forward_request(
+                &write.method,
+                &write.path,
+                write.query.clone(),
+                write.headers.clone(),
+                write.body.clone(),
+            )
+            .await;
+        let status = match result {
+            Ok(response) => response.status(),
+            Err(e) => {
+                debug!("Queued write {} could not be replayed yet: {}", write.id, e);
+                break;
+            }
+        };
+
+        if status.is_success() {
+            info!(
+                "Replayed queued write {}: {} {}",
+                write.id, write.method, write.path
+            );
+            summary.replayed += 1;
+        } else if status == StatusCode::CONFLICT {
+            warn!(
+                "Queued write {} conflicted, keeping it for inspection: {} {}",
+                write.id, write.method, write.path
+            );
+            queue.conflicts.lock().unwrap().push(write.clone());
+            summary.conflicts += 1;
+        } else if status.is_client_error() {
+            warn!(
+                "Dropping queued write {} rejected with {}: {} {}",
+                write.id, status, write.method, write.path
+            );
+            summary.rejected += 1;
+        } else {
+            debug!("Queued write {} got {}, retrying later", write.id, status);
+            break;
+        }
+        queue.pop_front();
+    }
+    summary
+}
+
+/// CouchDBが利用可能になったら、キューに入れた書き込みを定期的に再送する
+pub fn spawn_write_queue_replay(state: Arc<AppState>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if state.write_queue.is_empty()
+                || !state.health_state.couchdb_status.read().await.available
+            {
+                continue;
+            }
+            let summary = replay_queued_writes(&state).await;
+            info!(
+                "Write queue replay: {} replayed, {} conflicts, {} rejected, {} remaining",
+                summary.replayed,
+                summary.conflicts,
+                summary.rejected,
+                state.write_queue.len()
+            );
+        }
+    })
+}
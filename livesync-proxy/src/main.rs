@@ -3,45 +3,70 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use livesync_proxy::application::check::run_connection_check;
 use livesync_proxy::application::services::LiveSyncService;
-use livesync_proxy::domain::services::CouchDbRepository;
 use livesync_proxy::infrastructure::config::AppConfig;
 use livesync_proxy::infrastructure::couchdb::CouchDbClient;
+#[cfg(unix)]
+use livesync_proxy::infrastructure::transport::UpstreamTransport;
 use livesync_proxy::interfaces::web::health::HealthState;
+use livesync_proxy::interfaces::web::logs::LogBroadcaster;
 use livesync_proxy::interfaces::web::server::start_web_server;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
+    // Initialize logging (events are also teed to /api/logs/stream)
+    let log_broadcaster = LogBroadcaster::default();
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
         ))
         .with(tracing_subscriber::fmt::layer())
+        .with(log_broadcaster.layer())
         .init();
 
     info!("Starting LiveSync proxy server");
 
     // Load configuration
     let config = AppConfig::from_env();
-    info!("Loaded configuration: {:#?}", config);
+    info!("Loaded configuration: {:#}", config.redacted());
 
-    // CouchDBクライアントの作成
-    let couchdb_client = CouchDbClient::new(
-        &config.couchdb.url,
-        &config.couchdb.username,
-        &config.couchdb.password,
-    );
+    // 設定の妥当性を確認（不正な場合は起動しない）
+    if let Err(e) = config.validate() {
+        error!("Invalid configuration: {}", e);
+        return Err(anyhow::anyhow!("Invalid configuration: {}", e));
+    }
 
-    // データベース名を取得
-    let dbname = &config.couchdb.dbname;
-    info!("Ensuring CouchDB database exists: {}", dbname);
-    match couchdb_client.ensure_database(dbname).await {
-        Ok(_) => info!("Database '{}' is ready.", dbname),
-        Err(e) => info!("Failed to ensure database '{}': {}", dbname, e),
+    // CouchDBクライアントの作成
+    let couchdb_client_builder = CouchDbClient::builder(&config.couchdb.url)
+        .credentials(&config.couchdb.username, &config.couchdb.password)
+        .failover_urls(&config.couchdb.failover_urls)
+        .nodes(&config.couchdb.nodes)
+        .default_db(&config.couchdb.dbname)
+        .probe_method(config.couchdb.probe_method)
+        .connection(config.couchdb.connection.clone());
+    // unix:// の場合はUnixドメインソケットに直接接続する
+    #[cfg(unix)]
+    let couchdb_client_builder = match UpstreamTransport::parse(&config.couchdb.url) {
+        UpstreamTransport::Unix { socket_path } => couchdb_client_builder.unix_socket(socket_path),
+        UpstreamTransport::Tcp { .. } => couchdb_client_builder,
+    };
+    let couchdb_client = couchdb_client_builder.build();
+
+    // --check: サーバーを起動せずに接続を確認して終了する
+    if std::env::args()
+        .skip(1)
+        .any(|arg| arg == "--check" || arg == "check")
+    {
+        let report = run_connection_check(&couchdb_client, &config.couchdb.dbname).await;
+        println!("{}", report);
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        return Ok(());
     }
 
     // Test connection but continue even if it fails
@@ -61,9 +86,35 @@ async fn main() -> Result<()> {
     };
 
     // Create application service
-    let livesync_service = Arc::new(LiveSyncService::new(Arc::new(couchdb_client)));
+    let livesync_service = Arc::new(
+        LiveSyncService::new(Arc::new(couchdb_client))
+            .with_document_policy(config.document_policy.clone())
+            .with_document_transform(config.document_transform.clone())
+            .with_max_concurrent_replications(config.couchdb.max_concurrent_replications),
+    );
     debug!("Created LiveSync service");
 
+    // デフォルトと ENSURE_DBS のデータベースを作成（失敗しても起動は続ける）
+    let databases = config.couchdb.databases_to_ensure();
+    info!("Ensuring CouchDB databases exist: {}", databases.join(", "));
+    let failures = livesync_service.ensure_databases(&databases).await;
+    for (db_name, e) in &failures {
+        warn!("Failed to ensure database '{}': {}", db_name, e);
+    }
+    info!(
+        "{} of {} databases are ready",
+        databases.len() - failures.len(),
+        databases.len()
+    );
+    // クラスター構成かどうかを起動時に判定して保持する
+    match livesync_service.detect_cluster().await {
+        Ok(true) => info!("CouchDB is running as a cluster"),
+        Ok(false) => info!("CouchDB is running as a single node"),
+        Err(e) => warn!("Failed to detect whether CouchDB is clustered: {}", e),
+    }
+
+    let failed_databases: Vec<String> = failures.into_iter().map(|(db_name, _)| db_name).collect();
+
     // Get and log CouchDB URL and auth for verification
     let service_couchdb_url = livesync_service.get_couchdb_url();
     let service_couchdb_auth = livesync_service.get_couchdb_auth();
@@ -74,14 +125,21 @@ async fn main() -> Result<()> {
     );
 
     // Create health check state
-    let health_state = Arc::new(HealthState::new(
-        Arc::clone(&livesync_service),
-        Duration::from_secs(30), // 30秒間隔でヘルスチェック
-    ));
+    let health_state = Arc::new(
+        HealthState::new(
+            Arc::clone(&livesync_service),
+            Duration::from_secs(30), // 30秒間隔でヘルスチェック
+        )
+        .with_max_status_age(Duration::from_secs(config.health.max_status_age_secs))
+        .with_jitter_fraction(config.health.jitter_fraction)
+        .with_min_doc_count(&config.couchdb.dbname, config.health.min_doc_count)
+        .with_failed_databases(failed_databases),
+    );
 
     // 初期状態を設定（開発モードでは接続が失敗してもサーバーが起動するように）
     if couchdb_available {
         health_state.update_couchdb_status(true, None).await;
+        health_state.refresh_server_info().await;
     } else {
         health_state
             .update_couchdb_status(false, Some("Initial connection failed".to_string()))
@@ -91,7 +149,7 @@ async fn main() -> Result<()> {
     debug!("Created health check state");
 
     // Start health check background task
-    health_state.start_background_health_check();
+    let health_check = health_state.start_background_health_check();
     debug!("Started background health check");
 
     // サーバーアドレスの設定
@@ -99,7 +157,21 @@ async fn main() -> Result<()> {
     info!("Starting server on {}", addr);
 
     // 実際のサーバーを起動
-    start_web_server(addr, livesync_service, health_state).await?;
+    let result = start_web_server(
+        addr,
+        livesync_service,
+        Arc::clone(&health_state),
+        config,
+        log_broadcaster,
+    )
+    .await;
+
+    // サーバー停止後にヘルスチェックを終了させる
+    health_state.stop();
+    if let Err(e) = health_check.await {
+        error!("Health check task failed: {}", e);
+    }
+    result?;
 
     info!("Server shutdown gracefully");
     Ok(())
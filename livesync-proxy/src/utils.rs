@@ -1,4 +1,8 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
 use base64::{engine::general_purpose, Engine as _};
+use ipnet::IpNet;
 use tracing::debug;
 
 /// Base64エンコードを行う関数
@@ -22,6 +26,26 @@ pub fn base64_decode(input: &str) -> Result<String, base64::DecodeError> {
     Ok(String::from_utf8_lossy(&bytes).to_string())
 }
 
+/// パーセントエンコーディングをデコードする（不正なシーケンスはそのまま残す）
+pub fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
 /// CouchDB URLから認証情報を抽出する関数
 pub fn extract_auth_from_url(url: &str) -> Option<(String, String)> {
     if let Ok(parsed_url) = url::Url::parse(url) {
@@ -43,3 +67,32 @@ pub fn truncate_string(s: &str, max_length: usize) -> String {
         format!("{}...", &s[..max_length])
     }
 }
+
+/// 接続元のクライアントIPを決定する関数
+///
+/// 直接の接続元が信頼済みプロキシに含まれる場合のみ `X-Forwarded-For` を参照し、
+/// 右端から順に信頼済みでない最初のアドレスをクライアントとみなす。
+pub fn extract_client_ip(peer: SocketAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    let peer_ip = peer.ip();
+
+    if !is_trusted(&peer_ip) {
+        return peer_ip;
+    }
+
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|entry| entry.trim().parse().ok())
+        .collect();
+
+    forwarded
+        .iter()
+        .rev()
+        .find(|ip| !is_trusted(ip))
+        .or_else(|| forwarded.first())
+        .copied()
+        .unwrap_or(peer_ip)
+}
@@ -0,0 +1,735 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Request, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use livesync_proxy::infrastructure::config::ProxyConfig;
+use livesync_proxy::interfaces::web::admin::{DEFAULT_MAINTENANCE_PAGE, REPLICATION_POLL_INTERVAL};
+use livesync_proxy::interfaces::web::auth::{AdminAuthenticator, AuthError, Principal};
+use livesync_proxy::interfaces::web::server::create_router;
+use tower::ServiceExt;
+
+fn vault_upstream() -> Router {
+    Router::new().route(
+        "/vault",
+        get(|| async { Json(serde_json::json!({"db_name": "vault"})) }),
+    )
+}
+
+fn set_maintenance(enabled: bool) -> Request<Body> {
+    Request::post("/api/maintenance")
+        .header(header::AUTHORIZATION, common::admin_bearer())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({ "enabled": enabled }).to_string(),
+        ))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_maintenance_mode_toggles_db_proxying() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    // メンテナンスモードを有効化
+    let response = app.clone().oneshot(set_maintenance(true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "maintenance");
+
+    // 生存確認は成功し、レディネスはメンテナンス中を報告する
+    let response = app
+        .clone()
+        .oneshot(Request::get("/health/live").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["status"], "maintenance");
+
+    // メンテナンスモードを解除するとプロキシが再開する
+    let response = app.clone().oneshot(set_maintenance(false)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_maintenance_page_served_for_index() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+    app.clone().oneshot(set_maintenance(true)).await.unwrap();
+
+    // ページのパスが未設定の場合は組み込みのページを返す
+    let response = app
+        .clone()
+        .oneshot(Request::get("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, DEFAULT_MAINTENANCE_PAGE.as_bytes());
+
+    // 静的ファイルも同じページになる
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/static/js/app.js")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // 設定したファイルがあればそちらを返す
+    let page = std::env::temp_dir().join(format!("maintenance-{}.html", std::process::id()));
+    std::fs::write(&page, "<h1>Back soon</h1>").unwrap();
+    let config = ProxyConfig {
+        maintenance_page_path: Some(page.to_string_lossy().into_owned()),
+        ..ProxyConfig::default()
+    };
+    let app = common::app(&upstream, config);
+    app.clone().oneshot(set_maintenance(true)).await.unwrap();
+    let response = app
+        .oneshot(Request::get("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, "<h1>Back soon</h1>");
+    std::fs::remove_file(page).unwrap();
+}
+
+#[tokio::test]
+async fn test_admin_endpoints_require_token() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/api/maintenance")
+                .header(header::AUTHORIZATION, "Bearer wrong-token")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"enabled":true}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // トークン未設定の場合は管理者APIを無効化する
+    let state =
+        common::app_state(&upstream, ProxyConfig::default()).with_admin_config(Default::default());
+    let app = create_router(Arc::new(state));
+    let response = app.oneshot(set_maintenance(true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_batch_runs_status_and_databases() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/_all_dbs",
+        get(|| async { Json(serde_json::json!(["_users", "vault"])) }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::post("/api/batch")
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    serde_json::json!([
+                        {"op": "status"},
+                        {"op": "databases"},
+                        {"op": "unknown"}
+                    ])
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let results: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    // 結果はリクエストと同じ順序で返る
+    assert_eq!(results[0]["op"], "status");
+    assert_eq!(results[0]["ok"], true);
+    assert_eq!(results[0]["result"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(results[1]["op"], "databases");
+    assert_eq!(results[1]["result"], serde_json::json!(["_users", "vault"]));
+    assert_eq!(results[2]["ok"], false);
+}
+
+// 少し時間のかかるレプリケーションと進捗を返すアップストリーム
+fn replication_upstream() -> Router {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    let polls = Arc::new(AtomicU64::new(0));
+    Router::new()
+        .route(
+            "/_replicate",
+            axum::routing::post(|| async {
+                tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+                Json(serde_json::json!({"ok": true, "docs_written": 20}))
+            }),
+        )
+        .route(
+            "/_active_tasks",
+            get(move || {
+                let polls = Arc::clone(&polls);
+                async move {
+                    let read = 10 * (polls.fetch_add(1, Ordering::SeqCst) + 1);
+                    Json(serde_json::json!([{
+                        "type": "replication",
+                        "source": "http://couchdb/vault/",
+                        "target": "http://couchdb/backup/",
+                        "docs_read": read,
+                        "docs_written": read,
+                    }]))
+                }
+            }),
+        )
+}
+
+#[tokio::test]
+async fn test_replicate_stream_emits_start_progress_and_done() {
+    let upstream = common::spawn_upstream(replication_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let request = Request::post("/api/replicate/stream")
+        .header(header::AUTHORIZATION, common::admin_bearer())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({"source": "vault", "target": "backup"}).to_string(),
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+
+    // レプリケーション完了後にストリームが閉じられる
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let start = body.find("event: start").expect("start event");
+    let progress = body.find("event: progress").expect("progress event");
+    let done = body.find("event: done").expect("done event");
+    assert!(start < progress && progress < done);
+    assert!(body.contains(r#""docs_read":10"#));
+}
+
+#[tokio::test]
+async fn test_replicate_stream_stops_polling_after_client_disconnects() {
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // 進捗が変わらず、レプリケーションも終わらないアップストリーム
+    let polls = Arc::new(AtomicU64::new(0));
+    let counter = Arc::clone(&polls);
+    let upstream = Router::new()
+        .route(
+            "/_replicate",
+            axum::routing::post(|| async {
+                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                Json(serde_json::json!({"ok": true}))
+            }),
+        )
+        .route(
+            "/_active_tasks",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Json(serde_json::json!([])) }
+            }),
+        );
+    let upstream = common::spawn_upstream(upstream).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let request = Request::post("/api/replicate/stream")
+        .header(header::AUTHORIZATION, common::admin_bearer())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({"source": "vault", "target": "backup"}).to_string(),
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // startイベントを受け取ったところで切断する
+    let mut body = response.into_body().into_data_stream();
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&chunk).contains("event: start"));
+    drop(body);
+
+    // 切断後は進捗のポーリングが止まる
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    let after_disconnect = polls.load(Ordering::SeqCst);
+    tokio::time::sleep(REPLICATION_POLL_INTERVAL * 3).await;
+    assert_eq!(polls.load(Ordering::SeqCst), after_disconnect);
+}
+
+async fn inflight_paths(app: &Router) -> Vec<String> {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/inflight")
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    json.as_array()
+        .unwrap()
+        .iter()
+        .map(|request| request["path"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_inflight_registry_tracks_running_requests() {
+    // 解放されるまで応答を保留するアップストリーム
+    let release = Arc::new(tokio::sync::Semaphore::new(0));
+    let started = Arc::new(tokio::sync::Semaphore::new(0));
+    let upstream = {
+        let release = release.clone();
+        let started = started.clone();
+        common::spawn_upstream(Router::new().route(
+            "/vault/_changes",
+            get(move || {
+                let release = release.clone();
+                let started = started.clone();
+                async move {
+                    started.add_permits(1);
+                    let _ = release.acquire().await;
+                    Json(serde_json::json!({"results": [], "last_seq": "1"}))
+                }
+            }),
+        ))
+        .await
+    };
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let pending = tokio::spawn(
+        app.clone().oneshot(
+            Request::get("/db/vault/_changes?feed=longpoll")
+                .body(Body::empty())
+                .unwrap(),
+        ),
+    );
+    let _ = started.acquire().await.unwrap();
+    assert_eq!(inflight_paths(&app).await, ["/db/vault/_changes"]);
+
+    release.add_permits(1);
+    assert_eq!(pending.await.unwrap().unwrap().status(), StatusCode::OK);
+    assert!(inflight_paths(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_log_stream_delivers_emitted_events() {
+    use futures::StreamExt;
+    use livesync_proxy::interfaces::web::logs::LogBroadcaster;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let broadcaster = LogBroadcaster::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(broadcaster.layer()));
+    let state = common::app_state("http://127.0.0.1:9/", ProxyConfig::default())
+        .with_log_broadcaster(broadcaster);
+    let app = create_router(Arc::new(state));
+
+    let response = app
+        .oneshot(
+            Request::get("/api/logs/stream?level=warn")
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // レベルフィルターより詳細なイベントは配信されない
+    tracing::info!(target: "log_stream_test", "filtered out");
+    tracing::warn!(target: "log_stream_test", "disk almost full");
+
+    let mut body = response.into_body().into_data_stream();
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+    assert!(chunk.contains("event: log"));
+    assert!(chunk.contains("disk almost full"));
+    assert!(chunk.contains(r#""level":"WARN""#));
+}
+
+#[tokio::test]
+async fn test_config_endpoint_redacts_secrets() {
+    use livesync_proxy::infrastructure::config::{
+        AdminConfig, AppConfig, CouchDbConfig, ServerConfig,
+    };
+
+    let config = AppConfig {
+        server: ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 3000,
+            header_read_timeout_secs: 30,
+        },
+        couchdb: CouchDbConfig {
+            url: "http://couchdb:5984/".to_string(),
+            username: "admin".to_string(),
+            password: "s3cret-password".to_string(),
+            dbname: "obsidian".to_string(),
+            failover_urls: Vec::new(),
+            nodes: Vec::new(),
+            probe_method: Default::default(),
+            connection: Default::default(),
+            routes: Vec::new(),
+            ensure_dbs: Vec::new(),
+            max_concurrent_replications: 0,
+        },
+        proxy: Default::default(),
+        document_policy: Default::default(),
+        document_transform: Default::default(),
+        admin: AdminConfig {
+            token: Some("admin-token-value".to_string()),
+        },
+        metrics: Default::default(),
+        health: Default::default(),
+    };
+    let state =
+        common::app_state("http://127.0.0.1:9/", ProxyConfig::default()).with_app_config(config);
+    let app = create_router(Arc::new(state));
+
+    let response = app
+        .oneshot(
+            Request::get("/api/config")
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(!text.contains("s3cret-password"));
+    assert!(!text.contains("admin-token-value"));
+
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(
+        json["couchdb"]["password"],
+        serde_json::json!({"redacted": true, "length": 15})
+    );
+    assert_eq!(json["admin"]["token"]["length"], 17);
+    assert_eq!(json["couchdb"]["username"], "admin");
+    assert_eq!(json["derived"]["couchdb_base_url"], "http://127.0.0.1:9/");
+    assert_eq!(json["derived"]["timeouts_secs"]["longpoll"], 120);
+}
+
+// `X-Api-Key` を検証する独自の認証（JWTなどの代わり）
+struct ApiKeyAuthenticator;
+
+#[async_trait::async_trait]
+impl AdminAuthenticator for ApiKeyAuthenticator {
+    async fn authorize(&self, headers: &HeaderMap) -> Result<Principal, AuthError> {
+        match headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+            Some("ops-key") => Ok(Principal {
+                name: "ops".to_string(),
+            }),
+            Some(_) => Err(AuthError::Forbidden("Unknown API key".to_string())),
+            None => Err(AuthError::Unauthorized("X-Api-Key is required".to_string())),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_custom_admin_authenticator_accepts_and_rejects() {
+    let state = common::app_state("http://127.0.0.1:9/", ProxyConfig::default())
+        .with_admin_authenticator(Arc::new(ApiKeyAuthenticator));
+    let app = create_router(Arc::new(state));
+    let request = |key: Option<&str>| {
+        let mut builder = Request::post("/api/maintenance")
+            .header(header::AUTHORIZATION, common::admin_bearer())
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(key) = key {
+            builder = builder.header("x-api-key", key);
+        }
+        builder
+            .body(Body::from(
+                serde_json::json!({"enabled": false}).to_string(),
+            ))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(request(Some("ops-key"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 差し替えた認証では静的トークンだけでは通らない
+    let response = app.clone().oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app.oneshot(request(Some("other-key"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "forbidden");
+    assert_eq!(json["reason"], "Unknown API key");
+}
+
+#[tokio::test]
+async fn test_captured_request_can_be_replayed() {
+    // 受け取ったボディを記録するアップストリーム
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&received);
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/note",
+        axum::routing::put(move |body: String| {
+            let recorder = Arc::clone(&recorder);
+            async move {
+                recorder.lock().unwrap().push(body);
+                (
+                    StatusCode::CREATED,
+                    Json(serde_json::json!({"ok": true, "id": "note", "rev": "1-abc"})),
+                )
+            }
+        }),
+    ))
+    .await;
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            capture_requests: 5,
+            ..ProxyConfig::default()
+        },
+    );
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::put("/db/vault/note")
+                .header(header::AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"content":"hello"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // 記録されたリクエストには認証ヘッダーを含めない
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/replay")
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let captured: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let entry = &captured[0];
+    assert_eq!(entry["method"], "PUT");
+    assert_eq!(entry["path"], "/vault/note");
+    assert!(entry["headers"].get("authorization").is_none());
+    assert_eq!(entry["body_length"], 19);
+
+    let response = app
+        .oneshot(
+            Request::post(format!("/api/replay/{}", entry["id"]))
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["rev"], "1-abc");
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0], received[1]);
+}
+
+#[tokio::test]
+async fn test_replication_status_reports_scheduler_states() {
+    // CouchDBの `_scheduler/docs` の応答例
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/_scheduler/docs",
+        get(|| async {
+            Json(serde_json::json!({
+                "total_rows": 2,
+                "offset": 0,
+                "docs": [
+                    {
+                        "database": "_replicator",
+                        "doc_id": "vault-backup",
+                        "id": "a81a78e822837e66df423d54279c15fe+continuous",
+                        "node": "couchdb@127.0.0.1",
+                        "source": "http://couchdb:5984/vault/",
+                        "target": "http://couchdb:5984/backup/",
+                        "state": "running",
+                        "info": {"revisions_checked": 15, "docs_written": 15},
+                        "error_count": 0,
+                        "last_updated": "2024-01-01T00:00:00Z",
+                        "start_time": "2024-01-01T00:00:00Z"
+                    },
+                    {
+                        "database": "_replicator",
+                        "doc_id": "vault-offsite",
+                        "id": null,
+                        "node": "couchdb@127.0.0.1",
+                        "source": "http://couchdb:5984/vault/",
+                        "target": "https://offsite.example/vault/",
+                        "state": "crashed",
+                        "info": {"error": "db_not_found: could not open https://offsite.example/vault/"},
+                        "error_count": 3,
+                        "last_updated": "2024-01-01T00:05:00Z",
+                        "start_time": "2024-01-01T00:00:00Z"
+                    }
+                ]
+            }))
+        }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let request = Request::get("/api/replicate/status")
+        .header(header::AUTHORIZATION, common::admin_bearer())
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let replications = json["replications"].as_array().unwrap();
+    assert_eq!(replications.len(), 2);
+    assert_eq!(replications[0]["doc_id"], "vault-backup");
+    assert_eq!(replications[0]["state"], "running");
+    assert!(replications[0]["error"].is_null());
+    assert_eq!(replications[1]["state"], "crashed");
+    assert_eq!(replications[1]["error_count"], 3);
+    assert!(replications[1]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("db_not_found"));
+}
+
+#[tokio::test]
+async fn test_database_summary_combines_info_with_proxy_activity() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 問い合わせ回数を数えるデータベース情報のアップストリーム
+    let calls = Arc::new(AtomicUsize::new(0));
+    let upstream_calls = Arc::clone(&calls);
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault",
+        get(move || {
+            let calls = Arc::clone(&upstream_calls);
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({
+                    "db_name": "vault",
+                    "doc_count": 42,
+                    "doc_del_count": 3,
+                    "update_seq": "45-g1AAAA",
+                    "sizes": {"file": 4096, "external": 1024, "active": 2048}
+                }))
+            }
+        }),
+    ))
+    .await;
+    let state = Arc::new(common::app_state(&upstream, ProxyConfig::default()));
+
+    // プロキシで観測した同期の状況
+    let metrics = &state.metrics_state;
+    metrics
+        .record_request("/db/vault/_changes", "GET", 200, None)
+        .await;
+    metrics
+        .record_request("/db/vault/_bulk_docs", "POST", 201, None)
+        .await;
+    metrics
+        .record_request("/db/vault/missing", "GET", 404, None)
+        .await;
+    metrics.record_database_bytes("/db/vault/_bulk_docs", 1500);
+    metrics.record_database_bytes("/db/other/_changes", 99);
+
+    let app = create_router(Arc::clone(&state));
+    let summary = || async {
+        let request = Request::get("/api/databases/vault/summary")
+            .header(header::AUTHORIZATION, common::admin_bearer())
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let json = summary().await;
+    assert_eq!(json["db"], "vault");
+    assert_eq!(json["doc_count"], 42);
+    assert_eq!(json["update_seq"], "45-g1AAAA");
+    assert_eq!(json["sizes"]["active"], 2048);
+    assert_eq!(json["proxy"]["requests"], 3);
+    assert_eq!(json["proxy"]["errors"], 1);
+    assert_eq!(json["proxy"]["bytes"], 1500);
+    assert!(json["proxy"]["last_activity"].is_string());
+
+    // 短時間に繰り返し呼ばれてもCouchDBには問い合わせない
+    summary().await;
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use livesync_proxy::application::check::run_connection_check;
+use livesync_proxy::domain::models::CouchDbDocument;
+use livesync_proxy::domain::services::CouchDbRepository;
+
+use common::in_memory::InMemoryCouchDb;
+
+#[tokio::test]
+async fn test_connection_check_reports_missing_and_existing_database() {
+    let repo = InMemoryCouchDb::new();
+
+    // データベースがまだない場合は失敗
+    let report = run_connection_check(&repo, "vault").await;
+    assert!(!report.passed());
+    assert!(report.checks[0].passed);
+    assert!(!report.checks[1].passed);
+    assert!(report.to_string().contains("[FAIL] database"));
+
+    repo.save_document(
+        "vault",
+        CouchDbDocument {
+            id: "note".to_string(),
+            rev: None,
+            data: serde_json::json!({}),
+        },
+    )
+    .await
+    .unwrap();
+
+    let report = run_connection_check(&repo, "vault").await;
+    assert!(report.passed());
+    assert!(report.to_string().ends_with("Connection check passed"));
+}
@@ -0,0 +1,475 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{HeaderMap, Response, StatusCode},
+};
+use bytes::Bytes;
+use livesync_proxy::domain::models::{
+    CouchDbDocument, DatabaseInfo, DomainError, GetDocumentOptions, Membership, ServerInfo,
+};
+use livesync_proxy::domain::services::CouchDbRepository;
+use serde_json::Value;
+
+// インメモリCouchDBリポジトリの実装
+pub struct InMemoryCouchDb {
+    databases: Mutex<HashMap<String, HashMap<String, CouchDbDocument>>>,
+    revs_limits: Mutex<HashMap<String, u64>>,
+}
+
+impl InMemoryCouchDb {
+    pub fn new() -> Self {
+        Self {
+            databases: Mutex::new(HashMap::new()),
+            revs_limits: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// リビジョンの世代番号を取り出す（`3-abc` → 3）
+fn rev_generation(rev: &str) -> Option<u64> {
+    rev.split_once('-')?.0.parse().ok()
+}
+
+// 前のリビジョンと内容から、CouchDBと同様に世代を1つ進めたリビジョンを決定的に生成する
+fn next_rev(previous: Option<&str>, id: &str, data: &Value) -> String {
+    let generation = previous.and_then(rev_generation).unwrap_or(0) + 1;
+    let mut hasher = DefaultHasher::new();
+    (previous, id, data.to_string()).hash(&mut hasher);
+    format!("{}-{:016x}", generation, hasher.finish())
+}
+
+// CouchDBの409と同じ扱いになる競合エラー
+fn conflict(doc_id: &str) -> DomainError {
+    DomainError::CouchDbError(format!(
+        "Failed to save document {} with status: 409 Conflict",
+        doc_id
+    ))
+}
+
+#[async_trait]
+impl CouchDbRepository for InMemoryCouchDb {
+    async fn get_document(
+        &self,
+        db_name: &str,
+        doc_id: &str,
+    ) -> Result<CouchDbDocument, DomainError> {
+        let databases = self.databases.lock().unwrap();
+
+        if let Some(db) = databases.get(db_name) {
+            if let Some(doc) = db.get(doc_id) {
+                return Ok(doc.clone());
+            }
+        }
+
+        Err(DomainError::CouchDbError(format!(
+            "Document {} not found in database {}",
+            doc_id, db_name
+        )))
+    }
+
+    // リビジョンの履歴や添付ファイルは保持しないため、オプションは無視する
+    async fn get_document_with_options(
+        &self,
+        db_name: &str,
+        doc_id: &str,
+        _options: &GetDocumentOptions,
+    ) -> Result<CouchDbDocument, DomainError> {
+        self.get_document(db_name, doc_id).await
+    }
+
+    async fn save_document(
+        &self,
+        db_name: &str,
+        doc: CouchDbDocument,
+    ) -> Result<CouchDbDocument, DomainError> {
+        let mut databases = self.databases.lock().unwrap();
+
+        // データベースが存在しない場合は作成
+        let db = databases.entry(db_name.to_string()).or_default();
+
+        // ドキュメントのIDが空の場合はUUIDを生成
+        let id = if doc.id.is_empty() {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            doc.id.clone()
+        };
+
+        // 既存のリビジョンと一致する場合だけ更新し、世代を進める
+        let current = db.get(&id).and_then(|existing| existing.rev.clone());
+        if current != doc.rev {
+            return Err(conflict(&id));
+        }
+        let rev = next_rev(current.as_deref(), &id, &doc.data);
+
+        // 新しいドキュメントを作成
+        let mut new_doc = doc.clone();
+        new_doc.id = id;
+        new_doc.rev = Some(rev);
+
+        // ドキュメントを保存
+        db.insert(new_doc.id.clone(), new_doc.clone());
+
+        Ok(new_doc)
+    }
+
+    async fn delete_document(
+        &self,
+        db_name: &str,
+        doc_id: &str,
+        _rev: &str,
+    ) -> Result<(), DomainError> {
+        let mut databases = self.databases.lock().unwrap();
+
+        if let Some(db) = databases.get_mut(db_name) {
+            if db.remove(doc_id).is_some() {
+                return Ok(());
+            }
+        }
+
+        Err(DomainError::CouchDbError(format!(
+            "Document {} not found in database {}",
+            doc_id, db_name
+        )))
+    }
+
+    async fn copy_document(
+        &self,
+        db_name: &str,
+        src_id: &str,
+        dest_id: &str,
+    ) -> Result<CouchDbDocument, DomainError> {
+        let mut databases = self.databases.lock().unwrap();
+
+        let db = databases
+            .get_mut(db_name)
+            .ok_or_else(|| DomainError::CouchDbError(format!("Database {} not found", db_name)))?;
+
+        let source = db.get(src_id).cloned().ok_or_else(|| {
+            DomainError::CouchDbError(format!(
+                "Document {} not found in database {}",
+                src_id, db_name
+            ))
+        })?;
+
+        // 複製先のドキュメントを作成
+        let copied = CouchDbDocument {
+            id: dest_id.to_string(),
+            rev: Some(next_rev(None, dest_id, &source.data)),
+            data: source.data,
+        };
+        db.insert(copied.id.clone(), copied.clone());
+
+        Ok(copied)
+    }
+
+    async fn create_document_if_absent(
+        &self,
+        db_name: &str,
+        doc: CouchDbDocument,
+    ) -> Result<CouchDbDocument, DomainError> {
+        if let Ok(existing) = self.get_document(db_name, &doc.id).await {
+            return Ok(existing);
+        }
+        self.save_document(db_name, doc).await
+    }
+
+    async fn bulk_get(
+        &self,
+        db_name: &str,
+        requests: Vec<(String, Option<String>)>,
+    ) -> Result<Vec<CouchDbDocument>, DomainError> {
+        let databases = self.databases.lock().unwrap();
+
+        let Some(db) = databases.get(db_name) else {
+            return Ok(Vec::new());
+        };
+
+        // 見つからない、またはリビジョンが一致しないドキュメントは読み飛ばす
+        Ok(requests
+            .into_iter()
+            .filter_map(|(id, rev)| {
+                db.get(&id)
+                    .filter(|doc| rev.is_none() || doc.rev == rev)
+                    .cloned()
+            })
+            .collect())
+    }
+
+    async fn bulk_docs(
+        &self,
+        db_name: &str,
+        docs: Vec<CouchDbDocument>,
+        new_edits: bool,
+    ) -> Result<Vec<Value>, DomainError> {
+        // レプリケーションと同様に、指定されたリビジョンをそのまま保存する
+        if !new_edits {
+            let mut databases = self.databases.lock().unwrap();
+            let db = databases.entry(db_name.to_string()).or_default();
+            for doc in docs {
+                if doc.rev.is_none() {
+                    return Err(DomainError::InvalidMessage(format!(
+                        "Document {} has no _rev with new_edits=false",
+                        doc.id
+                    )));
+                }
+                db.insert(doc.id.clone(), doc);
+            }
+            return Ok(Vec::new());
+        }
+
+        let mut results = Vec::new();
+        for doc in docs {
+            let id = doc.id.clone();
+            results.push(match self.save_document(db_name, doc).await {
+                Ok(saved) => serde_json::json!({"ok": true, "id": saved.id, "rev": saved.rev}),
+                Err(_) => serde_json::json!({
+                    "id": id,
+                    "error": "conflict",
+                    "reason": "Document update conflict.",
+                }),
+            });
+        }
+        Ok(results)
+    }
+
+    async fn query_view(
+        &self,
+        db_name: &str,
+        _design_doc: &str,
+        _view_name: &str,
+        options: Value,
+    ) -> Result<Vec<CouchDbDocument>, DomainError> {
+        let databases = self.databases.lock().unwrap();
+
+        if let Some(db) = databases.get(db_name) {
+            // HashMapの順序に依存しないよう、CouchDBと同様に `_id` 順で返す
+            let mut docs: Vec<CouchDbDocument> = db.values().cloned().collect();
+            docs.sort_by(|a, b| a.id.cmp(&b.id));
+
+            // パーティション化されたデータベースでは `{partition}:` で始まるIDに絞る
+            if let Some(partition) = options.get("partition").and_then(Value::as_str) {
+                let prefix = format!("{}:", partition);
+                docs.retain(|doc| doc.id.starts_with(&prefix));
+            }
+
+            let descending = match options.get("descending") {
+                Some(Value::Bool(descending)) => *descending,
+                Some(Value::String(descending)) => descending == "true",
+                _ => false,
+            };
+            if descending {
+                docs.reverse();
+            }
+
+            // `keys` が指定された場合はそのIDのドキュメントだけを指定順に返す
+            if let Some(keys) = options.get("keys").and_then(Value::as_array) {
+                return Ok(keys
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter_map(|key| db.get(key).cloned())
+                    .collect());
+            }
+            return Ok(docs);
+        }
+
+        Ok(vec![])
+    }
+
+    async fn warm_view(
+        &self,
+        db_name: &str,
+        _design_doc: &str,
+        _view_name: &str,
+    ) -> Result<(), DomainError> {
+        let databases = self.databases.lock().unwrap();
+        if databases.contains_key(db_name) {
+            Ok(())
+        } else {
+            Err(DomainError::CouchDbError(format!(
+                "Database {} not found",
+                db_name
+            )))
+        }
+    }
+
+    async fn ensure_database(&self, db_name: &str) -> Result<(), DomainError> {
+        let mut databases = self.databases.lock().unwrap();
+
+        databases.entry(db_name.to_string()).or_default();
+
+        Ok(())
+    }
+
+    async fn replicate(
+        &self,
+        source: &str,
+        target: &str,
+        _options: Value,
+    ) -> Result<Value, DomainError> {
+        let mut databases = self.databases.lock().unwrap();
+
+        // ソースデータベースからドキュメントを取得し、すべてコピーする
+        let docs_count = if let Some(db) = databases.get(source) {
+            let source_docs: Vec<CouchDbDocument> = db.values().cloned().collect();
+            let count = source_docs.len();
+
+            // ターゲットデータベースを取得または作成
+            let target_db = databases.entry(target.to_string()).or_default();
+
+            // ドキュメントをコピー
+            for doc in source_docs {
+                target_db.insert(doc.id.clone(), doc);
+            }
+
+            count
+        } else {
+            return Err(DomainError::CouchDbError(format!(
+                "Source database {} not found",
+                source
+            )));
+        };
+
+        Ok(serde_json::json!({
+            "ok": true,
+            "docs_read": docs_count,
+            "docs_written": docs_count,
+            "docs_failed": 0
+        }))
+    }
+
+    async fn ping(&self) -> Result<(), DomainError> {
+        Ok(())
+    }
+
+    async fn server_info(&self) -> Result<ServerInfo, DomainError> {
+        Ok(ServerInfo {
+            couchdb: "Welcome".to_string(),
+            version: "3.3.3".to_string(),
+            features: Vec::new(),
+            vendor: None,
+        })
+    }
+
+    async fn membership(&self) -> Result<Membership, DomainError> {
+        Ok(Membership::default())
+    }
+
+    async fn database_info(&self, db_name: &str) -> Result<DatabaseInfo, DomainError> {
+        let databases = self.databases.lock().unwrap();
+        let db = databases
+            .get(db_name)
+            .ok_or_else(|| DomainError::CouchDbError(format!("Database {} not found", db_name)))?;
+        Ok(DatabaseInfo {
+            db_name: db_name.to_string(),
+            doc_count: db.len() as u64,
+            doc_del_count: 0,
+            update_seq: serde_json::json!(db.len()),
+            sizes: Default::default(),
+        })
+    }
+
+    async fn list_databases(&self) -> Result<Vec<String>, DomainError> {
+        let databases = self.databases.lock().unwrap();
+        let mut names: Vec<String> = databases.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn active_tasks(&self) -> Result<Value, DomainError> {
+        Ok(serde_json::json!([]))
+    }
+
+    async fn scheduler_docs(&self) -> Result<Value, DomainError> {
+        Ok(serde_json::json!({"total_rows": 0, "offset": 0, "docs": []}))
+    }
+
+    async fn get_revs_limit(&self, db_name: &str) -> Result<u64, DomainError> {
+        let revs_limits = self.revs_limits.lock().unwrap();
+        Ok(revs_limits.get(db_name).copied().unwrap_or(1000))
+    }
+
+    async fn set_revs_limit(&self, db_name: &str, limit: u64) -> Result<(), DomainError> {
+        let mut revs_limits = self.revs_limits.lock().unwrap();
+        revs_limits.insert(db_name.to_string(), limit);
+        Ok(())
+    }
+
+    async fn create_user(
+        &self,
+        name: &str,
+        _password: &str,
+        roles: Vec<String>,
+    ) -> Result<(), DomainError> {
+        let doc = CouchDbDocument {
+            id: format!("org.couchdb.user:{}", name),
+            rev: None,
+            data: serde_json::json!({"name": name, "roles": roles, "type": "user"}),
+        };
+        self.save_document("_users", doc).await.map(|_| ())
+    }
+
+    async fn set_db_security(
+        &self,
+        _db_name: &str,
+        _members: Vec<String>,
+        _admins: Vec<String>,
+    ) -> Result<(), DomainError> {
+        Ok(())
+    }
+
+    fn get_base_url(&self) -> String {
+        "http://localhost:5984".to_string()
+    }
+
+    fn get_auth_credentials(&self) -> Option<(String, String)> {
+        Some(("admin".to_string(), "password".to_string()))
+    }
+
+    async fn forward_request_stream(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        headers: HeaderMap,
+        body: Body,
+    ) -> Result<Response<Body>, DomainError> {
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(|e| DomainError::InvalidMessage(format!("Failed to read body: {}", e)))?;
+        self.forward_request(method, path, query, headers, body)
+            .await
+    }
+
+    async fn forward_request(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<String>,
+        _headers: HeaderMap,
+        _body: Bytes,
+    ) -> Result<Response<Body>, DomainError> {
+        // クエリ値をログに出力
+        let query_str = query.unwrap_or_default();
+
+        // テスト用の簡易実装 - 常に成功レスポンスを返す
+        let response_body = serde_json::json!({
+            "ok": true,
+            "method": method,
+            "path": path,
+            "query": query_str
+        });
+
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(response_body.to_string()))
+            .map_err(|e| DomainError::InvalidMessage(format!("Failed to build response: {}", e)))?;
+
+        Ok(response)
+    }
+}
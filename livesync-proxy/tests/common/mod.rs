@@ -0,0 +1,77 @@
+#![allow(dead_code)]
+
+pub mod in_memory;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::Request;
+use axum::http::Uri;
+use axum::Router;
+use axum::ServiceExt;
+use livesync_proxy::application::services::LiveSyncService;
+use livesync_proxy::infrastructure::config::{AdminConfig, ProxyConfig};
+use livesync_proxy::infrastructure::couchdb::CouchDbClient;
+use livesync_proxy::interfaces::web::health::HealthState;
+use livesync_proxy::interfaces::web::server::{create_router, AppState};
+use tower::Layer;
+
+// CouchDBと同様に連続したスラッシュを1つにまとめる
+async fn collapse_slashes(mut req: Request) -> Request {
+    let uri = req.uri();
+    if uri.path().contains("//") {
+        let mut path = uri.path().to_string();
+        while path.contains("//") {
+            path = path.replace("//", "/");
+        }
+        let path_and_query = match uri.query() {
+            Some(q) => format!("{}?{}", path, q),
+            None => path,
+        };
+        *req.uri_mut() = Uri::try_from(path_and_query).unwrap();
+    }
+    req
+}
+
+// テスト用のCouchDB代替サーバーを起動し、ベースURLを返す
+pub async fn spawn_upstream(router: Router) -> String {
+    // ルーティングより前にパスを正規化するため、ルーター全体をラップする
+    let service = axum::middleware::map_request(collapse_slashes).layer(router);
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, ServiceExt::<Request>::into_make_service(service))
+            .await
+            .unwrap();
+    });
+    format!("http://{}/", addr)
+}
+
+/// テストで使用する管理者トークン
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+// 指定したCouchDB URLを向くアプリケーション状態を作成
+pub fn app_state(couchdb_url: &str, proxy_config: ProxyConfig) -> AppState {
+    let client = CouchDbClient::new(couchdb_url, "admin", "password");
+    let service = Arc::new(LiveSyncService::new(Arc::new(client)));
+    let health_state = Arc::new(HealthState::new(
+        Arc::clone(&service),
+        Duration::from_secs(30),
+    ));
+    AppState::new(service, health_state, proxy_config).with_admin_config(AdminConfig {
+        token: Some(ADMIN_TOKEN.to_string()),
+    })
+}
+
+// アプリケーションのルーターを作成
+pub fn app(couchdb_url: &str, proxy_config: ProxyConfig) -> Router {
+    create_router(Arc::new(app_state(couchdb_url, proxy_config)))
+}
+
+// 管理者トークン付きの `Authorization` ヘッダー値
+pub fn admin_bearer() -> String {
+    format!("Bearer {}", ADMIN_TOKEN)
+}
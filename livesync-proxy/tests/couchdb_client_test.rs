@@ -0,0 +1,994 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::response::IntoResponse;
+use axum::{
+    extract::Path,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    routing::any,
+    Json, Router,
+};
+use livesync_proxy::domain::models::{
+    CouchDbDocument, DomainError, GetDocumentOptions, Membership,
+};
+use livesync_proxy::domain::services::CouchDbRepository;
+use livesync_proxy::infrastructure::config::{
+    ConnectionConfig, CouchDbNode, NodeRole, ProbeMethod, TlsVersion,
+};
+use livesync_proxy::infrastructure::couchdb::{ChangesFeedKind, CouchDbClient, UpstreamTimeouts};
+use livesync_proxy::interfaces::web::error::ProxyError;
+
+#[tokio::test]
+async fn test_copy_document_uses_destination_header() {
+    // COPYを受け付けて複製先を返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/{id}",
+        any(
+            |method: Method, Path(id): Path<String>, headers: HeaderMap| async move {
+                match method.as_str() {
+                    "COPY" if id == "source" => {
+                        let dest = headers.get("destination").unwrap().to_str().unwrap();
+                        assert_eq!(dest, "destination");
+                        (
+                            StatusCode::CREATED,
+                            Json(serde_json::json!({"ok": true, "id": dest, "rev": "1-abc"})),
+                        )
+                    }
+                    "GET" if id == "destination" => (
+                        StatusCode::OK,
+                        Json(serde_json::json!({"_id": id, "_rev": "1-abc", "name": "Original"})),
+                    ),
+                    _ => (
+                        StatusCode::NOT_FOUND,
+                        Json(serde_json::json!({"error": "not_found"})),
+                    ),
+                }
+            },
+        ),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let copied = client
+        .copy_document("vault", "source", "destination")
+        .await
+        .unwrap();
+
+    assert_eq!(copied.id, "destination");
+    assert_eq!(copied.rev.as_deref(), Some("1-abc"));
+    assert_eq!(copied.data["name"], "Original");
+}
+
+#[tokio::test]
+async fn test_copy_document_encodes_source_and_destination_ids() {
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let received = received.clone();
+        common::spawn_upstream(Router::new().fallback(
+            move |method: Method, uri: Uri, headers: HeaderMap| {
+                let received = received.clone();
+                async move {
+                    let destination = headers
+                        .get("destination")
+                        .map(|value| value.to_str().unwrap().to_string());
+                    received.lock().unwrap().push((
+                        method.clone(),
+                        uri.path().to_string(),
+                        destination,
+                    ));
+                    match method.as_str() {
+                        "COPY" => (
+                            StatusCode::CREATED,
+                            Json(serde_json::json!({"ok": true, "id": "b#2", "rev": "1-abc"})),
+                        ),
+                        _ => (
+                            StatusCode::OK,
+                            Json(serde_json::json!({"_id": "b#2", "_rev": "1-abc"})),
+                        ),
+                    }
+                }
+            },
+        ))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let copied = client.copy_document("vault", "a?1%", "b#2").await.unwrap();
+    assert_eq!(copied.id, "b#2");
+
+    let received = received.lock().unwrap();
+    assert_eq!(received[0].0.as_str(), "COPY");
+    assert_eq!(received[0].1, "/vault/a%3F1%25");
+    assert_eq!(received[0].2.as_deref(), Some("b%232"));
+    assert_eq!(received[1].1, "/vault/b%232");
+}
+
+#[tokio::test]
+async fn test_server_info_parses_welcome_payload() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/",
+        any(|| async {
+            Json(serde_json::json!({
+                "couchdb": "Welcome",
+                "version": "3.3.3",
+                "git_sha": "40afbcfc7",
+                "uuid": "a3b8f1c2d4e5f6a7b8c9d0e1f2a3b4c5",
+                "features": ["access-ready", "partitioned", "pluggable-storage-engines"],
+                "vendor": {"name": "The Apache Software Foundation"}
+            }))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let info = client.server_info().await.unwrap();
+
+    assert_eq!(info.couchdb, "Welcome");
+    assert_eq!(info.version, "3.3.3");
+    assert!(info.features.contains(&"partitioned".to_string()));
+    assert_eq!(
+        info.vendor.map(|vendor| vendor.name).as_deref(),
+        Some("The Apache Software Foundation")
+    );
+}
+
+#[tokio::test]
+async fn test_membership_detects_cluster_and_single_node_fallback() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/_membership",
+        any(|| async {
+            Json(serde_json::json!({
+                "all_nodes": ["couchdb@node1", "couchdb@node2", "couchdb@node3"],
+                "cluster_nodes": ["couchdb@node1", "couchdb@node2", "couchdb@node3"]
+            }))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let membership = client.membership().await.unwrap();
+    assert_eq!(membership.cluster_nodes.len(), 3);
+    assert!(membership.is_clustered());
+
+    // `_membership` がないサーバーは単一ノードとして扱う
+    let upstream = common::spawn_upstream(Router::new()).await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let membership = client.membership().await.unwrap();
+    assert!(membership.cluster_nodes.is_empty());
+    assert!(!membership.is_clustered());
+
+    // 単一ノードのCouchDB 3.xも自分だけを返す
+    let single = Membership {
+        all_nodes: vec!["nonode@nohost".to_string()],
+        cluster_nodes: vec!["nonode@nohost".to_string()],
+    };
+    assert!(!single.is_clustered());
+}
+
+#[tokio::test]
+async fn test_html_error_body_is_reported_with_snippet() {
+    // 前段のプロキシが200でHTMLを返すケース
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/_all_dbs",
+        any(|| async { axum::response::Html("<html><body><h1>Bad Gateway</h1></body></html>") }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let err = client.list_databases().await.unwrap_err();
+
+    match err {
+        DomainError::CouchDbStatus { status, body } => {
+            assert_eq!(status, 200);
+            assert!(body.contains("<h1>Bad Gateway</h1>"));
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_unreachable_primary_fails_over_to_secondary() {
+    // 接続を受け付けないポートをプライマリにする
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let primary = format!("http://{}/", closed.local_addr().unwrap());
+    drop(closed);
+
+    let secondary = common::spawn_upstream(Router::new().route(
+        "/vault",
+        any(|| async { Json(serde_json::json!({"db_name": "vault"})) }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&primary, "admin", "password")
+        .with_failover_urls(std::slice::from_ref(&secondary));
+
+    let response = client
+        .http_forward_request("GET", "/vault", None, HeaderMap::new(), Default::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    // 以降は切り替え先のノードを優先する
+    assert_eq!(client.get_base_url(), secondary);
+}
+
+// リクエスト数を数えるアップストリーム
+async fn counting_upstream(hits: Arc<AtomicUsize>) -> String {
+    common::spawn_upstream(Router::new().fallback(any(move || {
+        let hits = hits.clone();
+        async move {
+            hits.fetch_add(1, Ordering::SeqCst);
+            Json(serde_json::json!({"ok": true, "rows": []}))
+        }
+    })))
+    .await
+}
+
+#[tokio::test]
+async fn test_reads_are_spread_across_replicas_and_writes_go_to_primary() {
+    let primary_hits = Arc::new(AtomicUsize::new(0));
+    let replica_a_hits = Arc::new(AtomicUsize::new(0));
+    let replica_b_hits = Arc::new(AtomicUsize::new(0));
+    let primary = counting_upstream(primary_hits.clone()).await;
+    let replica = |url: String, weight| CouchDbNode {
+        url,
+        role: NodeRole::Replica,
+        weight,
+    };
+    let client = CouchDbClient::new(&primary, "admin", "password").with_nodes(&[
+        replica(counting_upstream(replica_a_hits.clone()).await, 2),
+        replica(counting_upstream(replica_b_hits.clone()).await, 1),
+    ]);
+
+    for _ in 0..6 {
+        let response = client
+            .http_forward_request(
+                "GET",
+                "/vault/_all_docs",
+                None,
+                HeaderMap::new(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    client
+        .http_forward_request(
+            "PUT",
+            "/vault/note",
+            None,
+            HeaderMap::new(),
+            r#"{"data":"hello"}"#.into(),
+        )
+        .await
+        .unwrap();
+
+    // 重み2:1で読み取りが分散され、書き込みはプライマリのみ
+    assert_eq!(replica_a_hits.load(Ordering::SeqCst), 4);
+    assert_eq!(replica_b_hits.load(Ordering::SeqCst), 2);
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_delete_document_normalizes_if_match_rev() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/note",
+        any(|uri: axum::http::Uri| async move {
+            assert_eq!(uri.query(), Some("rev=2-abc123"));
+            Json(serde_json::json!({"ok": true, "id": "note", "rev": "3-def456"}))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    // クエリ形式とIf-Match形式のどちらでも削除できる
+    client
+        .delete_document("vault", "note", "2-abc123")
+        .await
+        .unwrap();
+    client
+        .delete_document("vault", "note", "\"2-abc123\"")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_document_id_is_encoded_when_getting_and_deleting() {
+    let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let paths = paths.clone();
+        common::spawn_upstream(Router::new().fallback(move |method: Method, uri: Uri| {
+            let paths = paths.clone();
+            async move {
+                paths.lock().unwrap().push(uri.path().to_string());
+                match method {
+                    Method::DELETE => Json(serde_json::json!({"ok": true, "rev": "3-abc"})),
+                    _ => Json(serde_json::json!({"_id": "notes/a?b#c%d", "_rev": "2-abc"})),
+                }
+            }
+        }))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let doc = client.get_document("vault", "notes/a?b#c%d").await.unwrap();
+    assert_eq!(doc.id, "notes/a?b#c%d");
+    client
+        .delete_document("vault", "notes/a?b#c%d", "2-abc")
+        .await
+        .unwrap();
+
+    // IDはパスセグメントとしてエンコードされ、パスに // は含まれない
+    assert_eq!(
+        *paths.lock().unwrap(),
+        vec![
+            "/vault/notes%2Fa%3Fb%23c%25d".to_string(),
+            "/vault/notes%2Fa%3Fb%23c%25d".to_string(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_delete_document_rejects_malformed_rev() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = counting_upstream(hits.clone()).await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    for rev in ["", "abc", "x-abc", "2-", "0-abc", "2-abc&rev=1-x"] {
+        let result = client.delete_document("vault", "note", rev).await;
+        assert!(
+            matches!(result, Err(DomainError::InvalidMessage(_))),
+            "rev {:?} should be rejected",
+            rev
+        );
+    }
+    // 不正なrevはCouchDBに送信されない
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_bulk_get_flattens_results_and_skips_errors() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_bulk_get",
+        any(|Json(body): Json<serde_json::Value>| async move {
+            assert_eq!(
+                body,
+                serde_json::json!({"docs": [
+                    {"id": "a"},
+                    {"id": "b", "rev": "2-bbb"},
+                    {"id": "missing"}
+                ]})
+            );
+            Json(serde_json::json!({"results": [
+                {"id": "a", "docs": [{"ok": {"_id": "a", "_rev": "1-aaa", "data": "A"}}]},
+                {"id": "b", "docs": [{"ok": {"_id": "b", "_rev": "2-bbb", "data": "B"}}]},
+                {"id": "missing", "docs": [{"error": {
+                    "id": "missing", "rev": "undefined", "error": "not_found", "reason": "missing"
+                }}]}
+            ]}))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let docs = client
+        .bulk_get(
+            "vault",
+            vec![
+                ("a".to_string(), None),
+                ("b".to_string(), Some("2-bbb".to_string())),
+                ("missing".to_string(), None),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let ids: Vec<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids, ["a", "b"]);
+    assert_eq!(docs[1].rev.as_deref(), Some("2-bbb"));
+    assert_eq!(docs[0].data["data"], "A");
+}
+
+#[tokio::test]
+async fn test_query_view_posts_keys() {
+    // `keys` はPOSTのボディで受け取り、該当する行だけを返すビュー
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_design/app/_view/by_id",
+        any(|method: Method, body: String| async move {
+            assert_eq!(method, Method::POST);
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            let rows: Vec<serde_json::Value> = body["keys"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|key| serde_json::json!({"id": key, "key": key, "doc": {"_id": key}}))
+                .collect();
+            Json(serde_json::json!({"rows": rows}))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let docs = client
+        .query_view(
+            "vault",
+            "app",
+            "by_id",
+            serde_json::json!({"keys": ["b", "a"], "include_docs": "true"}),
+        )
+        .await
+        .unwrap();
+    let ids: Vec<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids, ["b", "a"]);
+}
+
+#[tokio::test]
+async fn test_query_view_targets_partition() {
+    // パーティション付きのパスだけに応答するビュー
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_partition/notes/_design/app/_view/by_id",
+        any(|uri: Uri| async move {
+            // `partition` はパスに含め、クエリパラメータとしては送らない
+            let query = uri.query().unwrap_or_default().to_string();
+            assert!(!query.contains("partition"), "{}", query);
+            assert!(query.contains("include_docs=true"), "{}", query);
+            Json(serde_json::json!({
+                "rows": [{"id": "notes:a", "key": "notes:a", "doc": {"_id": "notes:a"}}]
+            }))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let docs = client
+        .query_view(
+            "vault",
+            "app",
+            "by_id",
+            serde_json::json!({"partition": "notes", "include_docs": "true"}),
+        )
+        .await
+        .unwrap();
+    let ids: Vec<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids, ["notes:a"]);
+}
+
+#[tokio::test]
+async fn test_query_view_encodes_partition_as_one_segment() {
+    let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let paths = paths.clone();
+        common::spawn_upstream(Router::new().fallback(move |uri: Uri| {
+            let paths = paths.clone();
+            async move {
+                paths.lock().unwrap().push(uri.path().to_string());
+                Json(serde_json::json!({"rows": []}))
+            }
+        }))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    client
+        .query_view(
+            "vault",
+            "app",
+            "by_id",
+            serde_json::json!({"partition": "../_users/_all_docs?x"}),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        paths.lock().unwrap()[0],
+        "/vault/_partition/..%2F_users%2F_all_docs%3Fx/_design/app/_view/by_id"
+    );
+}
+
+#[tokio::test]
+async fn test_bulk_docs_without_new_edits_sends_revisions_untouched() {
+    // レプリケーションモードでは `_rev` と `_revisions` がそのまま届くことを確認する
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_bulk_docs",
+        any(|method: Method, body: String| async move {
+            assert_eq!(method, Method::POST);
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["new_edits"], false);
+            assert_eq!(body["docs"][0]["_id"], "note");
+            assert_eq!(body["docs"][0]["_rev"], "3-ccc");
+            assert_eq!(
+                body["docs"][0]["_revisions"],
+                serde_json::json!({"start": 3, "ids": ["ccc", "bbb", "aaa"]})
+            );
+            (StatusCode::CREATED, Json(serde_json::json!([])))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let doc = CouchDbDocument {
+        id: "note".to_string(),
+        rev: Some("3-ccc".to_string()),
+        data: serde_json::json!({
+            "_revisions": {"start": 3, "ids": ["ccc", "bbb", "aaa"]},
+            "content": "replicated",
+        }),
+    };
+    let results = client.bulk_docs("vault", vec![doc], false).await.unwrap();
+    assert!(results.is_empty());
+}
+
+#[tokio::test]
+async fn test_probe_falls_back_to_get_when_head_is_rejected() {
+    // HEADを405で拒否するリバースプロキシを模したアップストリーム
+    let gets = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let gets = gets.clone();
+        common::spawn_upstream(Router::new().route(
+            "/vault",
+            any(move |method: Method| {
+                let gets = gets.clone();
+                async move {
+                    if method == Method::HEAD {
+                        return (StatusCode::METHOD_NOT_ALLOWED, Json(serde_json::json!({})));
+                    }
+                    gets.fetch_add(1, Ordering::SeqCst);
+                    (
+                        StatusCode::OK,
+                        Json(serde_json::json!({"db_name": "vault"})),
+                    )
+                }
+            }),
+        ))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    assert!(client.database_exists("vault").await.unwrap());
+    assert_eq!(gets.load(Ordering::SeqCst), 1);
+
+    // GETを指定した場合は最初からGETで確認する
+    let client = client.with_probe_method(ProbeMethod::Get);
+    assert!(client.database_exists("vault").await.unwrap());
+    assert_eq!(gets.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_base_client_uses_configured_connection_settings() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/",
+        any(|| async { Json(serde_json::json!({"couchdb": "Welcome"})) }),
+    ))
+    .await;
+    let connection = ConnectionConfig {
+        tcp_keepalive_secs: 0,
+        tcp_nodelay: false,
+        pool_idle_timeout_secs: 5,
+        min_tls_version: TlsVersion::Tls1_2,
+    };
+
+    let client = CouchDbClient::new(&upstream, "admin", "password")
+        .with_connection_config(connection.clone());
+
+    assert_eq!(client.connection_config(), &connection);
+    assert_eq!(client.connection_config().tcp_keepalive(), None);
+    assert_eq!(
+        client.connection_config().pool_idle_timeout(),
+        std::time::Duration::from_secs(5)
+    );
+    // 設定を反映して作り直したクライアントで接続できる
+    assert!(client.ping().await.is_ok());
+}
+
+#[test]
+fn test_min_tls_version_defaults_to_1_2_and_maps_to_client_version() {
+    assert_eq!(
+        ConnectionConfig::default().min_tls_version,
+        TlsVersion::Tls1_2
+    );
+    assert_eq!("1.1".parse::<TlsVersion>(), Ok(TlsVersion::Tls1_1));
+    assert_eq!("TLSv1.2".parse::<TlsVersion>(), Ok(TlsVersion::Tls1_2));
+    // TLS 1.3 を最低とする設定はネイティブのTLSで扱えないため受け付けない
+    assert!("1.3".parse::<TlsVersion>().is_err());
+    assert_eq!(
+        TlsVersion::Tls1_2.to_reqwest(),
+        reqwest::tls::Version::TLS_1_2
+    );
+
+    // 指定した最低バージョンでクライアントを作り直せる
+    let connection = ConnectionConfig {
+        min_tls_version: TlsVersion::Tls1_0,
+        ..ConnectionConfig::default()
+    };
+    let client = CouchDbClient::new("https://couchdb.example:6984", "admin", "password")
+        .with_connection_config(connection);
+    assert_eq!(
+        client.connection_config().min_tls_version.to_reqwest(),
+        reqwest::tls::Version::TLS_1_0
+    );
+}
+
+#[tokio::test]
+async fn test_create_user_encodes_user_name_in_path() {
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let received = received.clone();
+        common::spawn_upstream(Router::new().fallback(any(
+            move |uri: axum::http::Uri, Json(body): Json<serde_json::Value>| {
+                let received = received.clone();
+                async move {
+                    received
+                        .lock()
+                        .unwrap()
+                        .push((uri.path().to_string(), body));
+                    (StatusCode::CREATED, Json(serde_json::json!({"ok": true})))
+                }
+            },
+        )))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    client
+        .create_user("bob/../admin?x", "secret", Vec::new())
+        .await
+        .unwrap();
+
+    // パスではエンコードし、ドキュメントの_idはそのままの名前を使う
+    let received = received.lock().unwrap();
+    assert_eq!(
+        received[0].0,
+        "/_users/org.couchdb.user:bob%2F..%2Fadmin%3Fx"
+    );
+    assert_eq!(received[0].1["_id"], "org.couchdb.user:bob/../admin?x");
+}
+
+#[tokio::test]
+async fn test_create_user_and_set_security_send_expected_documents() {
+    // 受け取ったパスとボディを記録するアップストリーム
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let received = received.clone();
+        common::spawn_upstream(Router::new().fallback(any(
+            move |uri: axum::http::Uri, Json(body): Json<serde_json::Value>| {
+                let received = received.clone();
+                async move {
+                    received
+                        .lock()
+                        .unwrap()
+                        .push((uri.path().to_string(), body));
+                    (StatusCode::CREATED, Json(serde_json::json!({"ok": true})))
+                }
+            },
+        )))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    client
+        .create_user("alice", "secret", vec!["vault-users".to_string()])
+        .await
+        .unwrap();
+    client
+        .set_db_security("vault", vec!["alice".to_string()], Vec::new())
+        .await
+        .unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received[0].0, "/_users/org.couchdb.user:alice");
+    assert_eq!(
+        received[0].1,
+        serde_json::json!({
+            "_id": "org.couchdb.user:alice",
+            "name": "alice",
+            "password": "secret",
+            "roles": ["vault-users"],
+            "type": "user",
+        })
+    );
+    assert_eq!(received[1].0, "/vault/_security");
+    assert_eq!(
+        received[1].1,
+        serde_json::json!({
+            "admins": {"names": [], "roles": []},
+            "members": {"names": ["alice"], "roles": []},
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_database_info_parses_couchdb_response() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault",
+        any(|| async {
+            Json(serde_json::json!({
+                "db_name": "vault",
+                "purge_seq": "0-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy",
+                "update_seq": "52-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy",
+                "sizes": {"file": 409829, "external": 14506, "active": 31532},
+                "props": {},
+                "doc_del_count": 3,
+                "doc_count": 49,
+                "disk_format_version": 8,
+                "compact_running": false,
+                "cluster": {"q": 2, "n": 1, "w": 1, "r": 1},
+                "instance_start_time": "0"
+            }))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let info = client.database_info("vault").await.unwrap();
+
+    assert_eq!(info.db_name, "vault");
+    assert_eq!(info.doc_count, 49);
+    assert_eq!(info.doc_del_count, 3);
+    assert_eq!(info.update_seq, "52-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy");
+    assert_eq!(info.sizes.file, 409829);
+    assert_eq!(info.sizes.external, 14506);
+    assert_eq!(info.sizes.active, 31532);
+}
+
+#[tokio::test]
+async fn test_create_document_if_absent_does_not_put_existing_document() {
+    let puts = Arc::new(AtomicUsize::new(0));
+    let upstream = {
+        let puts = puts.clone();
+        common::spawn_upstream(Router::new().route(
+            "/vault/settings",
+            any(move |method: Method| {
+                let puts = puts.clone();
+                async move {
+                    if method == Method::PUT {
+                        puts.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Json(serde_json::json!({"_id": "settings", "_rev": "3-abc", "content": "original"}))
+                }
+            }),
+        ))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+    let doc = CouchDbDocument {
+        id: "settings".to_string(),
+        rev: None,
+        data: serde_json::json!({"content": "overwritten"}),
+    };
+
+    let existing = client
+        .create_document_if_absent("vault", doc)
+        .await
+        .unwrap();
+
+    assert_eq!(existing.rev.as_deref(), Some("3-abc"));
+    assert_eq!(existing.data["content"], "original");
+    assert_eq!(puts.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_create_document_if_absent_returns_existing_document_on_conflict() {
+    // 確認時には存在せず、PUTの直前に別のクライアントが作成した状況を再現する
+    let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let paths = paths.clone();
+        common::spawn_upstream(Router::new().fallback(move |method: Method, uri: Uri| {
+            let paths = paths.clone();
+            async move {
+                paths.lock().unwrap().push(uri.path().to_string());
+                match method {
+                    Method::HEAD => StatusCode::NOT_FOUND.into_response(),
+                    Method::PUT => (
+                        StatusCode::CONFLICT,
+                        Json(serde_json::json!({"error": "conflict", "reason": "Document update conflict."})),
+                    )
+                        .into_response(),
+                    _ => Json(serde_json::json!({"_id": "notes/a b.md", "_rev": "1-abc", "content": "original"}))
+                        .into_response(),
+                }
+            }
+        }))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+    let doc = CouchDbDocument {
+        id: "notes/a b.md".to_string(),
+        rev: None,
+        data: serde_json::json!({"content": "overwritten"}),
+    };
+
+    let existing = client
+        .create_document_if_absent("vault", doc)
+        .await
+        .unwrap();
+
+    assert_eq!(existing.rev.as_deref(), Some("1-abc"));
+    assert_eq!(existing.data["content"], "original");
+    // IDはパスセグメントとしてエンコードされる
+    let paths = paths.lock().unwrap();
+    assert_eq!(paths[0], "/vault/notes%2Fa%20b.md");
+    assert_eq!(paths[1], "/vault/notes%2Fa%20b.md");
+}
+
+#[tokio::test]
+async fn test_warm_view_queries_view_with_limit_zero() {
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream = {
+        let received = received.clone();
+        common::spawn_upstream(Router::new().fallback(move |method: Method, uri: Uri| {
+            let received = received.clone();
+            async move {
+                received.lock().unwrap().push((
+                    method,
+                    uri.path().to_string(),
+                    uri.query().map(String::from),
+                ));
+                Json(serde_json::json!({"total_rows": 12, "offset": 0, "rows": []}))
+            }
+        }))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    client.warm_view("vault", "notes", "by_path").await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].0, Method::GET);
+    assert_eq!(received[0].1, "/vault/_design/notes/_view/by_path");
+    assert_eq!(received[0].2.as_deref(), Some("limit=0"));
+}
+
+#[tokio::test]
+async fn test_builder_applies_custom_timeouts() {
+    // 応答が遅いアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault",
+        any(|| async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            Json(serde_json::json!({"db_name": "vault"}))
+        }),
+    ))
+    .await;
+    let timeouts = UpstreamTimeouts {
+        default: Duration::from_millis(200),
+        longpoll: Duration::from_secs(300),
+        changes: Duration::from_secs(30),
+    };
+
+    let client = CouchDbClient::builder(&upstream)
+        .credentials("admin", "password")
+        .default_db("vault")
+        .timeouts(timeouts)
+        .user_agent("livesync-proxy-test")
+        .verbose(false)
+        .build();
+
+    assert_eq!(client.timeouts(), timeouts);
+    assert_eq!(client.get_base_url(), upstream);
+    // 通常のリクエストは短いタイムアウトで打ち切られる
+    let started = std::time::Instant::now();
+    assert!(client.database_info("vault").await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn test_timed_out_repository_call_yields_timeout() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/",
+        any(|| async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            Json(serde_json::json!({"couchdb": "Welcome", "version": "3.3.3"}))
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::builder(&upstream)
+        .credentials("admin", "password")
+        .timeouts(UpstreamTimeouts {
+            default: Duration::from_millis(200),
+            longpoll: Duration::from_secs(300),
+            changes: Duration::from_secs(30),
+        })
+        .build();
+
+    let error = client.server_info().await.unwrap_err();
+    assert!(matches!(error, DomainError::Timeout(_)), "{:?}", error);
+
+    // プロキシのエラーレスポンスでは504になる
+    let response = ProxyError::from(error).into_response();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[test]
+fn test_changes_feed_kind_selects_timeout() {
+    let timeouts = UpstreamTimeouts {
+        default: Duration::from_secs(10),
+        longpoll: Duration::from_secs(20),
+        changes: Duration::from_secs(30),
+    };
+
+    for (query, kind, timeout) in [
+        (None, ChangesFeedKind::Normal, Some(30)),
+        (Some("since=now"), ChangesFeedKind::Normal, Some(30)),
+        (Some("feed=normal"), ChangesFeedKind::Normal, Some(30)),
+        (
+            Some("since=now&feed=longpoll"),
+            ChangesFeedKind::Longpoll,
+            Some(20),
+        ),
+        (Some("feed=continuous"), ChangesFeedKind::Continuous, None),
+        (Some("feed=eventsource"), ChangesFeedKind::EventSource, None),
+    ] {
+        let detected = ChangesFeedKind::detect("/vault/_changes", query);
+        assert_eq!(detected, Some(kind), "query {:?}", query);
+        assert_eq!(
+            timeouts.for_feed(detected),
+            timeout.map(Duration::from_secs),
+            "query {:?}",
+            query
+        );
+    }
+
+    // _changes以外は通常のタイムアウトになる
+    let detected = ChangesFeedKind::detect("/vault/_all_docs", Some("feed=longpoll"));
+    assert_eq!(detected, None);
+    assert_eq!(timeouts.for_feed(detected), Some(Duration::from_secs(10)));
+    assert!(ChangesFeedKind::Continuous.is_streaming());
+    assert!(!ChangesFeedKind::Longpoll.is_streaming());
+}
+
+#[tokio::test]
+async fn test_get_document_with_options_adds_query_params() {
+    // 受け取ったクエリ文字列を記録するアップストリーム
+    let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let upstream_queries = Arc::clone(&queries);
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/note",
+        any(move |uri: Uri| {
+            let queries = Arc::clone(&upstream_queries);
+            async move {
+                queries
+                    .lock()
+                    .unwrap()
+                    .push(uri.query().unwrap_or_default().to_string());
+                Json(serde_json::json!({"_id": "note", "_rev": "3-ccc"}))
+            }
+        }),
+    ))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+
+    let options = GetDocumentOptions {
+        rev: Some("3-ccc".to_string()),
+        atts_since: vec!["1-aaa".to_string(), "2-bbb".to_string()],
+        attachments: true,
+        att_encoding_info: true,
+    };
+    let doc = client
+        .get_document_with_options("vault", "note", &options)
+        .await
+        .unwrap();
+    assert_eq!(doc.rev.as_deref(), Some("3-ccc"));
+    // オプションを指定しない場合はクエリを付けない
+    client.get_document("vault", "note").await.unwrap();
+
+    let queries = queries.lock().unwrap();
+    let params: Vec<(String, String)> = url::form_urlencoded::parse(queries[0].as_bytes())
+        .into_owned()
+        .collect();
+    assert_eq!(
+        params,
+        vec![
+            ("rev".to_string(), "3-ccc".to_string()),
+            ("atts_since".to_string(), r#"["1-aaa","2-bbb"]"#.to_string()),
+            ("attachments".to_string(), "true".to_string()),
+            ("att_encoding_info".to_string(), "true".to_string()),
+        ]
+    );
+    assert_eq!(queries[1], "");
+}
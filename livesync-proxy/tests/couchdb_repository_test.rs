@@ -1,16 +1,27 @@
+mod common;
+
 use async_trait::async_trait;
 use axum::{
     body::Body,
-    http::{HeaderMap, Response, StatusCode},
+    http::{HeaderMap, Request, Response, StatusCode},
 };
 use bytes::Bytes;
-use livesync_proxy::domain::models::{CouchDbDocument, DomainError};
+use livesync_proxy::application::services::LiveSyncService;
+use livesync_proxy::domain::models::{
+    CouchDbDocument, DatabaseInfo, DomainError, GetDocumentOptions, Membership, ServerInfo,
+};
 use livesync_proxy::domain::services::CouchDbRepository;
+use livesync_proxy::infrastructure::config::ProxyConfig;
+use livesync_proxy::infrastructure::couchdb::longpoll_aborted_response;
+use livesync_proxy::interfaces::web::health::HealthState;
+use livesync_proxy::interfaces::web::server::{create_router, AppState};
 use mockall::mock;
 use serde_json::Value;
-use std::collections::HashMap;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::time::Duration;
+use tower::ServiceExt;
+
+use common::in_memory::InMemoryCouchDb;
 
 // モックCouchDBリポジトリの作成
 mock! {
@@ -19,12 +30,33 @@ mock! {
     #[async_trait]
     impl CouchDbRepository for CouchDbMock {
         async fn get_document(&self, db_name: &str, doc_id: &str) -> Result<CouchDbDocument, DomainError>;
+        async fn get_document_with_options(&self, db_name: &str, doc_id: &str, options: &GetDocumentOptions) -> Result<CouchDbDocument, DomainError>;
         async fn save_document(&self, db_name: &str, doc: CouchDbDocument) -> Result<CouchDbDocument, DomainError>;
         async fn delete_document(&self, db_name: &str, doc_id: &str, rev: &str) -> Result<(), DomainError>;
+        async fn copy_document(&self, db_name: &str, src_id: &str, dest_id: &str) -> Result<CouchDbDocument, DomainError>;
+        async fn create_document_if_absent(&self, db_name: &str, doc: CouchDbDocument)
+            -> Result<CouchDbDocument, DomainError>;
+        async fn bulk_get(&self, db_name: &str, requests: Vec<(String, Option<String>)>)
+            -> Result<Vec<CouchDbDocument>, DomainError>;
+        async fn bulk_docs(&self, db_name: &str, docs: Vec<CouchDbDocument>, new_edits: bool)
+            -> Result<Vec<Value>, DomainError>;
         async fn query_view(&self, db_name: &str, design_doc: &str, view_name: &str, options: Value)
             -> Result<Vec<CouchDbDocument>, DomainError>;
+        async fn warm_view(&self, db_name: &str, design_doc: &str, view_name: &str) -> Result<(), DomainError>;
         async fn ensure_database(&self, db_name: &str) -> Result<(), DomainError>;
         async fn replicate(&self, source: &str, target: &str, options: Value) -> Result<Value, DomainError>;
+        async fn ping(&self) -> Result<(), DomainError>;
+        async fn server_info(&self) -> Result<ServerInfo, DomainError>;
+        async fn membership(&self) -> Result<Membership, DomainError>;
+        async fn database_info(&self, db_name: &str) -> Result<DatabaseInfo, DomainError>;
+        async fn list_databases(&self) -> Result<Vec<String>, DomainError>;
+        async fn active_tasks(&self) -> Result<Value, DomainError>;
+        async fn scheduler_docs(&self) -> Result<Value, DomainError>;
+        async fn get_revs_limit(&self, db_name: &str) -> Result<u64, DomainError>;
+        async fn set_revs_limit(&self, db_name: &str, limit: u64) -> Result<(), DomainError>;
+        async fn create_user(&self, name: &str, password: &str, roles: Vec<String>) -> Result<(), DomainError>;
+        async fn set_db_security(&self, db_name: &str, members: Vec<String>, admins: Vec<String>)
+            -> Result<(), DomainError>;
         fn get_base_url(&self) -> String;
         fn get_auth_credentials(&self) -> Option<(String, String)>;
         async fn forward_request(
@@ -35,196 +67,14 @@ mock! {
             headers: HeaderMap,
             body: Bytes,
         ) -> Result<Response<Body>, DomainError>;
-    }
-}
-
-// インメモリCouchDBリポジトリの実装
-struct InMemoryCouchDb {
-    databases: Mutex<HashMap<String, HashMap<String, CouchDbDocument>>>,
-}
-
-impl InMemoryCouchDb {
-    fn new() -> Self {
-        Self {
-            databases: Mutex::new(HashMap::new()),
-        }
-    }
-}
-
-#[async_trait]
-impl CouchDbRepository for InMemoryCouchDb {
-    async fn get_document(
-        &self,
-        db_name: &str,
-        doc_id: &str,
-    ) -> Result<CouchDbDocument, DomainError> {
-        let databases = self.databases.lock().unwrap();
-
-        if let Some(db) = databases.get(db_name) {
-            if let Some(doc) = db.get(doc_id) {
-                return Ok(doc.clone());
-            }
-        }
-
-        Err(DomainError::CouchDbError(format!(
-            "Document {} not found in database {}",
-            doc_id, db_name
-        )))
-    }
-
-    async fn save_document(
-        &self,
-        db_name: &str,
-        doc: CouchDbDocument,
-    ) -> Result<CouchDbDocument, DomainError> {
-        let mut databases = self.databases.lock().unwrap();
-
-        // データベースが存在しない場合は作成
-        let db = databases
-            .entry(db_name.to_string())
-            .or_insert_with(HashMap::new);
-
-        // ドキュメントのIDが空の場合はUUIDを生成
-        let id = if doc.id.is_empty() {
-            uuid::Uuid::new_v4().to_string()
-        } else {
-            doc.id.clone()
-        };
-
-        // リビジョンの生成
-        let rev = format!("1-{}", uuid::Uuid::new_v4().to_string());
-
-        // 新しいドキュメントを作成
-        let mut new_doc = doc.clone();
-        new_doc.id = id;
-        new_doc.rev = Some(rev);
-
-        // ドキュメントを保存
-        db.insert(new_doc.id.clone(), new_doc.clone());
-
-        Ok(new_doc)
-    }
-
-    async fn delete_document(
-        &self,
-        db_name: &str,
-        doc_id: &str,
-        _rev: &str,
-    ) -> Result<(), DomainError> {
-        let mut databases = self.databases.lock().unwrap();
-
-        if let Some(db) = databases.get_mut(db_name) {
-            if db.remove(doc_id).is_some() {
-                return Ok(());
-            }
-        }
-
-        Err(DomainError::CouchDbError(format!(
-            "Document {} not found in database {}",
-            doc_id, db_name
-        )))
-    }
-
-    async fn query_view(
-        &self,
-        db_name: &str,
-        _design_doc: &str,
-        _view_name: &str,
-        _options: Value,
-    ) -> Result<Vec<CouchDbDocument>, DomainError> {
-        let databases = self.databases.lock().unwrap();
-
-        if let Some(db) = databases.get(db_name) {
-            let docs: Vec<CouchDbDocument> = db.values().cloned().collect();
-            return Ok(docs);
-        }
-
-        Ok(vec![])
-    }
-
-    async fn ensure_database(&self, db_name: &str) -> Result<(), DomainError> {
-        let mut databases = self.databases.lock().unwrap();
-
-        databases
-            .entry(db_name.to_string())
-            .or_insert_with(HashMap::new);
-
-        Ok(())
-    }
-
-    async fn replicate(
-        &self,
-        source: &str,
-        target: &str,
-        _options: Value,
-    ) -> Result<Value, DomainError> {
-        let mut databases = self.databases.lock().unwrap();
-
-        // ソースデータベースからドキュメントを取得し、すべてコピーする
-        let docs_count = if let Some(db) = databases.get(source) {
-            let source_docs: Vec<CouchDbDocument> = db.values().cloned().collect();
-            let count = source_docs.len();
-
-            // ターゲットデータベースを取得または作成
-            let target_db = databases
-                .entry(target.to_string())
-                .or_insert_with(HashMap::new);
-
-            // ドキュメントをコピー
-            for doc in source_docs {
-                target_db.insert(doc.id.clone(), doc);
-            }
-
-            count
-        } else {
-            return Err(DomainError::CouchDbError(format!(
-                "Source database {} not found",
-                source
-            )));
-        };
-
-        Ok(serde_json::json!({
-            "ok": true,
-            "docs_read": docs_count,
-            "docs_written": docs_count,
-            "docs_failed": 0
-        }))
-    }
-
-    fn get_base_url(&self) -> String {
-        "http://localhost:5984".to_string()
-    }
-
-    fn get_auth_credentials(&self) -> Option<(String, String)> {
-        Some(("admin".to_string(), "password".to_string()))
-    }
-
-    async fn forward_request(
-        &self,
-        method: &str,
-        path: &str,
-        query: Option<String>,
-        _headers: HeaderMap,
-        _body: Bytes,
-    ) -> Result<Response<Body>, DomainError> {
-        // クエリ値をログに出力
-        let query_str = query.unwrap_or_default();
-
-        // テスト用の簡易実装 - 常に成功レスポンスを返す
-        let response_body = serde_json::json!({
-            "ok": true,
-            "method": method,
-            "path": path,
-            "query": query_str
-        });
-
-        let response = Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "application/json")
-            .body(Body::from(response_body.to_string()))
-            .map_err(|e| DomainError::InvalidMessage(format!("Failed to build response: {}", e)))?;
-
-        Ok(response)
+        async fn forward_request_stream(
+            &self,
+            method: &str,
+            path: &str,
+            query: Option<String>,
+            headers: HeaderMap,
+            body: Body,
+        ) -> Result<Response<Body>, DomainError>;
     }
 }
 
@@ -254,6 +104,84 @@ async fn test_save_and_get_document() {
     assert_eq!(saved_doc.data, retrieved_doc.data);
 }
 
+#[tokio::test]
+async fn test_update_increments_revision_generation() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+
+    let doc = CouchDbDocument {
+        id: "note".to_string(),
+        rev: None,
+        data: serde_json::json!({"content": "v1"}),
+    };
+    let created = repo.save_document("test-db", doc).await.unwrap();
+    let first_rev = created.rev.clone().unwrap();
+    assert!(first_rev.starts_with("1-"));
+
+    let updated = repo
+        .save_document(
+            "test-db",
+            CouchDbDocument {
+                data: serde_json::json!({"content": "v2"}),
+                ..created
+            },
+        )
+        .await
+        .unwrap();
+    let second_rev = updated.rev.clone().unwrap();
+    assert!(second_rev.starts_with("2-"));
+    assert_ne!(first_rev, second_rev);
+    assert_eq!(
+        repo.get_document("test-db", "note").await.unwrap().rev,
+        Some(second_rev)
+    );
+}
+
+#[tokio::test]
+async fn test_stale_revision_is_rejected_as_conflict() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+
+    let doc = CouchDbDocument {
+        id: "note".to_string(),
+        rev: None,
+        data: serde_json::json!({"content": "v1"}),
+    };
+    let created = repo.save_document("test-db", doc.clone()).await.unwrap();
+    repo.save_document("test-db", created.clone())
+        .await
+        .unwrap();
+
+    // 古いリビジョンでの更新と、リビジョンなしでの再作成はどちらも競合になる
+    for stale in [created, doc] {
+        match repo.save_document("test-db", stale).await {
+            Err(DomainError::CouchDbError(message)) => assert!(message.contains("409")),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+    let current = repo.get_document("test-db", "note").await.unwrap();
+    assert!(current.rev.unwrap().starts_with("2-"));
+}
+
+#[tokio::test]
+async fn test_bulk_docs_without_new_edits_keeps_supplied_rev() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+
+    let doc = CouchDbDocument {
+        id: "note".to_string(),
+        rev: Some("7-replicated".to_string()),
+        data: serde_json::json!({
+            "_revisions": {"start": 7, "ids": ["replicated"]},
+            "content": "from another node",
+        }),
+    };
+    let results = repo.bulk_docs("test-db", vec![doc], false).await.unwrap();
+    assert!(results.is_empty());
+
+    // 新しいリビジョンを生成せず、渡したリビジョンがそのまま保存される
+    let stored = repo.get_document("test-db", "note").await.unwrap();
+    assert_eq!(stored.rev.as_deref(), Some("7-replicated"));
+    assert_eq!(stored.data["content"], "from another node");
+}
+
 #[tokio::test]
 async fn test_delete_document() {
     // インメモリCouchDBリポジトリを作成
@@ -280,3 +208,203 @@ async fn test_delete_document() {
     let result = repo.get_document("test-db", &saved_doc.id).await;
     assert!(result.is_err());
 }
+
+#[tokio::test]
+async fn test_copy_document() {
+    // インメモリCouchDBリポジトリを作成
+    let repo = Arc::new(InMemoryCouchDb::new());
+
+    let doc = CouchDbDocument {
+        id: "source".to_string(),
+        rev: None,
+        data: serde_json::json!({"name": "Original"}),
+    };
+    repo.save_document("test-db", doc).await.unwrap();
+
+    // ドキュメントを複製
+    let copied = repo
+        .copy_document("test-db", "source", "destination")
+        .await
+        .unwrap();
+    assert_eq!(copied.id, "destination");
+    assert_eq!(copied.data, serde_json::json!({"name": "Original"}));
+
+    // 複製元は残っていることを確認
+    assert!(repo.get_document("test-db", "source").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_query_view_returns_stable_order() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+
+    for id in ["charlie", "alpha", "echo", "bravo", "delta"] {
+        let doc = CouchDbDocument {
+            id: id.to_string(),
+            rev: None,
+            data: serde_json::json!({}),
+        };
+        repo.save_document("test-db", doc).await.unwrap();
+    }
+
+    // 何度問い合わせても `_id` 順で返ることを確認
+    for _ in 0..5 {
+        let docs = repo
+            .query_view("test-db", "app", "all", serde_json::json!({}))
+            .await
+            .unwrap();
+        let ids: Vec<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+        assert_eq!(ids, ["alpha", "bravo", "charlie", "delta", "echo"]);
+    }
+
+    let docs = repo
+        .query_view(
+            "test-db",
+            "app",
+            "all",
+            serde_json::json!({"descending": true}),
+        )
+        .await
+        .unwrap();
+    let ids: Vec<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids, ["echo", "delta", "charlie", "bravo", "alpha"]);
+}
+
+#[tokio::test]
+async fn test_get_revs_limit_through_service() {
+    let mut mock = MockCouchDbMock::new();
+    mock.expect_get_revs_limit()
+        .withf(|db_name| db_name == "vault")
+        .returning(|_| Ok(250));
+    let service = LiveSyncService::new(Arc::new(mock));
+
+    assert_eq!(service.get_revs_limit("vault").await.unwrap(), 250);
+}
+
+#[tokio::test]
+async fn test_set_revs_limit_validates_positive_integer() {
+    let mut mock = MockCouchDbMock::new();
+    mock.expect_set_revs_limit()
+        .withf(|db_name, limit| db_name == "vault" && *limit == 100)
+        .times(1)
+        .returning(|_, _| Ok(()));
+    let service = LiveSyncService::new(Arc::new(mock));
+
+    assert_eq!(service.set_revs_limit("vault", 100).await.unwrap(), 100);
+
+    // 0以下の値はCouchDBに送らずに拒否する
+    for invalid in [0, -5] {
+        let result = service.set_revs_limit("vault", invalid).await;
+        assert!(matches!(result, Err(DomainError::InvalidMessage(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_query_view_filters_to_keys() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+
+    for id in ["alpha", "bravo", "charlie"] {
+        let doc = CouchDbDocument {
+            id: id.to_string(),
+            rev: None,
+            data: serde_json::json!({}),
+        };
+        repo.save_document("test-db", doc).await.unwrap();
+    }
+
+    // 指定したキーのみが指定順に返り、存在しないキーは無視される
+    let docs = repo
+        .query_view(
+            "test-db",
+            "app",
+            "all",
+            serde_json::json!({"keys": ["charlie", "missing", "alpha"]}),
+        )
+        .await
+        .unwrap();
+    let ids: Vec<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids, ["charlie", "alpha"]);
+}
+
+#[tokio::test]
+async fn test_create_user_provisions_users_database() {
+    let mut mock = MockCouchDbMock::new();
+    let mut seq = mockall::Sequence::new();
+    mock.expect_ensure_database()
+        .withf(|db_name| db_name == "_users")
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|_| Ok(()));
+    mock.expect_create_user()
+        .withf(|name, password, roles| {
+            name == "alice" && password == "secret" && *roles == ["vault-users".to_string()]
+        })
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|_, _, _| Ok(()));
+    let service = LiveSyncService::new(Arc::new(mock));
+
+    service
+        .create_user("alice", "secret", vec!["vault-users".to_string()])
+        .await
+        .unwrap();
+
+    // 空の名前やパスワードはCouchDBに送らずに拒否する
+    let result = service.create_user("", "secret", Vec::new()).await;
+    assert!(matches!(result, Err(DomainError::InvalidMessage(_))));
+}
+
+#[tokio::test]
+async fn test_create_document_if_absent_keeps_existing_document() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+    let doc = |content: &str| CouchDbDocument {
+        id: "settings".to_string(),
+        rev: None,
+        data: serde_json::json!({ "content": content }),
+    };
+
+    let created = repo
+        .create_document_if_absent("vault", doc("original"))
+        .await
+        .unwrap();
+
+    // 2回目は上書きせずに既存のドキュメントを返す
+    let second = repo
+        .create_document_if_absent("vault", doc("overwritten"))
+        .await
+        .unwrap();
+    assert_eq!(second.rev, created.rev);
+    assert_eq!(second.data["content"], "original");
+
+    let stored = repo.get_document("vault", "settings").await.unwrap();
+    assert_eq!(stored.rev, created.rev);
+    assert_eq!(stored.data["content"], "original");
+}
+
+#[tokio::test]
+async fn test_aborted_longpoll_increments_abort_counter() {
+    let mut mock = MockCouchDbMock::new();
+    mock.expect_forward_request()
+        .returning(|_, _, _, _, _| Ok(longpoll_aborted_response().unwrap()));
+    let service = Arc::new(LiveSyncService::new(Arc::new(mock)));
+    let health_state = Arc::new(HealthState::new(
+        Arc::clone(&service),
+        Duration::from_secs(30),
+    ));
+    let state = Arc::new(AppState::new(service, health_state, ProxyConfig::default()));
+    let metrics_state = Arc::clone(&state.metrics_state);
+
+    let response = create_router(state)
+        .oneshot(
+            Request::get("/db/vault/_changes?feed=longpoll&since=now")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // 中断はエラーではなく専用のカウンターに数えられる
+    let counts = metrics_state.request_counts.read().await;
+    assert_eq!(counts.longpoll_aborts, 1);
+    assert_eq!(counts.longpoll_errors, 0);
+}
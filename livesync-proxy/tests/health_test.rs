@@ -0,0 +1,187 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    Json,
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+use livesync_proxy::application::services::LiveSyncService;
+use livesync_proxy::domain::models::CouchDbDocument;
+use livesync_proxy::domain::services::CouchDbRepository;
+use livesync_proxy::infrastructure::config::ProxyConfig;
+use livesync_proxy::infrastructure::couchdb::CouchDbClient;
+use livesync_proxy::interfaces::web::health::{
+    health_handler, jittered_interval, HealthState, JitterRng,
+};
+use livesync_proxy::interfaces::web::server::{create_router, AppState};
+
+use common::in_memory::InMemoryCouchDb;
+
+#[tokio::test]
+async fn test_stop_terminates_background_health_check() {
+    let client = CouchDbClient::new("http://127.0.0.1:9/", "admin", "secret");
+    let service = Arc::new(LiveSyncService::new(Arc::new(client)));
+    let health_state = Arc::new(HealthState::new(service, Duration::from_secs(3600)));
+
+    let handle = health_state.start_background_health_check();
+    health_state.stop();
+
+    // 長い間隔で待機中でも、停止通知ですぐに終了する
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("health check task did not stop")
+        .expect("health check task panicked");
+}
+
+#[tokio::test]
+async fn test_stale_health_status_is_reported_degraded() {
+    let client = CouchDbClient::new("http://127.0.0.1:9/", "admin", "secret");
+    let service = Arc::new(LiveSyncService::new(Arc::new(client)));
+    let health_state = Arc::new(
+        HealthState::new(service, Duration::from_secs(30))
+            .with_max_status_age(Duration::from_secs(60)),
+    );
+    health_state.update_couchdb_status(true, None).await;
+
+    let Json(response) = health_handler(State(Arc::clone(&health_state))).await;
+    assert_eq!(response.status, "healthy");
+    assert_eq!(response.reason, None);
+
+    // バックグラウンドのチェックが止まり、最後の成功結果が古くなった状態
+    health_state.couchdb_status.write().await.last_checked =
+        SystemTime::now() - Duration::from_secs(3600);
+
+    let Json(response) = health_handler(State(health_state)).await;
+    assert_eq!(response.status, "degraded");
+    assert_eq!(response.reason.as_deref(), Some("health check stale"));
+}
+
+#[test]
+fn test_jittered_interval_varies_within_band() {
+    let mut rng = JitterRng::new(42);
+    let base = Duration::from_secs(30);
+
+    let intervals: Vec<Duration> = (0..100)
+        .map(|_| jittered_interval(base, 0.2, &mut rng))
+        .collect();
+
+    for interval in &intervals {
+        assert!(*interval >= Duration::from_secs(24) && *interval <= Duration::from_secs(36));
+    }
+    // 同じ値が続かず、帯の上下に散らばる
+    assert!(intervals.iter().any(|interval| *interval < base));
+    assert!(intervals.iter().any(|interval| *interval > base));
+
+    // 同じシードなら同じ系列を再現できる
+    let mut replay = JitterRng::new(42);
+    assert_eq!(jittered_interval(base, 0.2, &mut replay), intervals[0]);
+
+    // ジッター0では間隔を変えない
+    assert_eq!(jittered_interval(base, 0.0, &mut rng), base);
+}
+
+#[tokio::test]
+async fn test_readiness_waits_for_minimum_document_count() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+    repo.ensure_database("vault").await.unwrap();
+    let service = Arc::new(LiveSyncService::new(repo.clone()));
+    let health_state = Arc::new(
+        HealthState::new(Arc::clone(&service), Duration::from_secs(30))
+            .with_min_doc_count("vault", 2)
+            .with_doc_count_cache_ttl(Duration::ZERO),
+    );
+    health_state.update_couchdb_status(true, None).await;
+    let app = create_router(Arc::new(AppState::new(
+        service,
+        health_state,
+        ProxyConfig::default(),
+    )));
+
+    let ready = || async {
+        let response = app
+            .clone()
+            .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        (status, json)
+    };
+
+    for id in ["note-1", "note-2"] {
+        let (status, json) = ready().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["status"], "not_seeded");
+
+        let doc = CouchDbDocument {
+            id: id.to_string(),
+            rev: None,
+            data: json!({"content": id}),
+        };
+        repo.save_document("vault", doc).await.unwrap();
+    }
+
+    // しきい値に達するとレディネスが成功する
+    let (status, json) = ready().await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["status"], "ready");
+}
+
+#[tokio::test]
+async fn test_ensure_databases_creates_each_and_reports_failures_in_health() {
+    let repo = Arc::new(InMemoryCouchDb::new());
+    let service = Arc::new(LiveSyncService::new(repo.clone()));
+
+    // 複数のデータベースをまとめて作成する
+    let databases = vec![
+        "vault".to_string(),
+        "team-a".to_string(),
+        "team-b".to_string(),
+    ];
+    let failures = service.ensure_databases(&databases).await;
+    assert!(failures.is_empty());
+    assert_eq!(
+        repo.list_databases().await.unwrap(),
+        vec!["team-a", "team-b", "vault"]
+    );
+
+    // 作成できなかったデータベースは理由として報告するが、レディネスは失敗させない
+    let health_state = Arc::new(
+        HealthState::new(Arc::clone(&service), Duration::from_secs(30))
+            .with_failed_databases(vec!["team-c".to_string()]),
+    );
+    health_state.update_couchdb_status(true, None).await;
+    let Json(health) = health_handler(State(Arc::clone(&health_state))).await;
+    assert_eq!(health.status, "degraded");
+    assert_eq!(
+        health.reason.as_deref(),
+        Some("Failed to ensure databases: team-c")
+    );
+
+    let app = create_router(Arc::new(AppState::new(
+        service,
+        health_state,
+        ProxyConfig::default(),
+    )));
+    let response = app
+        .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["status"], "ready");
+    assert_eq!(json["reason"], "Failed to ensure databases: team-c");
+}
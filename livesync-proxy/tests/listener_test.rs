@@ -0,0 +1,69 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use axum::{routing::get, Router};
+use livesync_proxy::interfaces::web::listener::serve;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// ヘッダーの受信期限を1秒にしたサーバーを起動し、アドレスを返す
+async fn spawn_server() -> SocketAddr {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/ping", get(|| async { "pong" }));
+    tokio::spawn(serve(
+        listener,
+        app,
+        Some(Duration::from_secs(1)),
+        std::future::pending(),
+    ));
+    addr
+}
+
+#[tokio::test]
+async fn test_complete_headers_are_served() {
+    let addr = spawn_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /ping HTTP/1.1\r\nHost: proxy\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("pong"));
+}
+
+#[tokio::test]
+async fn test_slow_headers_are_dropped() {
+    let addr = spawn_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /ping HTTP/1.1\r\nHost: proxy\r\n")
+        .await
+        .unwrap();
+    let started = Instant::now();
+
+    // ヘッダーを少しずつ送り続けても、期限を過ぎると切断される
+    let (mut reader, mut writer) = stream.into_split();
+    let trickle = tokio::spawn(async move {
+        for i in 0..20 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let line = format!("X-Slow-{}: 1\r\n", i);
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut buf = Vec::new();
+    let result = tokio::time::timeout(Duration::from_secs(3), reader.read_to_end(&mut buf)).await;
+    trickle.abort();
+
+    assert!(result.is_ok(), "connection was not closed in time");
+    assert!(buf.is_empty(), "{}", String::from_utf8_lossy(&buf));
+    assert!(started.elapsed() >= Duration::from_millis(900));
+}
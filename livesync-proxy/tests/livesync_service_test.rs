@@ -1 +1,174 @@
+mod common;
 
+use std::sync::{Arc, Mutex};
+
+use axum::{routing::any, Json, Router};
+use livesync_proxy::application::services::LiveSyncService;
+use livesync_proxy::domain::models::{
+    CouchDbDocument, DocumentPolicy, DocumentTransform, DomainError,
+};
+use livesync_proxy::infrastructure::couchdb::CouchDbClient;
+
+// すべてのリクエストに成功を返すアップストリーム
+async fn accepting_service(policy: DocumentPolicy) -> LiveSyncService {
+    let upstream = common::spawn_upstream(Router::new().fallback(any(|| async {
+        Json(serde_json::json!({"ok": true, "id": "note", "rev": "1-abc"}))
+    })))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+    LiveSyncService::new(Arc::new(client)).with_document_policy(policy)
+}
+
+fn note(data: serde_json::Value) -> CouchDbDocument {
+    CouchDbDocument {
+        id: "note".to_string(),
+        rev: None,
+        data,
+    }
+}
+
+#[tokio::test]
+async fn test_oversize_document_is_rejected() {
+    let service = accepting_service(DocumentPolicy {
+        max_size_bytes: Some(64),
+        ..DocumentPolicy::default()
+    })
+    .await;
+
+    let result = service
+        .handle_document_sync("vault", note(serde_json::json!({"data": "x".repeat(100)})))
+        .await;
+
+    assert!(matches!(result, Err(DomainError::InvalidMessage(_))));
+}
+
+#[tokio::test]
+async fn test_document_missing_required_field_is_rejected() {
+    let service = accepting_service(DocumentPolicy {
+        required_fields: vec!["type".to_string()],
+        ..DocumentPolicy::default()
+    })
+    .await;
+
+    let result = service
+        .handle_document_sync("vault", note(serde_json::json!({"data": "hello"})))
+        .await;
+
+    assert!(matches!(result, Err(DomainError::InvalidMessage(_))));
+
+    // 必須フィールドがあれば保存される
+    let saved = service
+        .handle_document_sync(
+            "vault",
+            note(serde_json::json!({"type": "plain", "data": "hello"})),
+        )
+        .await
+        .unwrap();
+    assert_eq!(saved.rev.as_deref(), Some("1-abc"));
+}
+
+#[tokio::test]
+async fn test_document_transform_adds_metadata_on_save() {
+    // 保存リクエストのボディを記録するアップストリーム
+    let saved_body = Arc::new(Mutex::new(None));
+    let upstream = {
+        let saved_body = saved_body.clone();
+        common::spawn_upstream(Router::new().fallback(any(move |body: axum::body::Bytes| {
+            let saved_body = saved_body.clone();
+            async move {
+                // データベース確認などボディのないリクエストは記録しない
+                if let Ok(body) = serde_json::from_slice::<serde_json::Value>(&body) {
+                    *saved_body.lock().unwrap() = Some(body);
+                }
+                Json(serde_json::json!({"ok": true, "id": "note", "rev": "2-def"}))
+            }
+        })))
+        .await
+    };
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+    let mut metadata = serde_json::Map::new();
+    metadata.insert("proxied_by".to_string(), "livesync-proxy".into());
+    metadata.insert("_id".to_string(), "hijacked".into());
+    let service =
+        LiveSyncService::new(Arc::new(client)).with_document_transform(DocumentTransform {
+            metadata,
+            timestamp_field: Some("proxied_at".to_string()),
+        });
+
+    let mut doc = note(serde_json::json!({"data": "hello"}));
+    doc.rev = Some("1-abc".to_string());
+    service.handle_document_sync("vault", doc).await.unwrap();
+
+    let body = saved_body.lock().unwrap().clone().unwrap();
+    assert_eq!(body["_id"], "note");
+    assert_eq!(body["_rev"], "1-abc");
+    assert_eq!(body["data"], "hello");
+    assert_eq!(body["proxied_by"], "livesync-proxy");
+    assert!(body["proxied_at"].is_string());
+}
+
+#[tokio::test]
+async fn test_replication_beyond_limit_is_rejected() {
+    // 通知されるまでレプリケーションが終わらないアップストリーム
+    let release = Arc::new(tokio::sync::Semaphore::new(0));
+    let upstream_release = Arc::clone(&release);
+    let upstream = common::spawn_upstream(Router::new().fallback(any(move || {
+        let release = Arc::clone(&upstream_release);
+        async move {
+            release.acquire().await.unwrap().forget();
+            Json(serde_json::json!({"ok": true}))
+        }
+    })))
+    .await;
+    let client = CouchDbClient::new(&upstream, "admin", "password");
+    let service =
+        Arc::new(LiveSyncService::new(Arc::new(client)).with_max_concurrent_replications(2));
+
+    let running: Vec<_> = (0..2)
+        .map(|i| {
+            let service = Arc::clone(&service);
+            tokio::spawn(async move {
+                service
+                    .handle_replication("vault", &format!("backup-{}", i), serde_json::json!({}))
+                    .await
+            })
+        })
+        .collect();
+    while service.active_replications() < 2 {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    // 上限を超えたレプリケーションはCouchDBに送らずに拒否する
+    let result = service
+        .handle_replication("vault", "backup-2", serde_json::json!({}))
+        .await;
+    assert!(matches!(result, Err(DomainError::TooManyReplications(2))));
+
+    // 実行中のものが終われば枠が空く
+    release.add_permits(2);
+    for handle in running {
+        assert!(handle.await.unwrap().is_ok());
+    }
+    assert_eq!(service.active_replications(), 0);
+}
+
+#[tokio::test]
+async fn test_detect_cluster_caches_result() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/_membership",
+        any(|| async {
+            Json(serde_json::json!({
+                "all_nodes": ["couchdb@node1", "couchdb@node2"],
+                "cluster_nodes": ["couchdb@node1", "couchdb@node2"]
+            }))
+        }),
+    ))
+    .await;
+    let service =
+        LiveSyncService::new(Arc::new(CouchDbClient::new(&upstream, "admin", "password")));
+
+    // 判定するまでは不明
+    assert_eq!(service.is_clustered(), None);
+    assert!(service.detect_cluster().await.unwrap());
+    assert_eq!(service.is_clustered(), Some(true));
+}
@@ -0,0 +1,142 @@
+use livesync_proxy::interfaces::web::metrics::{
+    extract_database, normalize_method, normalize_path, parse_client_version, MetricsState,
+    RequestCounts, MAX_CLIENT_VERSION_LABELS, MAX_DATABASE_LABELS,
+};
+
+#[test]
+fn test_extract_database_from_proxy_path() {
+    assert_eq!(extract_database("/db/vault/note"), Some("vault"));
+    assert_eq!(extract_database("/db/vault"), Some("vault"));
+    assert_eq!(extract_database("/db/vault/_changes"), Some("vault"));
+    assert_eq!(extract_database("/db/"), None);
+    assert_eq!(extract_database("/dbx/vault"), None);
+    assert_eq!(extract_database("/api/status"), None);
+}
+
+#[test]
+fn test_database_labels_are_capped() {
+    let metrics = MetricsState::new();
+
+    assert_eq!(metrics.database_label("/db/_all_dbs"), "_server");
+    for i in 0..MAX_DATABASE_LABELS {
+        let path = format!("/db/vault{}/note", i);
+        assert_eq!(metrics.database_label(&path), format!("vault{}", i));
+    }
+
+    // 上限を超えた新しいデータベースは other にまとめ、既存のものはそのまま
+    assert_eq!(metrics.database_label("/db/overflow/note"), "other");
+    assert_eq!(metrics.database_label("/db/vault0/note"), "vault0");
+}
+
+#[test]
+fn test_client_version_label_from_livesync_user_agent() {
+    let metrics = MetricsState::new();
+    let user_agent =
+        "Mozilla/5.0 (Macintosh) obsidian/1.5.12 Chrome/120.0 obsidian-livesync/0.23.5";
+
+    assert_eq!(parse_client_version(user_agent), Some("0.23.5"));
+    assert_eq!(metrics.client_version_label(Some(user_agent)), "0.23.5");
+
+    // 識別できないUser-Agentやヘッダーなしは other にまとめる
+    assert_eq!(metrics.client_version_label(Some("curl/8.4.0")), "other");
+    assert_eq!(metrics.client_version_label(None), "other");
+    assert_eq!(
+        metrics.client_version_label(Some("obsidian-livesync/{bad}")),
+        "other"
+    );
+
+    for i in 1..MAX_CLIENT_VERSION_LABELS {
+        let user_agent = format!("obsidian-livesync/0.{}.0", i);
+        assert_eq!(
+            metrics.client_version_label(Some(&user_agent)),
+            format!("0.{}.0", i)
+        );
+    }
+    assert_eq!(
+        metrics.client_version_label(Some("obsidian-livesync/9.9.9")),
+        "other"
+    );
+    assert_eq!(metrics.client_version_label(Some(user_agent)), "0.23.5");
+}
+
+#[test]
+fn test_normalize_path_collapses_encoded_document_ids() {
+    assert_eq!(normalize_path("/db/vault/note%20one"), "/db/{db}/{doc}");
+    assert_eq!(normalize_path("/db/vault/note+one"), "/db/{db}/{doc}");
+    assert_eq!(
+        normalize_path("/db/vault/note/image%2Fa.png"),
+        "/db/{db}/{doc}/{attachment}"
+    );
+    assert_eq!(normalize_path("/db//vault/"), "/db/{db}");
+}
+
+#[test]
+fn test_normalize_path_keeps_couchdb_endpoints() {
+    assert_eq!(normalize_path("/db"), "/db");
+    assert_eq!(normalize_path("/db/_all_dbs"), "/db/_all_dbs");
+    assert_eq!(normalize_path("/db/vault/_changes"), "/db/{db}/_changes");
+    assert_eq!(
+        normalize_path("/db/vault/%5Flocal/abc"),
+        "/db/{db}/_local/{doc}"
+    );
+    assert_eq!(
+        normalize_path("/db/vault/_design/app/_view/by_date"),
+        "/db/{db}/_design/{ddoc}/_view/{name}"
+    );
+    assert_eq!(normalize_path("/api/status"), "/api/status");
+    assert_eq!(normalize_method("get"), "GET");
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_without_recorder_returns_503() {
+    use std::sync::Arc;
+
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use livesync_proxy::interfaces::web::metrics::create_metrics_router;
+    use tower::ServiceExt;
+
+    let app: axum::Router = create_metrics_router(Arc::new(MetricsState::disabled()));
+    let response = app
+        .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(body.is_empty());
+}
+
+#[test]
+fn test_request_counts_summary_line() {
+    let counts = RequestCounts {
+        total: 120,
+        success: 110,
+        error: 10,
+        longpoll_requests: 40,
+        longpoll_errors: 2,
+        longpoll_aborts: 5,
+        bulk_docs_requests: 8,
+        bulk_docs_errors: 1,
+    };
+
+    assert_eq!(
+        counts.summary_line(),
+        "Request summary: total=120 success=110 error=10 longpoll=40 (errors=2, aborts=5) bulk_docs=8 (errors=1)"
+    );
+}
+
+#[tokio::test]
+async fn test_flush_on_shutdown_renders_metrics() {
+    // 終了時のフラッシュはパニックせずにPrometheus形式の出力を返す
+    let metrics = MetricsState::new();
+    metrics
+        .record_request("/db/vault/doc", "GET", 200, None)
+        .await;
+    let rendered = metrics.flush_on_shutdown(None).await;
+    assert!(rendered.is_some());
+
+    // メトリクスが無効な場合は何も出力しない
+    let disabled = MetricsState::disabled();
+    assert!(disabled.flush_on_shutdown(None).await.is_none());
+}
@@ -0,0 +1,78 @@
+use axum::{
+    body::to_bytes,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use livesync_proxy::interfaces::web::error::ProxyError;
+
+async fn render(error: ProxyError) -> (StatusCode, axum::http::HeaderMap, serde_json::Value) {
+    let response = error.into_response();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_proxy_errors_render_couchdb_style_json() {
+    let cases = [
+        (
+            ProxyError::MethodNotAllowed("PATCH".to_string()),
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            "Method PATCH is not supported by the proxy",
+        ),
+        (
+            ProxyError::TooManyRequests,
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_requests",
+            "Too many concurrent requests from this client",
+        ),
+        (
+            ProxyError::HeaderFieldsTooLarge,
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            "request_header_fields_too_large",
+            "Request headers exceed the configured limits",
+        ),
+        (
+            ProxyError::RequestBody("length limit exceeded".to_string()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "request_body_error",
+            "Failed to read request body: length limit exceeded",
+        ),
+        (
+            ProxyError::Upstream("connection refused".to_string()),
+            StatusCode::BAD_GATEWAY,
+            "bad_gateway",
+            "Failed to forward request to CouchDB: connection refused",
+        ),
+        (
+            ProxyError::ResponseBody("stream closed".to_string()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "response_body_error",
+            "Failed to process response: stream closed",
+        ),
+        (
+            ProxyError::ResponseTooLarge { limit: 1024 },
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            "Response exceeds the proxy buffer limit of 1024 bytes; request fewer documents at a time",
+        ),
+    ];
+
+    for (error, status, code, reason) in cases {
+        let (actual_status, headers, body) = render(error).await;
+        assert_eq!(actual_status, status);
+        assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+        assert_eq!(body, serde_json::json!({"error": code, "reason": reason}));
+    }
+}
+
+#[tokio::test]
+async fn test_proxy_errors_add_allow_and_retry_after_headers() {
+    let (_, headers, _) = render(ProxyError::MethodNotAllowed("PATCH".to_string())).await;
+    assert!(headers[header::ALLOW].to_str().unwrap().contains("COPY"));
+
+    let (_, headers, _) = render(ProxyError::TooManyRequests).await;
+    assert_eq!(headers[header::RETRY_AFTER], "1");
+}
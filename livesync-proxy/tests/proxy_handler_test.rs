@@ -0,0 +1,1537 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::response::IntoResponse;
+use axum::{
+    body::{to_bytes, Body},
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    routing::{any, get},
+    Json, Router,
+};
+use livesync_proxy::application::services::LiveSyncService;
+use livesync_proxy::infrastructure::config::{
+    parse_forwarded_auth_users, parse_response_header_policy, parse_trusted_proxies, ChaosConfig,
+    ErrorResponseFormat, ProxyConfig,
+};
+use livesync_proxy::infrastructure::couchdb::{CouchDbClient, UPSTREAM_TIMEOUT_HEADER};
+use livesync_proxy::interfaces::web::handlers::{
+    changes_last_seq, clamp_upstream_timeout, has_path_traversal, header_safe_line,
+    log_slow_request, pretty_json_for_debug, rewrite_upstream_location, sniff_content_type,
+    PROXY_VERSION_HEADER,
+};
+use livesync_proxy::interfaces::web::server::create_router;
+use tower::ServiceExt;
+
+// CouchDBのデータベース情報を模したアップストリーム
+fn vault_upstream() -> Router {
+    Router::new().route(
+        "/vault",
+        get(|| async { Json(serde_json::json!({"db_name": "vault", "doc_count": 0})) }),
+    )
+}
+
+#[tokio::test]
+async fn test_proxied_response_has_version_header() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(PROXY_VERSION_HEADER).unwrap(),
+        env!("CARGO_PKG_VERSION")
+    );
+}
+
+#[tokio::test]
+async fn test_version_header_can_be_disabled() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            version_header: false,
+            ..ProxyConfig::default()
+        },
+    );
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(PROXY_VERSION_HEADER).is_none());
+}
+
+#[tokio::test]
+async fn test_unsupported_method_is_rejected_with_405() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("TRACE")
+                .uri("/db/vault")
+                .header(header::ORIGIN, "app://obsidian.md")
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    let allow = response.headers().get(header::ALLOW).unwrap();
+    assert!(allow.to_str().unwrap().contains("COPY"));
+}
+
+#[tokio::test]
+async fn test_error_response_format_json_and_text() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+
+    for (format, content_type, expected) in [
+        (
+            ErrorResponseFormat::Json,
+            "application/json",
+            serde_json::json!({
+                "error": "method_not_allowed",
+                "reason": "Method TRACE is not supported by the proxy",
+            })
+            .to_string(),
+        ),
+        (
+            ErrorResponseFormat::Text,
+            "text/plain; charset=utf-8",
+            "error: method_not_allowed\nreason: Method TRACE is not supported by the proxy\n"
+                .to_string(),
+        ),
+    ] {
+        let app = common::app(
+            &upstream,
+            ProxyConfig {
+                error_response_format: format,
+                ..ProxyConfig::default()
+            },
+        );
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("TRACE")
+                    .uri("/db/vault")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], content_type);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap(), expected);
+    }
+}
+
+#[tokio::test]
+async fn test_upstream_redirect_location_is_rewritten() {
+    let upstream_base = Arc::new(std::sync::OnceLock::<String>::new());
+    let base = Arc::clone(&upstream_base);
+    let upstream = common::spawn_upstream(
+        Router::new()
+            .route(
+                "/vault/old",
+                get(move || {
+                    let location = format!("{}vault/new", base.get().unwrap());
+                    async move { (StatusCode::FOUND, [(header::LOCATION, location)]) }
+                }),
+            )
+            .route("/vault/new", get(|| async { "followed" })),
+    )
+    .await;
+    upstream_base.set(upstream.clone()).unwrap();
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(Request::get("/db/vault/old").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    // リダイレクトは追わず、CouchDBのURLをプロキシ経由のパスに書き換えて返す
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(response.headers()[header::LOCATION], "/db/vault/new");
+}
+
+#[test]
+fn test_rewrite_upstream_location() {
+    let base = "http://couchdb:5984/";
+    assert_eq!(
+        rewrite_upstream_location("http://couchdb:5984/vault/doc?rev=1", base).as_deref(),
+        Some("/db/vault/doc?rev=1")
+    );
+    assert_eq!(
+        rewrite_upstream_location("/vault/doc", base).as_deref(),
+        Some("/db/vault/doc")
+    );
+    assert_eq!(
+        rewrite_upstream_location("/couchdb/vault", "http://host/couchdb/").as_deref(),
+        Some("/db/vault")
+    );
+    // 別のホストや似た名前のポートは書き換えない
+    assert_eq!(
+        rewrite_upstream_location("https://evil.example/vault", base),
+        None
+    );
+    assert_eq!(
+        rewrite_upstream_location("http://couchdb:59840/vault", base),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_copy_method_is_forwarded() {
+    // 受け取ったメソッドをそのまま返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/note",
+        any(|method: Method| async move { Json(serde_json::json!({"method": method.as_str()})) }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("COPY")
+                .uri("/db/vault/note")
+                .header("destination", "note-copy")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["method"], "COPY");
+}
+
+#[tokio::test]
+async fn test_fallback_returns_json_404() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(Request::get("/no/such/route").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "not_found");
+    assert_eq!(json["path"], "/no/such/route");
+    assert!(json["reason"].is_string());
+}
+
+#[tokio::test]
+async fn test_ranged_get_returns_partial_content() {
+    // Rangeヘッダーに応じて部分的なボディを返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/note/image.png",
+        get(|headers: HeaderMap| async move {
+            let range = headers.get(header::RANGE).unwrap().to_str().unwrap();
+            assert_eq!(range, "bytes=0-3");
+            assert_eq!(headers.get(header::IF_RANGE).unwrap(), "\"1-abc\"");
+            (
+                StatusCode::PARTIAL_CONTENT,
+                [
+                    (header::CONTENT_TYPE, "image/png"),
+                    (header::CONTENT_RANGE, "bytes 0-3/10"),
+                ],
+                "0123",
+            )
+        }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::get("/db/vault/note/image.png")
+                .header(header::RANGE, "bytes=0-3")
+                .header(header::IF_RANGE, "\"1-abc\"")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers().get(header::CONTENT_RANGE).unwrap(),
+        "bytes 0-3/10"
+    );
+    assert_eq!(response.headers().get(header::CONTENT_LENGTH).unwrap(), "4");
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "image/png"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"0123");
+}
+
+#[test]
+fn test_upstream_timeout_override_is_clamped_to_max() {
+    assert_eq!(clamp_upstream_timeout("300", 600), Some(300));
+    assert_eq!(clamp_upstream_timeout("10000", 600), Some(600));
+    assert_eq!(clamp_upstream_timeout("0", 600), None);
+    assert_eq!(clamp_upstream_timeout("soon", 600), None);
+}
+
+#[tokio::test]
+async fn test_upstream_timeout_override_applies_only_to_trusted_requests() {
+    // 応答に2秒かかるアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_design/notes/_view/all",
+        get(|| async {
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            Json(serde_json::json!({"rows": []}))
+        }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+    let request = |authorization: Option<String>| {
+        let mut builder =
+            Request::get("/db/vault/_design/notes/_view/all").header(UPSTREAM_TIMEOUT_HEADER, "1");
+        if let Some(value) = authorization {
+            builder = builder.header(header::AUTHORIZATION, value);
+        }
+        builder.body(Body::empty()).unwrap()
+    };
+
+    // 管理者トークンがあれば上書きしたタイムアウトが適用される
+    let response = app
+        .clone()
+        .oneshot(request(Some(common::admin_bearer())))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+    // 信頼されない送信元からのヘッダーは無視される
+    let response = app.oneshot(request(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_per_client_are_limited() {
+    // longpollを模して、解放されるまで応答を保留するアップストリーム
+    let release = std::sync::Arc::new(tokio::sync::Semaphore::new(0));
+    let started = std::sync::Arc::new(tokio::sync::Semaphore::new(0));
+    let upstream = {
+        let release = release.clone();
+        let started = started.clone();
+        common::spawn_upstream(Router::new().route(
+            "/vault/_changes",
+            get(move || {
+                let release = release.clone();
+                let started = started.clone();
+                async move {
+                    started.add_permits(1);
+                    let _ = release.acquire().await;
+                    Json(serde_json::json!({"results": [], "last_seq": "1"}))
+                }
+            }),
+        ))
+        .await
+    };
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            max_concurrent_per_client: 2,
+            ..ProxyConfig::default()
+        },
+    );
+
+    let longpoll = |auth: &'static str| {
+        Request::get("/db/vault/_changes?feed=longpoll")
+            .header(header::AUTHORIZATION, auth)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let first = tokio::spawn(app.clone().oneshot(longpoll("Basic YWxpY2U6cGFzcw==")));
+    let second = tokio::spawn(app.clone().oneshot(longpoll("Basic YWxpY2U6cGFzcw==")));
+    // 2件がアップストリームに到達するまで待つ
+    let _ = started.acquire_many(2).await.unwrap();
+
+    // 同じクライアントからの3件目は拒否される
+    let third = app
+        .clone()
+        .oneshot(longpoll("Basic YWxpY2U6cGFzcw=="))
+        .await
+        .unwrap();
+    assert_eq!(third.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 別のクライアントは影響を受けない
+    let other = tokio::spawn(app.clone().oneshot(longpoll("Basic Ym9iOnBhc3M=")));
+    let _ = started.acquire_many(1).await.unwrap();
+
+    release.add_permits(3);
+    assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+    assert_eq!(second.await.unwrap().unwrap().status(), StatusCode::OK);
+    assert_eq!(other.await.unwrap().unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_longpolls_past_global_cap_are_rejected() {
+    let release = Arc::new(tokio::sync::Semaphore::new(0));
+    let started = Arc::new(tokio::sync::Semaphore::new(0));
+    let upstream = {
+        let release = release.clone();
+        let started = started.clone();
+        let router = Router::new().route(
+            "/vault/_changes",
+            get(move || {
+                let release = release.clone();
+                let started = started.clone();
+                async move {
+                    started.add_permits(1);
+                    let _ = release.acquire().await;
+                    Json(serde_json::json!({"results": [], "last_seq": "1"}))
+                }
+            }),
+        );
+        common::spawn_upstream(router.merge(vault_upstream())).await
+    };
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            max_longpoll_connections: 2,
+            ..ProxyConfig::default()
+        },
+    );
+
+    let longpoll = || {
+        Request::get("/db/vault/_changes?feed=longpoll&since=now")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // 別々のクライアントでも上限はサーバー全体で共有される
+    let first = tokio::spawn(app.clone().oneshot(longpoll()));
+    let second = tokio::spawn(app.clone().oneshot(longpoll()));
+    let _ = started.acquire_many(2).await.unwrap();
+
+    let third = app.clone().oneshot(longpoll()).await.unwrap();
+    assert_eq!(third.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(third.headers().contains_key(header::RETRY_AFTER));
+    let body = to_bytes(third.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "too_many_longpolls");
+
+    // 通常のリクエストは制限されない
+    let normal = app
+        .clone()
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(normal.status(), StatusCode::OK);
+
+    release.add_permits(2);
+    assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+    assert_eq!(second.await.unwrap().unwrap().status(), StatusCode::OK);
+
+    // 終了したlongpollの枠は解放される
+    let fourth = tokio::spawn(app.clone().oneshot(longpoll()));
+    let _ = started.acquire_many(1).await.unwrap();
+    release.add_permits(1);
+    assert_eq!(fourth.await.unwrap().unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_options_is_answered_without_upstream() {
+    let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let upstream = {
+        let hits = hits.clone();
+        common::spawn_upstream(Router::new().fallback(any(move || {
+            let hits = hits.clone();
+            async move {
+                hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                StatusCode::NOT_FOUND
+            }
+        })))
+        .await
+    };
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/db/vault")
+                .header(header::ORIGIN, "app://obsidian.md")
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // プリフライトはCORSレイヤーがCouchDBに転送せずに応答する
+    assert!(response.status().is_success());
+    let headers = response.headers();
+    assert_eq!(
+        headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "app://obsidian.md"
+    );
+    let allowed_methods = headers
+        .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(allowed_methods.contains("PUT"));
+    assert!(headers
+        .get(header::ACCESS_CONTROL_ALLOW_HEADERS)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("authorization"));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_preflight_allows_copy() {
+    let app = common::app("http://127.0.0.1:9/", ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/db/vault/note")
+                .header(header::ORIGIN, "app://obsidian.md")
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "COPY")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // CouchDB固有のCOPYもプリフライトで許可される
+    assert!(response.status().is_success());
+    let allowed_methods = response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(allowed_methods.contains("COPY"));
+}
+
+// ログ出力を記録するライター
+#[derive(Clone, Default)]
+struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_slow_request_is_logged_and_fast_one_is_not() {
+    use std::time::Duration;
+
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+    let threshold = Duration::from_secs(2);
+
+    tracing::subscriber::with_default(subscriber, || {
+        assert!(log_slow_request(
+            "GET",
+            "/db/vault/_all_docs",
+            None,
+            200,
+            Duration::from_millis(2500),
+            threshold,
+        ));
+        assert!(!log_slow_request(
+            "GET",
+            "/db/vault/fast",
+            None,
+            200,
+            Duration::from_millis(150),
+            threshold,
+        ));
+        // longpollは待機が前提のため対象外
+        assert!(!log_slow_request(
+            "GET",
+            "/db/vault/_changes",
+            Some("feed=longpoll&since=now"),
+            200,
+            Duration::from_secs(60),
+            threshold,
+        ));
+    });
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(output.contains("Slow request: GET /db/vault/_all_docs -> 200 in 2500 ms"));
+    assert!(!output.contains("/db/vault/fast"));
+    assert!(!output.contains("_changes"));
+}
+
+// CouchDBのルートとデータベース情報を返すアップストリーム
+fn root_and_vault_upstream() -> Router {
+    vault_upstream().route("/", get(|| async { Json(couchdb_welcome()) }))
+}
+
+fn couchdb_welcome() -> serde_json::Value {
+    serde_json::json!({"couchdb": "Welcome", "version": "3.3.3", "vendor": {"name": "The Apache Software Foundation"}})
+}
+
+async fn get_db_root(app: Router) -> serde_json::Value {
+    let response = app
+        .oneshot(Request::get("/db").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_db_root_maps_to_couchdb_root_by_default() {
+    let upstream = common::spawn_upstream(root_and_vault_upstream()).await;
+    let app = create_router(std::sync::Arc::new(
+        common::app_state(&upstream, ProxyConfig::default()).with_default_db("vault"),
+    ));
+
+    assert_eq!(get_db_root(app).await["couchdb"], "Welcome");
+}
+
+#[tokio::test]
+async fn test_db_root_returns_upstream_welcome_document() {
+    let upstream = common::spawn_upstream(root_and_vault_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    // プロキシ独自の応答ではなくCouchDBのウェルカムドキュメントをそのまま返す
+    assert_eq!(get_db_root(app.clone()).await, couchdb_welcome());
+
+    let response = app
+        .oneshot(Request::get("/db/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json, couchdb_welcome());
+}
+
+#[tokio::test]
+async fn test_db_root_can_map_to_default_db() {
+    let upstream = common::spawn_upstream(root_and_vault_upstream()).await;
+    let proxy_config = ProxyConfig {
+        map_root_to_default_db: true,
+        ..ProxyConfig::default()
+    };
+    let app = create_router(std::sync::Arc::new(
+        common::app_state(&upstream, proxy_config).with_default_db("vault"),
+    ));
+
+    assert_eq!(get_db_root(app).await["db_name"], "vault");
+}
+
+#[tokio::test]
+async fn test_status_uses_cached_server_info_without_waiting_for_couchdb() {
+    // 最初はウェルカムドキュメントを返し、その後は応答しなくなるアップストリーム
+    let hanging = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let upstream = {
+        let hanging = hanging.clone();
+        common::spawn_upstream(Router::new().route(
+            "/",
+            get(move || {
+                let hanging = hanging.clone();
+                async move {
+                    if hanging.load(std::sync::atomic::Ordering::SeqCst) {
+                        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                    }
+                    Json(couchdb_welcome())
+                }
+            }),
+        ))
+        .await
+    };
+    let state = Arc::new(common::app_state(&upstream, ProxyConfig::default()));
+    state.health_state.refresh_server_info().await;
+    hanging.store(true, std::sync::atomic::Ordering::SeqCst);
+    let app = create_router(state);
+
+    // CouchDBが応答しなくてもキャッシュしたバージョンをすぐに返す
+    let response = tokio::time::timeout(
+        std::time::Duration::from_secs(2),
+        app.oneshot(Request::get("/api/status").body(Body::empty()).unwrap()),
+    )
+    .await
+    .expect("status endpoint waited for CouchDB")
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["services"]["couchdb"]["version"], "3.3.3");
+}
+
+#[tokio::test]
+async fn test_excessive_headers_are_rejected_with_431() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let proxy_config = ProxyConfig {
+        max_header_count: 10,
+        max_header_bytes: 1024,
+        ..ProxyConfig::default()
+    };
+    let app = common::app(&upstream, proxy_config);
+
+    let mut too_many = Request::get("/db/vault");
+    for i in 0..20 {
+        too_many = too_many.header(format!("x-extra-{}", i), "1");
+    }
+    let response = app
+        .clone()
+        .oneshot(too_many.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+    );
+
+    let too_large = Request::get("/db/vault")
+        .header("x-extra", "a".repeat(2048))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(too_large).await.unwrap();
+    assert_eq!(
+        response.status(),
+        StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+    );
+
+    // 上限内のリクエストはそのまま転送される
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// 内部向けのヘッダーを付けて応答するアップストリーム
+fn internal_headers_upstream() -> Router {
+    Router::new().route(
+        "/vault",
+        get(|| async {
+            (
+                [("x-couch-node", "couchdb@node1"), ("x-custom", "kept")],
+                Json(serde_json::json!({"db_name": "vault"})),
+            )
+        }),
+    )
+}
+
+#[tokio::test]
+async fn test_denied_response_headers_are_removed() {
+    let upstream = common::spawn_upstream(internal_headers_upstream()).await;
+
+    // 既定のdenylistでは内部ヘッダーだけが取り除かれる
+    let app = common::app(&upstream, ProxyConfig::default());
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-couch-node"));
+    assert_eq!(response.headers()["x-custom"], "kept");
+
+    // allowlistでは指定外のヘッダーも取り除かれる
+    let proxy_config = ProxyConfig {
+        response_header_policy: parse_response_header_policy("allow:etag"),
+        ..ProxyConfig::default()
+    };
+    let app = common::app(&upstream, proxy_config);
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-custom"));
+    assert!(response.headers().contains_key(header::CONTENT_TYPE));
+    assert!(response.headers().contains_key(PROXY_VERSION_HEADER));
+}
+
+#[test]
+fn test_small_json_is_pretty_printed_for_debug() {
+    let pretty = pretty_json_for_debug(br#"{"ok":true,"rows":[1,2]}"#).unwrap();
+    assert_eq!(
+        pretty,
+        "{\n  \"ok\": true,\n  \"rows\": [\n    1,\n    2\n  ]\n}"
+    );
+
+    // JSONでないものや大きなボディは対象外
+    assert!(pretty_json_for_debug(b"<html></html>").is_none());
+    let large = format!(r#"{{"data":"{}"}}"#, "a".repeat(2000));
+    assert!(pretty_json_for_debug(large.as_bytes()).is_none());
+}
+
+#[tokio::test]
+async fn test_oversized_buffered_response_returns_413() {
+    // 標準のバッファ上限（10MB）を超えるレスポンスを返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_all_docs",
+        get(|| async { vec![b'a'; 11 * 1024 * 1024] }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::get("/db/vault/_all_docs")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "payload_too_large");
+    assert!(json["reason"]
+        .as_str()
+        .unwrap()
+        .contains(&(10 * 1024 * 1024).to_string()));
+}
+
+// 最初の1回だけ429を返すアップストリーム
+fn rate_limited_once_upstream() -> Router {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    Router::new().route(
+        "/vault",
+        get(move || {
+            let calls = calls.clone();
+            async move {
+                if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(header::RETRY_AFTER, "0")],
+                        Json(serde_json::json!({"error": "too_many_requests"})),
+                    )
+                        .into_response()
+                } else {
+                    Json(serde_json::json!({"db_name": "vault"})).into_response()
+                }
+            }
+        }),
+    )
+}
+
+#[tokio::test]
+async fn test_upstream_429_is_retried_when_enabled() {
+    let upstream = common::spawn_upstream(rate_limited_once_upstream()).await;
+    let proxy_config = ProxyConfig {
+        retry_after_max_secs: 5,
+        ..ProxyConfig::default()
+    };
+    let app = common::app(&upstream, proxy_config);
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["db_name"], "vault");
+}
+
+#[tokio::test]
+async fn test_upstream_429_passes_retry_after_through_when_disabled() {
+    let upstream = common::spawn_upstream(rate_limited_once_upstream()).await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "0");
+}
+
+#[tokio::test]
+async fn test_chaos_mode_injects_fault_with_probability_one() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let proxy_config = ProxyConfig {
+        chaos: ChaosConfig {
+            enabled: true,
+            fault_probability: 1.0,
+            ..ChaosConfig::default()
+        },
+        ..ProxyConfig::default()
+    };
+    let app = common::app(&upstream, proxy_config);
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "chaos_fault");
+}
+
+#[tokio::test]
+async fn test_chaos_mode_does_nothing_unless_enabled() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let proxy_config = ProxyConfig {
+        chaos: ChaosConfig {
+            fault_probability: 1.0,
+            ..ChaosConfig::default()
+        },
+        ..ProxyConfig::default()
+    };
+    let app = common::app(&upstream, proxy_config);
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_multipart_related_response_is_forwarded_verbatim() {
+    const CONTENT_TYPE: &str = r#"multipart/related; boundary="e89b3e29388aef23453450d10e5aaed0""#;
+    const BODY: &str = "--e89b3e29388aef23453450d10e5aaed0\r\n\
+        Content-Type: application/json\r\n\r\n\
+        {\"_id\":\"note\",\"_rev\":\"1-abc\",\"_attachments\":{\"a.png\":{\"follows\":true}}}\r\n\
+        --e89b3e29388aef23453450d10e5aaed0\r\n\
+        Content-Disposition: attachment; filename=\"a.png\"\r\n\r\n\
+        \u{89}PNG\r\n\
+        --e89b3e29388aef23453450d10e5aaed0--";
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/note",
+        get(|| async { ([(header::CONTENT_TYPE, CONTENT_TYPE)], BODY) }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::get("/db/vault/note?attachments=true")
+                .header(header::ACCEPT, "multipart/related")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], CONTENT_TYPE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, BODY.as_bytes());
+}
+
+#[tokio::test]
+async fn test_unavailable_couchdb_is_still_tried_by_default() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let state = common::app_state(&upstream, ProxyConfig::default());
+    state
+        .health_state
+        .update_couchdb_status(false, Some("down".to_string()))
+        .await;
+    let app = create_router(std::sync::Arc::new(state));
+
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_fail_fast_returns_503_while_couchdb_is_unavailable() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let proxy_config = ProxyConfig {
+        fail_fast_when_unavailable: true,
+        ..ProxyConfig::default()
+    };
+    let state = std::sync::Arc::new(common::app_state(&upstream, proxy_config));
+    state
+        .health_state
+        .update_couchdb_status(false, Some("down".to_string()))
+        .await;
+    let app = create_router(state.clone());
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "service_unavailable");
+
+    // 復旧が検知されれば転送を再開する
+    state.health_state.update_couchdb_status(true, None).await;
+    let response = app
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_prefix_routes_select_backend() {
+    // どのバックエンドに届いたかをパスとともに返すアップストリーム
+    let backend = |name: &'static str| {
+        Router::new().fallback(move |uri: axum::http::Uri| async move {
+            Json(serde_json::json!({"backend": name, "path": uri.path()}))
+        })
+    };
+    let default_upstream = common::spawn_upstream(backend("default")).await;
+    let team_a_upstream = common::spawn_upstream(backend("team-a")).await;
+
+    let team_a_client = CouchDbClient::new(&team_a_upstream, "team-a", "secret");
+    let team_a_service = Arc::new(LiveSyncService::new(Arc::new(team_a_client)));
+    let state = common::app_state(&default_upstream, ProxyConfig::default())
+        .with_backend_route("team-a/*", team_a_service);
+    let app = create_router(Arc::new(state));
+
+    for (path, backend, upstream_path) in [
+        ("/db/team-a/note", "team-a", "/team-a/note"),
+        ("/db/team-a", "team-a", "/team-a"),
+        ("/db/team-abc/note", "default", "/team-abc/note"),
+        ("/db/team-b/note", "default", "/team-b/note"),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["backend"], backend, "{}", path);
+        assert_eq!(json["path"], upstream_path, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_changes_last_seq_is_forwarded_byte_for_byte() {
+    // クラスタ構成のCouchDBが返す形式のシーケンス
+    const BODY: &str = r#"{"results":[{"seq":"23-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy","id":"note","changes":[{"rev":"2-abc"}]}],"last_seq":"23-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy_w-X","pending":0}"#;
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_changes",
+        get(|| async { ([(header::CONTENT_TYPE, "application/json")], BODY) }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::get("/db/vault/_changes?feed=longpoll&since=22")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, BODY.as_bytes());
+    assert_eq!(
+        changes_last_seq(&body),
+        Some(r#""23-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy_w-X""#)
+    );
+}
+
+#[tokio::test]
+async fn test_original_path_is_forwarded_when_enabled() {
+    // 受け取った監査用ヘッダーを返すアップストリーム
+    let upstream =
+        common::spawn_upstream(Router::new().fallback(|headers: HeaderMap| async move {
+            let get = |name: &str| headers.get(name).map(|v| v.to_str().unwrap().to_string());
+            Json(serde_json::json!({
+                "path": get("x-original-path"),
+                "query": get("x-original-query"),
+            }))
+        }))
+        .await;
+
+    let request = || {
+        Request::get("/db/vault/notes%2Fdaily.md?rev=1-abc&attachments=true")
+            .header("x-original-path", "/spoofed")
+            .body(Body::empty())
+            .unwrap()
+    };
+    let body_json = |response: axum::response::Response| async move {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+    };
+
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            forward_original_path: true,
+            ..ProxyConfig::default()
+        },
+    );
+    let json = body_json(app.oneshot(request()).await.unwrap()).await;
+    assert_eq!(json["path"], "/db/vault/notes%2Fdaily.md");
+    assert_eq!(json["query"], "rev=1-abc&attachments=true");
+
+    // 無効な場合は付与しない
+    let app = common::app(&upstream, ProxyConfig::default());
+    let json = body_json(app.oneshot(request()).await.unwrap()).await;
+    assert_eq!(json["path"], "/spoofed");
+    assert_eq!(json["query"], serde_json::Value::Null);
+}
+
+#[test]
+fn test_header_safe_line_strips_control_characters() {
+    assert_eq!(header_safe_line("/db/a\r\nb\tc"), "/db/a??b?c");
+    assert_eq!(header_safe_line(&"a".repeat(5000)).len(), 2048);
+}
+
+#[tokio::test]
+async fn test_empty_put_forwards_content_type_and_zero_length() {
+    // 受け取ったヘッダーとボディの長さを返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/newdb",
+        any(
+            |method: Method, headers: HeaderMap, body: axum::body::Bytes| async move {
+                let get = |name: header::HeaderName| {
+                    headers
+                        .get_all(name)
+                        .iter()
+                        .map(|v| v.to_str().unwrap().to_string())
+                        .collect::<Vec<_>>()
+                };
+                (
+                    StatusCode::CREATED,
+                    Json(serde_json::json!({
+                        "method": method.as_str(),
+                        "content_length": get(header::CONTENT_LENGTH),
+                        "content_type": get(header::CONTENT_TYPE),
+                        "body_len": body.len(),
+                    })),
+                )
+            },
+        ),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    // クライアントが `Content-Length` を付けない場合も、付けた場合も重複せずに0となる
+    for content_length in [None, Some("0")] {
+        let mut request =
+            Request::put("/db/newdb").header(header::CONTENT_TYPE, "application/json");
+        if let Some(content_length) = content_length {
+            request = request.header(header::CONTENT_LENGTH, content_length);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["method"], "PUT");
+        assert_eq!(json["content_length"], serde_json::json!(["0"]));
+        assert_eq!(
+            json["content_type"],
+            serde_json::json!(["application/json"])
+        );
+        assert_eq!(json["body_len"], 0);
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_route_returns_not_implemented() {
+    let app = common::app("http://127.0.0.1:9/", ProxyConfig::default());
+
+    let response = app
+        .oneshot(
+            Request::get("/ws")
+                .header(header::CONNECTION, "upgrade")
+                .header(header::UPGRADE, "websocket")
+                .header("sec-websocket-version", "13")
+                .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "not_implemented");
+    let reason = json["reason"].as_str().unwrap();
+    assert!(reason.contains("WebSocket") && reason.contains("/db"));
+}
+
+#[tokio::test]
+async fn test_cors_debug_rejects_disallowed_origin_with_json() {
+    let upstream = common::spawn_upstream(vault_upstream()).await;
+    let request = |origin: &str| {
+        Request::get("/db/vault")
+            .header(header::ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            cors_debug: true,
+            ..ProxyConfig::default()
+        },
+    );
+    let response = app
+        .clone()
+        .oneshot(request("https://evil.example"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "forbidden");
+    assert_eq!(json["origin"], "https://evil.example");
+
+    // 許可されたオリジンは通常どおり転送される
+    let response = app.oneshot(request("app://obsidian.md")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 既定ではCORSヘッダーを付けないだけで拒否しない
+    let app = common::app(&upstream, ProxyConfig::default());
+    let response = app.oneshot(request("https://evil.example")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn test_forwarded_user_from_trusted_proxy_uses_mapped_credentials() {
+    // 受け取ったAuthorizationヘッダーを返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault",
+        get(|headers: HeaderMap| async move {
+            let authorization = headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            Json(serde_json::json!({ "authorization": authorization }))
+        }),
+    ))
+    .await;
+    let app = common::app(
+        &upstream,
+        ProxyConfig {
+            trusted_proxies: parse_trusted_proxies("10.0.0.0/8"),
+            forwarded_auth_users: parse_forwarded_auth_users("alice=couch_alice:secret"),
+            ..ProxyConfig::default()
+        },
+    );
+    let request = |peer: &str, user: &str| {
+        let mut request = Request::get("/db/vault")
+            .header("X-Auth-User", user)
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        request
+    };
+    let authorization = |response: axum::response::Response| async move {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["authorization"].as_str().unwrap().to_string()
+    };
+
+    // 信頼できるプロキシからの既知のユーザーは対応する認証情報で転送される
+    let response = app
+        .clone()
+        .oneshot(request("10.0.0.5:40000", "alice"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        authorization(response).await,
+        format!(
+            "Basic {}",
+            livesync_proxy::utils::base64_encode("couch_alice:secret")
+        )
+    );
+
+    // 信頼できない接続元や未知のユーザーはデフォルトの認証情報のまま
+    for (peer, user) in [("203.0.113.9:40000", "alice"), ("10.0.0.5:40000", "bob")] {
+        let response = app.clone().oneshot(request(peer, user)).await.unwrap();
+        assert_ne!(
+            authorization(response).await,
+            format!(
+                "Basic {}",
+                livesync_proxy::utils::base64_encode("couch_alice:secret")
+            )
+        );
+    }
+}
+
+#[test]
+fn test_sniff_content_type_from_body() {
+    assert_eq!(
+        sniff_content_type(b"  {\"ok\":true}"),
+        Some("application/json")
+    );
+    assert_eq!(sniff_content_type(b"[1,2]"), Some("application/json"));
+    assert_eq!(
+        sniff_content_type(b"<html><body>Bad Gateway</body></html>"),
+        Some("text/html")
+    );
+    assert_eq!(
+        sniff_content_type(&[0x89, b'P', b'N', b'G']),
+        Some("application/octet-stream")
+    );
+    assert_eq!(sniff_content_type(b""), None);
+}
+
+#[tokio::test]
+async fn test_missing_content_type_is_sniffed_but_upstream_value_is_kept() {
+    // Content-Typeを付けずにボディを返すアップストリーム
+    let bare = |body: &'static [u8]| {
+        move || async move { axum::response::Response::new(Body::from(body)) }
+    };
+    let upstream = common::spawn_upstream(
+        Router::new()
+            .route("/vault/json", get(bare(b"{\"ok\":true}")))
+            .route("/vault/html", get(bare(b"<html>error</html>")))
+            .route("/vault/binary", get(bare(b"\x00\x01\x02")))
+            .route(
+                "/vault/text",
+                get(|| async { ([(header::CONTENT_TYPE, "text/plain")], "{not json}") }),
+            ),
+    )
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    for (path, expected) in [
+        ("/db/vault/json", "application/json"),
+        ("/db/vault/html", "text/html"),
+        ("/db/vault/binary", "application/octet-stream"),
+        // 上流が指定した値は推定で上書きしない
+        ("/db/vault/text", "text/plain"),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", path);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            expected,
+            "{}",
+            path
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_expect_header_is_forwarded_and_upstream_rejection_is_relayed() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Expectヘッダー付きの書き込みを417で拒否するアップストリーム
+    let calls = Arc::new(AtomicUsize::new(0));
+    let upstream_calls = Arc::clone(&calls);
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/_bulk_docs",
+        axum::routing::post(move |headers: HeaderMap| {
+            let calls = Arc::clone(&upstream_calls);
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                match headers.get(header::EXPECT) {
+                    Some(value) if value == "100-continue" => (
+                        StatusCode::EXPECTATION_FAILED,
+                        Json(serde_json::json!({"error": "expectation_failed"})),
+                    ),
+                    _ => (StatusCode::CREATED, Json(serde_json::json!([]))),
+                }
+            }
+        }),
+    ))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    let body = r#"{"docs":[{"_id":"note"}]}"#;
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/db/vault/_bulk_docs")
+                .header(header::EXPECT, "100-continue")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_LENGTH, body.len())
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::EXPECTATION_FAILED);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // 上限を超えるボディは上流に送らずに417を返す
+    let response = app
+        .oneshot(
+            Request::post("/db/vault/_bulk_docs")
+                .header(header::EXPECT, "100-continue")
+                .header(header::CONTENT_LENGTH, 64 * 1024 * 1024)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::EXPECTATION_FAILED);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "expectation_failed");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_has_path_traversal_decodes_before_checking() {
+    assert!(has_path_traversal("vault/../_users/org.couchdb.user:admin"));
+    assert!(has_path_traversal("vault/%2e%2e/_config"));
+    assert!(has_path_traversal("vault/%2E%2E%2F_config"));
+    assert!(has_path_traversal("vault/%252e%252e/_config"));
+    assert!(has_path_traversal("vault/./note"));
+    // ドットを含むだけのドキュメントIDは通す
+    assert!(!has_path_traversal("vault/notes.v2..md"));
+    assert!(!has_path_traversal("vault/h:..hidden"));
+}
+
+#[tokio::test]
+async fn test_traversal_paths_are_rejected_before_forwarding() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 転送されたリクエストを数えるアップストリーム
+    let calls = Arc::new(AtomicUsize::new(0));
+    let upstream_calls = Arc::clone(&calls);
+    let upstream = common::spawn_upstream(Router::new().fallback(any(move || {
+        let calls = Arc::clone(&upstream_calls);
+        async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Json(serde_json::json!({"_id": "notes.md", "_rev": "1-abc"}))
+        }
+    })))
+    .await;
+    let app = common::app(&upstream, ProxyConfig::default());
+
+    for path in ["/db/vault/../_users", "/db/vault/%2e%2e/_config"] {
+        let response = app
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "bad_request");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    // ドットを含むドキュメントIDはそのまま転送する
+    let response = app
+        .oneshot(
+            Request::get("/db/vault/notes.md")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_large_request_body_is_streamed_to_upstream() {
+    // 受け取ったボディの長さと `Content-Length` を返すアップストリーム
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault/{doc}",
+        any(|headers: HeaderMap, body: axum::body::Bytes| async move {
+            Json(serde_json::json!({
+                "received": body.len(),
+                "content_length": headers
+                    .get(header::CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string),
+            }))
+        }),
+    ))
+    .await;
+    let config = ProxyConfig {
+        stream_request_body_threshold: 1024,
+        capture_requests: 10,
+        ..ProxyConfig::default()
+    };
+    let state = Arc::new(common::app_state(&upstream, config));
+    let app = create_router(Arc::clone(&state));
+
+    let put = |doc: &str, size: usize| {
+        Request::put(format!("/db/vault/{}", doc))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_LENGTH, size)
+            .body(Body::from(vec![b'a'; size]))
+            .unwrap()
+    };
+
+    // しきい値を超えるボディはバッファせずに流す（記録の対象にならない）
+    let response = app.clone().oneshot(put("large", 64 * 1024)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["received"], 64 * 1024);
+    assert_eq!(json["content_length"], "65536");
+    assert!(state.request_capture.snapshot().is_empty());
+
+    // しきい値以下のボディは従来どおりバッファして転送する
+    let response = app.oneshot(put("small", 100)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let captured = state.request_capture.snapshot();
+    assert_eq!(captured.len(), 1);
+    assert_eq!(captured[0].path, "/vault/small");
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use livesync_proxy::infrastructure::config::{AppConfig, CouchDbConfig, ProxyConfig, ServerConfig};
+use livesync_proxy::interfaces::web::handlers::PROXY_VERSION_HEADER;
+use livesync_proxy::interfaces::web::reload::{apply_config_reload, spawn_config_reload};
+use livesync_proxy::interfaces::web::server::create_router;
+use tower::ServiceExt;
+
+fn base_config() -> AppConfig {
+    AppConfig {
+        server: ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 3000,
+            header_read_timeout_secs: 30,
+        },
+        couchdb: CouchDbConfig {
+            url: "http://couchdb:5984/".to_string(),
+            username: "admin".to_string(),
+            password: "secret".to_string(),
+            dbname: "obsidian".to_string(),
+            failover_urls: Vec::new(),
+            nodes: Vec::new(),
+            probe_method: Default::default(),
+            connection: Default::default(),
+            routes: Vec::new(),
+            ensure_dbs: Vec::new(),
+            max_concurrent_replications: 0,
+        },
+        proxy: Default::default(),
+        document_policy: Default::default(),
+        document_transform: Default::default(),
+        admin: Default::default(),
+        metrics: Default::default(),
+        health: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_sighup_reload_updates_runtime_tunable_value() {
+    let upstream = common::spawn_upstream(Router::new().route(
+        "/vault",
+        get(|| async { Json(serde_json::json!({"db_name": "vault"})) }),
+    ))
+    .await;
+    let state = Arc::new(common::app_state(&upstream, ProxyConfig::default()));
+
+    let reloaded = {
+        let mut config = base_config();
+        config.proxy.version_header = false;
+        config.proxy.max_concurrent_per_client = 4;
+        config
+    };
+    let _reload =
+        spawn_config_reload(Arc::clone(&state), base_config(), move || reloaded.clone()).unwrap();
+
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // シグナルの処理は非同期のため、反映されるまで待つ
+    for _ in 0..50 {
+        if !state.proxy_config().version_header {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(!state.proxy_config().version_header);
+    assert_eq!(state.proxy_config().max_concurrent_per_client, 4);
+
+    let response = create_router(state)
+        .oneshot(Request::get("/db/vault").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key(PROXY_VERSION_HEADER));
+}
+
+#[tokio::test]
+async fn test_reload_skips_settings_that_require_restart() {
+    let state = common::app_state("http://127.0.0.1:9/", ProxyConfig::default());
+    let mut current = base_config();
+
+    let mut new = base_config();
+    new.server.port = 4000;
+    new.proxy.slow_request_threshold_ms = 500;
+
+    let changes = current.changes(&new);
+    assert_eq!(changes.applied, ["slow_request_threshold_ms"]);
+    assert_eq!(changes.restart_required, ["server"]);
+
+    apply_config_reload(&state, &mut current, new);
+    assert_eq!(state.proxy_config().slow_request_threshold_ms, 500);
+    // 再起動が必要な設定は保持したまま
+    assert_eq!(current.server.port, 3000);
+    assert_eq!(current.proxy.slow_request_threshold_ms, 500);
+}
+
+#[tokio::test]
+async fn test_reloaded_forwarded_auth_passwords_are_redacted_in_config() {
+    std::env::set_var(
+        "FORWARDED_AUTH_USERS",
+        "alice=couch_alice:forwarded-secret-pass",
+    );
+    let mut new = base_config();
+    new.proxy = AppConfig::from_env().proxy;
+    std::env::remove_var("FORWARDED_AUTH_USERS");
+    assert_eq!(new.proxy.forwarded_auth_users.len(), 1);
+
+    let state = common::app_state("http://127.0.0.1:9/", ProxyConfig::default())
+        .with_app_config(base_config());
+    let mut current = base_config();
+    apply_config_reload(&state, &mut current, new);
+    assert_eq!(state.proxy_config().forwarded_auth_users.len(), 1);
+
+    // 再読み込み後の `/db` プロキシの設定もパスワードを伏せて表示する
+    let response = create_router(Arc::new(state))
+        .oneshot(
+            Request::get("/api/config")
+                .header(header::AUTHORIZATION, common::admin_bearer())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8_lossy(&body);
+    assert!(!text.contains("forwarded-secret-pass"), "{}", text);
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json["proxy"]["forwarded_auth_users"][0]["password"]["redacted"],
+        true
+    );
+}
+
+#[test]
+fn test_reload_reads_dotenv_without_touching_process_environment() {
+    let dir = std::env::temp_dir().join(format!("livesync-proxy-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dotenv = dir.join(".env");
+    std::fs::write(&dotenv, "CAPTURE_REQUESTS=7\nCORS_DEBUG=true\n").unwrap();
+
+    let reloaded = AppConfig::reload_from_dotenv_file(&dotenv);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // .env の値は新しい設定にだけ反映され、プロセスの環境変数は変更しない
+    assert_eq!(reloaded.proxy.capture_requests, 7);
+    assert!(reloaded.proxy.cors_debug);
+    assert!(std::env::var("CAPTURE_REQUESTS").is_err());
+    assert!(std::env::var("CORS_DEBUG").is_err());
+}
@@ -0,0 +1,100 @@
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{routing::get, Json, Router};
+use livesync_proxy::application::services::LiveSyncService;
+use livesync_proxy::domain::services::CouchDbRepository;
+use livesync_proxy::infrastructure::couchdb::CouchDbClient;
+use livesync_proxy::infrastructure::transport::{UpstreamTransport, UNIX_SOCKET_BASE_URL};
+use livesync_proxy::interfaces::web::health::HealthState;
+use serde_json::json;
+use tokio::net::UnixListener;
+
+#[test]
+fn test_parse_unix_socket_url() {
+    assert_eq!(
+        UpstreamTransport::parse("unix:///run/couchdb.sock/"),
+        UpstreamTransport::Unix {
+            socket_path: PathBuf::from("/run/couchdb.sock")
+        }
+    );
+}
+
+#[test]
+fn test_parse_http_url_is_tcp() {
+    assert_eq!(
+        UpstreamTransport::parse("http://couchdb:5984/"),
+        UpstreamTransport::Tcp {
+            url: "http://couchdb:5984/".to_string()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_client_reaches_couchdb_over_unix_socket() {
+    let socket_path =
+        std::env::temp_dir().join(format!("livesync-proxy-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket_path);
+
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let upstream = Router::new().fallback(get(|| async {
+        Json(json!({"couchdb": "Welcome", "version": "3.3.3"}))
+    }));
+    tokio::spawn(async move {
+        axum::serve(listener, upstream).await.unwrap();
+    });
+
+    let client = CouchDbClient::builder(&format!("unix://{}", socket_path.display()))
+        .unix_socket(socket_path.clone())
+        .build();
+    assert_eq!(client.get_base_url(), UNIX_SOCKET_BASE_URL);
+    assert!(client.ping().await.is_ok());
+
+    let _ = std::fs::remove_file(&socket_path);
+}
+
+#[tokio::test]
+async fn test_health_check_reaches_couchdb_over_unix_socket() {
+    let socket_path =
+        std::env::temp_dir().join(format!("livesync-proxy-health-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket_path);
+
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let upstream = Router::new().fallback(get(|| async {
+        Json(json!({"couchdb": "Welcome", "version": "3.3.3"}))
+    }));
+    tokio::spawn(async move {
+        axum::serve(listener, upstream).await.unwrap();
+    });
+
+    let client = CouchDbClient::builder(&format!("unix://{}", socket_path.display()))
+        .credentials("admin", "secret")
+        .unix_socket(socket_path.clone())
+        .build();
+    let service = Arc::new(LiveSyncService::new(Arc::new(client)));
+    let health_state =
+        Arc::new(HealthState::new(service, Duration::from_millis(50)).with_jitter_fraction(0.0));
+    health_state
+        .update_couchdb_status(false, Some("Initial connection failed".to_string()))
+        .await;
+
+    // バックグラウンドのチェックがソケット経由でCouchDBに到達する
+    let handle = health_state.start_background_health_check();
+    let available = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if health_state.couchdb_status.read().await.available {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    health_state.stop();
+    let _ = handle.await;
+    let _ = std::fs::remove_file(&socket_path);
+
+    assert!(available.is_ok(), "health check never reached the socket");
+}
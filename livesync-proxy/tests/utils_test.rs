@@ -0,0 +1,39 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
+use livesync_proxy::infrastructure::config::parse_trusted_proxies;
+use livesync_proxy::utils::extract_client_ip;
+
+fn forwarded_headers(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", value.parse().unwrap());
+    headers
+}
+
+#[test]
+fn test_client_ip_from_trusted_proxy_uses_forwarded_for() {
+    let trusted = parse_trusted_proxies("10.0.0.0/8, 192.168.1.1");
+    let peer: SocketAddr = "10.1.2.3:4567".parse().unwrap();
+    let headers = forwarded_headers("203.0.113.7, 192.168.1.1");
+
+    let ip = extract_client_ip(peer, &headers, &trusted);
+
+    assert_eq!(ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+}
+
+#[test]
+fn test_client_ip_from_untrusted_peer_ignores_forwarded_for() {
+    let trusted = parse_trusted_proxies("10.0.0.0/8");
+    let peer: SocketAddr = "198.51.100.20:4567".parse().unwrap();
+    let headers = forwarded_headers("203.0.113.7");
+
+    let ip = extract_client_ip(peer, &headers, &trusted);
+
+    assert_eq!(ip, "198.51.100.20".parse::<IpAddr>().unwrap());
+}
+
+#[test]
+fn test_invalid_trusted_proxy_entries_are_skipped() {
+    let trusted = parse_trusted_proxies("10.0.0.0/8,not-an-ip,,::1");
+    assert_eq!(trusted.len(), 2);
+}
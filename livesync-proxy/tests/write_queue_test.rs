@@ -0,0 +1,173 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::Request as UpstreamRequest,
+    http::{header, Request, StatusCode},
+    routing::any,
+    Json, Router,
+};
+use livesync_proxy::infrastructure::config::ProxyConfig;
+use livesync_proxy::interfaces::web::server::create_router;
+use livesync_proxy::interfaces::web::write_queue::{is_queueable_write, replay_queued_writes};
+use tower::ServiceExt;
+
+#[test]
+fn test_only_document_writes_are_queueable() {
+    assert!(is_queueable_write("PUT", "/vault/note.md"));
+    assert!(is_queueable_write("PUT", "/vault/_design/notes"));
+    assert!(is_queueable_write("POST", "/vault/_bulk_docs"));
+    assert!(!is_queueable_write("PUT", "/vault"));
+    assert!(!is_queueable_write("GET", "/vault/note.md"));
+    assert!(!is_queueable_write("POST", "/vault/_find"));
+    assert!(!is_queueable_write("PUT", "/_users/org.couchdb.user:alice"));
+}
+
+#[tokio::test]
+async fn test_queued_write_is_replayed_when_couchdb_recovers() {
+    // 受け取った書き込みを記録するアップストリーム
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let upstream_received = Arc::clone(&received);
+    let upstream =
+        common::spawn_upstream(Router::new().fallback(any(move |request: UpstreamRequest| {
+            let received = Arc::clone(&upstream_received);
+            async move {
+                let (parts, body) = request.into_parts();
+                let body: Bytes = to_bytes(body, usize::MAX).await.unwrap();
+                received.lock().unwrap().push((
+                    parts.method.to_string(),
+                    parts.uri.path().to_string(),
+                    String::from_utf8(body.to_vec()).unwrap(),
+                ));
+                (
+                    StatusCode::CREATED,
+                    Json(serde_json::json!({"ok": true, "id": "note.md", "rev": "1-abc"})),
+                )
+            }
+        })))
+        .await;
+    let state = Arc::new(common::app_state(
+        &upstream,
+        ProxyConfig {
+            write_queue_capacity: 1,
+            ..ProxyConfig::default()
+        },
+    ));
+    let app = create_router(Arc::clone(&state));
+    let put = |id: &str| {
+        Request::put(format!("/db/vault/{}", id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"content":"offline edit"}"#))
+            .unwrap()
+    };
+
+    // CouchDBが停止中の書き込みはキューに入れて202を返す
+    state
+        .health_state
+        .update_couchdb_status(false, Some("connection refused".to_string()))
+        .await;
+    let response = app.clone().oneshot(put("note.md")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["queued"], true);
+    assert_eq!(json["id"], 1);
+    assert_eq!(state.write_queue.len(), 1);
+    assert!(received.lock().unwrap().is_empty());
+
+    // 上限を超えた書き込みはキューに入れずに転送を試みる
+    let response = app.clone().oneshot(put("other.md")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(state.write_queue.len(), 1);
+
+    // 復旧後に受け付けた書き込みを再送する
+    state.health_state.update_couchdb_status(true, None).await;
+    let summary = replay_queued_writes(&state).await;
+    assert_eq!(summary.replayed, 1);
+    assert!(state.write_queue.is_empty());
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(
+        received[1],
+        (
+            "PUT".to_string(),
+            "/vault/note.md".to_string(),
+            r#"{"content":"offline edit"}"#.to_string()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_conflicting_queued_write_is_kept_for_inspection() {
+    // 停止中に別のクライアントが更新していたため、再送が409になるアップストリーム
+    let upstream = common::spawn_upstream(Router::new().fallback(any(|| async {
+        (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": "conflict", "reason": "Document update conflict."})),
+        )
+    })))
+    .await;
+    let state = Arc::new(common::app_state(
+        &upstream,
+        ProxyConfig {
+            write_queue_capacity: 1,
+            ..ProxyConfig::default()
+        },
+    ));
+    let app = create_router(Arc::clone(&state));
+
+    state
+        .health_state
+        .update_couchdb_status(false, Some("connection refused".to_string()))
+        .await;
+    let response = app
+        .clone()
+        .oneshot(
+            Request::put("/db/vault/note.md")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"content":"offline edit"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    // 競合した書き込みは破棄せず、キューから競合リストに移す
+    state.health_state.update_couchdb_status(true, None).await;
+    let summary = replay_queued_writes(&state).await;
+    assert_eq!(summary.conflicts, 1);
+    assert!(state.write_queue.is_empty());
+
+    let admin_get = || {
+        Request::get("/api/write-queue")
+            .header(header::AUTHORIZATION, common::admin_bearer())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = app.clone().oneshot(admin_get()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["pending"], serde_json::json!([]));
+    assert_eq!(json["conflicts"][0]["id"], 1);
+    assert_eq!(json["conflicts"][0]["path"], "/vault/note.md");
+    assert_eq!(
+        json["conflicts"][0]["body"],
+        r#"{"content":"offline edit"}"#
+    );
+
+    // 解消後に管理APIで取り除く
+    let remove = || {
+        Request::delete("/api/write-queue/conflicts/1")
+            .header(header::AUTHORIZATION, common::admin_bearer())
+            .body(Body::empty())
+            .unwrap()
+    };
+    let response = app.clone().oneshot(remove()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(state.write_queue.conflicts().is_empty());
+    let response = app.oneshot(remove()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}